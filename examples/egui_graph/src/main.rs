@@ -61,6 +61,7 @@ impl Render {
             &tgpu::InstanceCreateInfo {
                 app_name: "egui RenderGraph",
                 engine_name: "Example Engine",
+                debug_printf: false,
             },
             display,
         )?;
@@ -91,6 +92,8 @@ impl Render {
             },
             preferred_image_count: 3,
             preferred_present_mode: tgpu::PresentModeKHR::MAILBOX,
+            require_present_mode: false,
+            storage: false,
             format_selector: Box::new(|formats| {
                 formats
                     .iter()
@@ -46,13 +46,20 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let instance = tgpu::Instance::new(&tgpu::InstanceCreateInfo {
         app_name: "Headless MatMul",
         engine_name: "Example Engine",
+        debug_printf: false,
     })?;
 
     let adapters = instance.adapters(&[])?.collect::<Vec<_>>();
     let adapter = adapters[0].clone();
 
+    // Bounds-safe buffer accesses while iterating on the shader's index math.
+    let features = tgpu::DeviceFeatures::default()
+        .robust_buffer_access(cfg!(debug_assertions) && adapter.features().robust_buffer_access);
+
     let (device, mut queues) = instance.request_device(
-        &tgpu::DeviceCreateInfo::default(),
+        &tgpu::DeviceCreateInfo::default()
+            .features(features)
+            .compute_only(true),
         adapter,
         &[tgpu::QueueRequest {
             required_flags: tgpu::QueueFlags::COMPUTE | tgpu::QueueFlags::TRANSFER,
@@ -70,25 +77,28 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         ..Default::default()
     };
 
-    let buf_a = device.create_buffer(&tgpu::BufferDesc {
-        label: Some(tgpu::Label::Name("A")),
-        size: std::mem::size_of::<f32>() * len_a,
-        ..storage_buffer.clone()
-    })?;
-    let buf_b = device.create_buffer(&tgpu::BufferDesc {
-        label: Some(tgpu::Label::Name("B")),
-        size: std::mem::size_of::<f32>() * len_b,
-        ..storage_buffer.clone()
-    })?;
+    let buf_a = device.create_buffer_init(
+        &queue,
+        &tgpu::BufferDesc {
+            label: Some(tgpu::Label::Name("A")),
+            ..storage_buffer.clone()
+        },
+        bytemuck::cast_slice(&host_a),
+    )?;
+    let buf_b = device.create_buffer_init(
+        &queue,
+        &tgpu::BufferDesc {
+            label: Some(tgpu::Label::Name("B")),
+            ..storage_buffer.clone()
+        },
+        bytemuck::cast_slice(&host_b_t),
+    )?;
     let buf_c = device.create_buffer(&tgpu::BufferDesc {
         label: Some(tgpu::Label::Name("C")),
         size: std::mem::size_of::<f32>() * len_c,
         ..storage_buffer
     })?;
 
-    buf_a.write_slice(&host_a);
-    buf_b.write_slice(&host_b_t);
-
     let bindless = device.create_bindless_heap(&tgpu::BindlessInfo {
         max_read_buffers: 2,
         max_rw_buffers: 1,
@@ -109,6 +119,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         push_constant_size: Some(std::mem::size_of::<Push>() as u32),
         descriptor_layouts: &[bindless.layout()],
         cache: None,
+        dispatch_base: false,
+        validate_layouts: false,
     });
 
     let tile: u32 = 16;
@@ -132,12 +144,11 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     queue.submit(tgpu::SubmitInfo {
         records: &[rec.finish()],
         ..Default::default()
-    });
+    })?;
 
     device.wait_idle();
 
-    let mut host_c = vec![0.0f32; len_c];
-    buf_c.read_slice(&mut host_c);
+    let host_c = buf_c.mapped_slice::<f32>()?.to_vec();
 
     // Tiny correctness spot-check against CPU on a few random entries
     let mut max_abs_err = 0.0f32;
@@ -53,6 +53,7 @@ pub struct Render {
     particles: Vec<Particle>,
     particle_buffer: tgpu::Buffer,
     present_image: tgpu::ViewImage,
+    present_sampler: tgpu::Sampler,
     bindless: tgpu::BindlessHeap,
 
     present_pipeline: tgpu::RenderPipeline,
@@ -71,6 +72,7 @@ impl Render {
             &tgpu::InstanceCreateInfo {
                 app_name: "Particles",
                 engine_name: "Example Engine",
+                debug_printf: false,
             },
             display,
         )?;
@@ -102,6 +104,8 @@ impl Render {
             },
             preferred_image_count: 3,
             preferred_present_mode: tgpu::PresentModeKHR::MAILBOX,
+            require_present_mode: false,
+            storage: false,
             format_selector: Box::new(|formats| {
                 formats
                     .iter()
@@ -143,6 +147,11 @@ impl Render {
 
         particle_buffer.write_slice(&particles);
 
+        let present_sampler = device.try_create_sampler(&tgpu::SamplerCreateInfo {
+            label: Some(tgpu::Label::Name("Present Sampler")),
+            ..Default::default()
+        })?;
+
         let present_image = device.create_view_image(&tgpu::ViewImageDesc {
             image: tgpu::ImageDesc {
                 label: Some(tgpu::Label::Name("present image")),
@@ -159,10 +168,7 @@ impl Render {
                     | tgpu::ImageUses::SAMPLED,
                 ..Default::default()
             },
-            sampler: Some(tgpu::SamplerCreateInfo {
-                label: Some(tgpu::Label::Name("Present Sampler")),
-                ..Default::default()
-            }),
+            shared_sampler: Some(&present_sampler),
             ..Default::default()
         })?;
 
@@ -181,7 +187,7 @@ impl Render {
             &present_image.view,
             vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
         );
-        let present_sampler_handle = bindless.add_sampler(present_image.sampler.as_ref().unwrap());
+        let present_sampler_handle = bindless.add_sampler(&present_sampler);
 
         const SHADER: &str = include_str!("./shader.slang");
 
@@ -195,6 +201,8 @@ impl Render {
             push_constant_size: Some(std::mem::size_of::<PushConstants>() as u32),
             descriptor_layouts: &[bindless.layout()],
             cache: None,
+            dispatch_base: false,
+            validate_layouts: false,
         });
 
         let clear_pipeline = device.create_compute_pipeline(&tgpu::ComputePipelineInfo {
@@ -203,6 +211,8 @@ impl Render {
             descriptor_layouts: &[bindless.layout()],
             push_constant_size: Some(std::mem::size_of::<PushConstants>() as u32),
             cache: None,
+            dispatch_base: false,
+            validate_layouts: false,
         });
 
         let present_pipeline = device.create_render_pipeline(&tgpu::RenderPipelineInfo {
@@ -211,14 +221,30 @@ impl Render {
             fragment_shader: shader.entry("fragmentMain"),
             color_formats: &[swapchain.format()],
             depth_format: None,
+            depth_test: false,
+            depth_write: false,
+            depth_compare: vk::CompareOp::ALWAYS,
+            stencil_format: None,
+            stencil_test: false,
+            stencil_front: vk::StencilOpState::default(),
+            stencil_back: vk::StencilOpState::default(),
             descriptor_layouts: &[bindless.layout()],
             push_constant_size: Some(std::mem::size_of::<PushConstants>() as u32),
             blend_states: None,
+            dynamic_blend_constants: false,
+            color_write_masks: None,
             vertex_input_state: None,
             topology: tgpu::PrimitiveTopology::TRIANGLE_LIST,
+            primitive_restart: false,
             polygon: tgpu::PolygonMode::FILL,
             cull: tgpu::CullModeFlags::BACK,
             front_face: tgpu::FrontFace::COUNTER_CLOCKWISE,
+            view_mask: 0,
+            local_read: None,
+            viewport_count: 1,
+            allow_derivatives: false,
+            base: None,
+            validate_layouts: false,
         });
 
         let pc = PushConstants {
@@ -241,6 +267,7 @@ impl Render {
             particles,
             particle_buffer,
             present_image,
+            present_sampler,
             bindless,
             present_pipeline,
             compute_pipeline,
@@ -281,6 +308,10 @@ impl Render {
         );
         recorder.push_compute_constants(&self.compute_pipeline, self.pc);
         recorder.dispatch(particle_groups as u32, 1, 1);
+        recorder.barrier_between_passes(
+            tgpu::BufferAccessTransition::compute_storage_read_write(),
+            tgpu::BufferAccessTransition::compute_storage_read_write(),
+        );
 
         let width = self.swapchain.extent().width.div_ceil(16);
         let height = self.swapchain.extent().height.div_ceil(16);
@@ -318,16 +349,10 @@ impl Render {
         let color = (frame_number / 120.0).sin().abs();
         let invert_color = 1.0 - color;
 
-        let attachment = vk::RenderingAttachmentInfo::default()
-            .image_view(self.swapchain.view(frame).inner.handle)
-            .image_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
-            .load_op(vk::AttachmentLoadOp::CLEAR)
-            .store_op(vk::AttachmentStoreOp::STORE)
-            .clear_value(vk::ClearValue {
-                color: vk::ClearColorValue {
-                    float32: [0.0, color, invert_color, 1.0],
-                },
-            });
+        let attachment = tgpu::ColorAttachment::clear(
+            self.swapchain.view(frame),
+            [0.0, color, invert_color, 1.0],
+        );
 
         recorder.bind_render_pipeline(&self.present_pipeline);
         recorder.bind_render_descriptor_set(
@@ -341,6 +366,7 @@ impl Render {
         recorder.begin_render(
             &tgpu::RenderInfo {
                 colors: &[attachment],
+                attachment_views: &[self.swapchain.view(frame)],
                 area: vk::Rect2D {
                     extent: self.swapchain.extent(),
                     ..Default::default()
@@ -391,7 +417,7 @@ impl Render {
             signal_binary: &[finished_semaphore],
             fence: Some(self.swapchain.inner.fence(frame)),
             ..Default::default()
-        });
+        })?;
 
         match self.swapchain.present(&self.queue, frame) {
             Ok(true) | Err(_) => {
@@ -418,31 +444,19 @@ impl Render {
         let _ = self.swapchain.recreate();
         self.pc.window = [size.width, size.height];
 
-        let present_image = self
-            .device
-            .create_view_image(&tgpu::ViewImageDesc {
-                image: tgpu::ImageDesc {
-                    label: Some(tgpu::Label::Name("present image")),
-                    format: self.swapchain.format(),
-                    extent: vk::Extent3D {
-                        width: self.swapchain.extent().width,
-                        height: self.swapchain.extent().height,
-                        depth: 1,
-                    },
-                    usage: tgpu::ImageUses::COPY_SRC
-                        | tgpu::ImageUses::COPY_DST
-                        | tgpu::ImageUses::STORAGE
-                        | tgpu::ImageUses::COLOR_ATTACHMENT
-                        | tgpu::ImageUses::SAMPLED,
-                    ..Default::default()
+        // `recreate` waited for the device to go idle, so the old image can be dropped right away.
+        let _old = self
+            .present_image
+            .resize(
+                &self.device,
+                vk::Extent3D {
+                    width: self.swapchain.extent().width,
+                    height: self.swapchain.extent().height,
+                    depth: 1,
                 },
-                sampler: Some(tgpu::SamplerCreateInfo {
-                    label: Some(tgpu::Label::Name("Present Sampler")),
-                    ..Default::default()
-                }),
-                ..Default::default()
-            })
-            .expect("Create Present Image");
+            )
+            .expect("Resize Present Image");
+        let present_image = &self.present_image;
 
         self.bindless.update_storage_image(
             self.pc.output_image,
@@ -454,12 +468,6 @@ impl Render {
             &present_image.view,
             vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
         );
-        self.bindless.update_sampler(
-            self.pc.present_sampler,
-            present_image.sampler.as_ref().unwrap(),
-        );
-
-        self.present_image = present_image;
     }
 }
 
@@ -55,6 +55,7 @@ pub struct Render {
     particles: Vec<Particle>,
     particle_buffer: tgpu::Buffer,
     present_image: tgpu::ViewImage,
+    present_sampler: tgpu::Sampler,
     bindless: tgpu::BindlessHeap,
     present_pipeline: tgpu::RenderPipeline,
     compute_pipeline: tgpu::ComputePipeline,
@@ -72,6 +73,7 @@ impl Render {
             &tgpu::InstanceCreateInfo {
                 app_name: "Particles GPU Graph",
                 engine_name: "Example Engine",
+                debug_printf: false,
             },
             display,
         )?;
@@ -112,6 +114,8 @@ impl Render {
             },
             preferred_image_count: 3,
             preferred_present_mode: tgpu::PresentModeKHR::MAILBOX,
+            require_present_mode: false,
+            storage: false,
             format_selector: Box::new(|formats| {
                 formats
                     .iter()
@@ -151,7 +155,11 @@ impl Render {
         })?;
         particle_buffer.write_slice(&particles);
 
-        let present_image = create_present_image(&device, &swapchain)?;
+        let present_sampler = device.try_create_sampler(&tgpu::SamplerCreateInfo {
+            label: Some(tgpu::Label::Name("Present Sampler")),
+            ..Default::default()
+        })?;
+        let present_image = create_present_image(&device, &swapchain, &present_sampler)?;
 
         let bindless = device.create_bindless_heap(&tgpu::BindlessInfo {
             max_rw_buffers: 1,
@@ -168,7 +176,7 @@ impl Render {
             &present_image.view,
             vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
         );
-        let present_sampler_handle = bindless.add_sampler(present_image.sampler.as_ref().unwrap());
+        let present_sampler_handle = bindless.add_sampler(&present_sampler);
 
         let shader = device
             .create_shader(None, tgpu::ShaderSource::Slang(SHADER.as_bytes()))
@@ -180,6 +188,8 @@ impl Render {
             push_constant_size: Some(std::mem::size_of::<PushConstants>() as u32),
             descriptor_layouts: &[bindless.layout()],
             cache: None,
+            dispatch_base: false,
+            validate_layouts: false,
         });
 
         let clear_pipeline = device.create_compute_pipeline(&tgpu::ComputePipelineInfo {
@@ -188,6 +198,8 @@ impl Render {
             descriptor_layouts: &[bindless.layout()],
             push_constant_size: Some(std::mem::size_of::<PushConstants>() as u32),
             cache: None,
+            dispatch_base: false,
+            validate_layouts: false,
         });
 
         let present_pipeline = device.create_render_pipeline(&tgpu::RenderPipelineInfo {
@@ -196,14 +208,30 @@ impl Render {
             fragment_shader: shader.entry("fragmentMain"),
             color_formats: &[swapchain.format()],
             depth_format: None,
+            depth_test: false,
+            depth_write: false,
+            depth_compare: vk::CompareOp::ALWAYS,
+            stencil_format: None,
+            stencil_test: false,
+            stencil_front: vk::StencilOpState::default(),
+            stencil_back: vk::StencilOpState::default(),
             descriptor_layouts: &[bindless.layout()],
             push_constant_size: Some(std::mem::size_of::<PushConstants>() as u32),
             blend_states: None,
+            dynamic_blend_constants: false,
+            color_write_masks: None,
             vertex_input_state: None,
             topology: tgpu::PrimitiveTopology::TRIANGLE_LIST,
+            primitive_restart: false,
             polygon: tgpu::PolygonMode::FILL,
             cull: tgpu::CullModeFlags::BACK,
             front_face: tgpu::FrontFace::COUNTER_CLOCKWISE,
+            view_mask: 0,
+            local_read: None,
+            viewport_count: 1,
+            allow_derivatives: false,
+            base: None,
+            validate_layouts: false,
         });
 
         let pc = PushConstants {
@@ -227,6 +255,7 @@ impl Render {
             particles,
             particle_buffer,
             present_image,
+            present_sampler,
             bindless,
             present_pipeline,
             compute_pipeline,
@@ -401,7 +430,8 @@ impl Render {
         self.pc.window = [size.width, size.height];
 
         let present_image =
-            create_present_image(&self.device, &self.swapchain).expect("Create Present Image");
+            create_present_image(&self.device, &self.swapchain, &self.present_sampler)
+                .expect("Create Present Image");
 
         self.bindless.update_storage_image(
             self.pc.output_image,
@@ -413,10 +443,6 @@ impl Render {
             &present_image.view,
             vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
         );
-        self.bindless.update_sampler(
-            self.pc.present_sampler,
-            present_image.sampler.as_ref().unwrap(),
-        );
 
         self.present_image = present_image;
         self.graph_cache.clear();
@@ -426,6 +452,7 @@ impl Render {
 fn create_present_image(
     device: &tgpu::Device,
     swapchain: &tgpu::Swapchain,
+    sampler: &tgpu::Sampler,
 ) -> Result<tgpu::ViewImage, tgpu::GPUError> {
     device.create_view_image(&tgpu::ViewImageDesc {
         image: tgpu::ImageDesc {
@@ -443,10 +470,7 @@ fn create_present_image(
                 | tgpu::ImageUses::SAMPLED,
             ..Default::default()
         },
-        sampler: Some(tgpu::SamplerCreateInfo {
-            label: Some(tgpu::Label::Name("Present Sampler")),
-            ..Default::default()
-        }),
+        shared_sampler: Some(sampler),
         ..Default::default()
     })
 }
@@ -84,6 +84,7 @@ impl Render {
             &tgpu::InstanceCreateInfo {
                 app_name: "Simple Buffer Triangle",
                 engine_name: "Example Engine",
+                debug_printf: false,
             },
             display,
         )?;
@@ -149,6 +150,8 @@ impl Render {
             },
             preferred_image_count: 3,
             preferred_present_mode: tgpu::PresentModeKHR::MAILBOX,
+            require_present_mode: false,
+            storage: false,
             format_selector: Box::new(|formats| {
                 formats
                     .iter()
@@ -230,16 +233,10 @@ impl Render {
         let color = (frame_number / 120.0).sin().abs();
         let invert_color = 1.0 - color;
 
-        let attachment = vk::RenderingAttachmentInfo::default()
-            .image_view(self.swapchain.view(frame).inner.handle)
-            .image_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
-            .load_op(vk::AttachmentLoadOp::CLEAR)
-            .store_op(vk::AttachmentStoreOp::STORE)
-            .clear_value(vk::ClearValue {
-                color: vk::ClearColorValue {
-                    float32: [0.0, invert_color, color, 0.5],
-                },
-            });
+        let attachment = tgpu::ColorAttachment::clear(
+            self.swapchain.view(frame),
+            [0.0, invert_color, color, 0.5],
+        );
 
         recorder.bind_render_pipeline(&self.pipeline);
         recorder.bind_render_descriptor_set(self.bindless.descriptor_set(), &self.pipeline, 0, &[]);
@@ -248,6 +245,7 @@ impl Render {
         recorder.begin_render(
             &tgpu::RenderInfo {
                 colors: &[attachment],
+                attachment_views: &[self.swapchain.view(frame)],
                 area: vk::Rect2D {
                     extent: self.swapchain.extent(),
                     ..Default::default()
@@ -298,7 +296,7 @@ impl Render {
             signal_binary: &[finished_semaphore],
             fence: Some(self.swapchain.inner.fence(frame)),
             ..Default::default()
-        });
+        })?;
 
         match self.swapchain.present(&self.queue, frame) {
             Ok(true) | Err(_) => {
@@ -1,12 +1,10 @@
-use raw_window_handle::{HasDisplayHandle, HasWindowHandle};
+use tgpu::app::{AppContext, FrameContext, RenderApp, RenderAppInfo, winit};
 use tgpu::ash::vk;
 
 use winit::{
-    application::ApplicationHandler,
     event::{KeyEvent, WindowEvent},
-    event_loop::{ActiveEventLoop, EventLoop},
+    event_loop::ActiveEventLoop,
     keyboard::{KeyCode, PhysicalKey},
-    window::Window,
 };
 
 const TRIANGLE_SHADER_SLANG: &str = r#"
@@ -41,274 +39,90 @@ float4 fmain(VSOutput input) : SV_Target0 {
 }
 "#;
 
-#[allow(unused)]
-pub struct Render {
-    window: Window,
-    instance: tgpu::Instance,
-    device: tgpu::Device,
-    queue: tgpu::Queue,
-    swapchain: tgpu::Swapchain,
-    buffer: tgpu::Buffer,
+struct Triangle {
     pipeline: tgpu::RenderPipeline,
-    frame_count: usize,
 }
 
-impl Render {
-    pub fn new(window: Window) -> Result<Render, tgpu::GPUError> {
-        let display = window.display_handle().unwrap().as_raw();
-
-        let instance = tgpu::Instance::new_with_display(
-            &tgpu::InstanceCreateInfo {
-                app_name: "Triangle",
-                engine_name: "Example Engine",
-            },
-            display,
-        )?;
-
-        let adapters = instance.adapters(&[])?.collect::<Vec<_>>();
-        // println!("adapters: {:#?}", adapters);
-
-        let adapter = adapters[0].clone();
-
-        let (device, mut queues) = instance.request_device(
-            &tgpu::DeviceCreateInfo::default(),
-            adapter,
-            &[tgpu::QueueRequest {
-                required_flags: tgpu::QueueFlags::GRAPHICS,
-                exclude_flags: tgpu::QueueFlags::empty(),
-                strict: false,
-                allow_fallback_share: true,
-            }],
+impl RenderApp for Triangle {
+    fn init(ctx: &AppContext) -> Result<Self, tgpu::GPUError> {
+        let shader = ctx.device.create_shader(
+            Some(tgpu::Label::Name("shader")),
+            tgpu::ShaderSource::Slang(TRIANGLE_SHADER_SLANG.as_bytes()),
         )?;
 
-        let queue = queues.next().unwrap();
-
-        let size = window.inner_size();
-
-        // TODO: use this buffer
-        let buffer = device.create_buffer(&tgpu::BufferDesc {
-            label: Some(tgpu::Label::Name("test")),
-            size: 420,
-            usage: tgpu::BufferUses::COPY_DST,
-            memory: tgpu::MemoryPreset::Dynamic,
-            host_access: tgpu::HostAccess::WriteSequential,
-            ..Default::default()
-        })?;
-
-        let swapchain = device.create_swapchain(&tgpu::SwapchainCreateInfo {
-            display: window.display_handle().unwrap().as_raw(),
-            window: window.window_handle().unwrap().as_raw(),
-            preferred_extent: vk::Extent2D {
-                width: size.width,
-                height: size.height,
-            },
-            preferred_image_count: 3,
-            preferred_present_mode: tgpu::PresentModeKHR::MAILBOX,
-            format_selector: Box::new(|formats| {
-                formats
-                    .iter()
-                    .find(|f| {
-                        f.format == tgpu::Format::B8G8R8A8_SRGB
-                            && f.color_space == tgpu::ColorSpaceKHR::SRGB_NONLINEAR
-                    })
-                    .copied()
-                    .unwrap_or(formats[0])
-            }),
-        })?;
-
-        let shader = device
-            .create_shader(
-                Some(tgpu::Label::Name("shader")),
-                tgpu::ShaderSource::Slang(TRIANGLE_SHADER_SLANG.as_bytes()),
-            )
-            .expect("Shader");
-
-        let pipeline = device.create_render_pipeline(&tgpu::RenderPipelineInfo {
-            label: Some(tgpu::Label::Name("Present Pipeline")),
-            vertex_shader: shader.entry("vmain"),
-            fragment_shader: shader.entry("fmain"),
-            cull: tgpu::CullModeFlags::BACK,
-            topology: tgpu::PrimitiveTopology::TRIANGLE_LIST,
-            polygon: tgpu::PolygonMode::FILL,
-            front_face: vk::FrontFace::CLOCKWISE,
-            color_formats: &[swapchain.format()],
-            ..Default::default()
-        });
-
-        let new = Self {
-            window,
-            instance,
-            device,
-            queue,
-            swapchain,
-            buffer,
-            pipeline,
-            frame_count: 0,
-        };
-
-        Ok(new)
-    }
-
-    fn render_frame(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        let frame = self.swapchain.acquire_next(None)?;
-        log::trace!("Start Frame {:?}", frame.index);
-        if frame.suboptimal {
-            log::debug!("recreate swapchain");
-            let size = self.window.inner_size();
-            self.swapchain.set_preferred_extent(vk::Extent2D {
-                width: size.width,
-                height: size.height,
-            });
-            let _ = self.swapchain.recreate();
-            return Ok(());
-        }
-        let mut recorder = self.queue.record();
-
-        recorder.image_transition(
-            self.swapchain.image(frame),
-            tgpu::ImageTransition {
-                from: tgpu::ImageLayoutTransition::UNDEFINED,
-                to: tgpu::ImageLayoutTransition::COLOR,
-                aspect: vk::ImageAspectFlags::COLOR,
+        let pipeline = ctx
+            .device
+            .create_render_pipeline(&tgpu::RenderPipelineInfo {
+                label: Some(tgpu::Label::Name("Present Pipeline")),
+                vertex_shader: shader.entry("vmain"),
+                fragment_shader: shader.entry("fmain"),
+                cull: tgpu::CullModeFlags::BACK,
+                topology: tgpu::PrimitiveTopology::TRIANGLE_LIST,
+                polygon: tgpu::PolygonMode::FILL,
+                front_face: vk::FrontFace::CLOCKWISE,
+                color_formats: &[ctx.swapchain.format()],
                 ..Default::default()
-            },
-        );
+            });
 
-        let frame_number = self.frame_count as f32;
+        Ok(Self { pipeline })
+    }
 
-        let color = (frame_number / 120.0).sin().abs();
+    fn render(&mut self, frame: FrameContext<'_>) {
+        let color = (frame.frame_number as f32 / 120.0).sin().abs();
         let invert_color = 1.0 - color;
 
-        let attachment = vk::RenderingAttachmentInfo::default()
-            .image_view(self.swapchain.view(frame).inner.handle)
-            .image_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
-            .load_op(vk::AttachmentLoadOp::CLEAR)
-            .store_op(vk::AttachmentStoreOp::STORE)
-            .clear_value(vk::ClearValue {
-                color: vk::ClearColorValue {
-                    float32: [0.0, invert_color, color, 0.5],
-                },
-            });
+        let attachment = tgpu::ColorAttachment::clear(frame.view, [0.0, invert_color, color, 0.5]);
 
-        recorder.bind_render_pipeline(&self.pipeline);
+        frame.recorder.bind_render_pipeline(&self.pipeline);
 
-        recorder.begin_render(
+        frame.recorder.begin_render(
             &tgpu::RenderInfo {
                 colors: &[attachment],
+                attachment_views: &[frame.view],
                 area: vk::Rect2D {
-                    extent: self.swapchain.extent(),
+                    extent: frame.extent,
                     ..Default::default()
                 },
                 ..Default::default()
             },
             |recorder| {
-                let viewport = vk::Viewport {
+                recorder.viewport(vk::Viewport {
                     x: 0.0,
                     y: 0.0,
-                    width: self.swapchain.extent().width as f32,
-                    height: self.swapchain.extent().height as f32,
+                    width: frame.extent.width as f32,
+                    height: frame.extent.height as f32,
                     min_depth: 0.0,
                     max_depth: 1.0,
-                };
-
-                let scissor = vk::Rect2D {
-                    extent: self.swapchain.extent(),
+                });
+                recorder.scissor(vk::Rect2D {
+                    extent: frame.extent,
                     ..Default::default()
-                };
-
-                recorder.viewport(viewport);
-                recorder.scissor(scissor);
+                });
 
                 recorder.draw(0..3, 0..1);
             },
         );
-
-        recorder.image_transition(
-            self.swapchain.image(frame),
-            tgpu::ImageTransition {
-                from: tgpu::ImageLayoutTransition::COLOR,
-                to: tgpu::ImageLayoutTransition::PRESENT,
-                aspect: vk::ImageAspectFlags::COLOR,
-                ..Default::default()
-            },
-        );
-
-        let available_semaphore = self.swapchain.inner.available_semaphore(frame);
-        let finished_semaphore = self.swapchain.inner.finished_semaphore(frame);
-
-        self.queue.submit(tgpu::SubmitInfo {
-            records: &[recorder.finish()],
-            wait_binary: &[(
-                available_semaphore,
-                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
-            )],
-            signal_binary: &[finished_semaphore],
-            fence: Some(self.swapchain.inner.fence(frame)),
-            ..Default::default()
-        });
-
-        match self.swapchain.present(&self.queue, frame) {
-            Ok(true) | Err(_) => {
-                log::debug!("recreate swapchain");
-                let size = self.window.inner_size();
-                self.swapchain.set_preferred_extent(vk::Extent2D {
-                    width: size.width,
-                    height: size.height,
-                });
-                let _ = self.swapchain.recreate();
-                return Ok(());
-            }
-            _ => {}
-        }
-
-        self.frame_count += 1;
-
-        log::trace!("Finish Frame {:?}", frame.index);
-
-        Ok(())
-    }
-}
-
-#[derive(Default)]
-pub struct App {
-    render: Option<Render>,
-}
-
-impl ApplicationHandler for App {
-    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-        let window = event_loop
-            .create_window(Window::default_attributes())
-            .expect("Acquire Window");
-
-        window.request_redraw();
-        let render = Render::new(window).expect("Create Render");
-        self.render = Some(render);
     }
 
     fn window_event(
         &mut self,
+        _ctx: &AppContext,
         event_loop: &ActiveEventLoop,
-        _window_id: winit::window::WindowId,
-        event: WindowEvent,
-    ) {
-        match event {
-            WindowEvent::CloseRequested => event_loop.exit(),
-            WindowEvent::KeyboardInput {
-                event:
-                    KeyEvent {
-                        physical_key: PhysicalKey::Code(KeyCode::Escape),
-                        ..
-                    },
-                ..
-            } => event_loop.exit(),
-            WindowEvent::RedrawRequested => {
-                if let Some(render) = &mut self.render {
-                    let _ = render.render_frame();
-                    render.window.request_redraw();
-                }
-            }
-            _ => (),
+        event: &WindowEvent,
+    ) -> bool {
+        if let WindowEvent::KeyboardInput {
+            event:
+                KeyEvent {
+                    physical_key: PhysicalKey::Code(KeyCode::Escape),
+                    ..
+                },
+            ..
+        } = event
+        {
+            event_loop.exit();
+            return true;
         }
+        false
     }
 }
 
@@ -317,9 +131,10 @@ fn main() {
         .filter_module("naga", log::LevelFilter::Warn)
         .init();
 
-    let event_loop = EventLoop::new().expect("acquire event loop");
-    let mut app = App::default();
-    if let Err(err) = event_loop.run_app(&mut app) {
+    if let Err(err) = tgpu::app::run::<Triangle>(RenderAppInfo {
+        title: "Triangle",
+        ..Default::default()
+    }) {
         eprintln!("run app failed: {err}");
     }
 }
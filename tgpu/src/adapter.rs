@@ -2,7 +2,10 @@ use std::{ffi::CStr, fmt, sync::Arc};
 
 use ash::vk;
 
-use crate::{GPUError, raw::InstanceImpl};
+use crate::{
+    GPUError,
+    raw::{InstanceImpl, RawInstance},
+};
 
 #[derive(Debug, Clone)]
 pub struct Adapter {
@@ -54,6 +57,23 @@ pub struct AdapterFeatures {
     pub descriptor_indexing: AdapterDescriptorIndexingFeatures,
     pub buffer_device_address: bool,
     pub shader_int64: bool,
+    pub fragment_stores_and_atomics: bool,
+    pub draw_indirect_count: bool,
+    pub multiview: bool,
+    pub sparse_binding: bool,
+    pub sparse_residency_image_2d: bool,
+    pub dynamic_rendering_local_read: bool,
+    pub device_fault: bool,
+    pub multi_viewport: bool,
+    /// Semaphores can be exported as OS handles; see `Device::create_exportable_semaphore`.
+    pub external_semaphore: bool,
+    /// Buffer and image memory can be shared as OS handles; see `BufferUses::EXTERNAL`.
+    pub external_memory: bool,
+    pub robust_buffer_access: bool,
+    /// `VK_EXT_robustness2`'s `robustBufferAccess2`.
+    pub robust_buffer_access2: bool,
+    /// `VK_KHR_shader_non_semantic_info`, needed for shader debug printf.
+    pub shader_non_semantic_info: bool,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
@@ -102,6 +122,7 @@ pub struct AdapterLimits {
     pub max_storage_buffer_range: u32,
     pub max_per_stage_resources: u32,
     pub max_per_stage_descriptor_sampled_images: u32,
+    pub max_viewports: u32,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -134,6 +155,57 @@ impl AdapterInfo {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryHeap {
+    pub size: vk::DeviceSize,
+    pub device_local: bool,
+    pub flags: vk::MemoryHeapFlags,
+}
+
+impl From<vk::MemoryHeap> for MemoryHeap {
+    fn from(heap: vk::MemoryHeap) -> Self {
+        Self {
+            size: heap.size,
+            device_local: heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL),
+            flags: heap.flags,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryType {
+    /// Index into `Adapter::memory_heaps`.
+    pub heap_index: u32,
+    pub device_local: bool,
+    pub host_visible: bool,
+    pub host_coherent: bool,
+    pub host_cached: bool,
+    pub lazily_allocated: bool,
+    pub flags: vk::MemoryPropertyFlags,
+}
+
+impl MemoryType {
+    /// Device-local memory the host can map directly, e.g. with resizable BAR.
+    pub fn is_device_local_host_visible(&self) -> bool {
+        self.device_local && self.host_visible
+    }
+}
+
+impl From<vk::MemoryType> for MemoryType {
+    fn from(ty: vk::MemoryType) -> Self {
+        let flags = ty.property_flags;
+        Self {
+            heap_index: ty.heap_index,
+            device_local: flags.contains(vk::MemoryPropertyFlags::DEVICE_LOCAL),
+            host_visible: flags.contains(vk::MemoryPropertyFlags::HOST_VISIBLE),
+            host_coherent: flags.contains(vk::MemoryPropertyFlags::HOST_COHERENT),
+            host_cached: flags.contains(vk::MemoryPropertyFlags::HOST_CACHED),
+            lazily_allocated: flags.contains(vk::MemoryPropertyFlags::LAZILY_ALLOCATED),
+            flags,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct RankedAdapter {
     pub adapter: Adapter,
@@ -142,19 +214,30 @@ pub struct RankedAdapter {
 
 pub type RawAdapter = Arc<AdapterImpl>;
 
-#[derive(Debug)]
 pub struct AdapterImpl {
     pub handle: vk::PhysicalDevice,
     pub properties: vk::PhysicalDeviceProperties,
+    pub memory_properties: vk::PhysicalDeviceMemoryProperties,
     pub queue_properties: Arc<[vk::QueueFamilyProperties]>,
     pub features: AdapterFeatures,
     pub formats: Arc<[(vk::Format, vk::FormatProperties)]>,
     pub info: AdapterInfo,
+    pub instance: RawInstance,
+}
+
+impl fmt::Debug for AdapterImpl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AdapterImpl")
+            .field("handle", &self.handle)
+            .field("features", &self.features)
+            .field("info", &self.info)
+            .finish_non_exhaustive()
+    }
 }
 
 impl AdapterImpl {
     pub unsafe fn new(
-        instance: &InstanceImpl,
+        instance: &RawInstance,
         pdev: vk::PhysicalDevice,
         formats: &[vk::Format],
     ) -> Self {
@@ -163,14 +246,18 @@ impl AdapterImpl {
         let queue_properties = unsafe { instance.queue_properties(pdev) };
         let format_properties = unsafe { instance.format_properties(pdev, formats) };
         let info = adapter_info_from_properties(&properties, queue_properties.len());
+        let memory_properties =
+            unsafe { instance.handle.get_physical_device_memory_properties(pdev) };
 
         Self {
             handle: pdev,
             properties,
+            memory_properties,
             queue_properties: Arc::from(queue_properties),
             features,
             formats: Arc::from(format_properties),
             info,
+            instance: instance.clone(),
         }
     }
 
@@ -191,9 +278,26 @@ impl AdapterImpl {
     pub unsafe fn handle(&self) -> vk::PhysicalDevice {
         self.handle
     }
+
+    /// Format properties from the cache filled at enumeration, queried on demand otherwise.
+    pub fn format_properties(&self, format: vk::Format) -> vk::FormatProperties {
+        if let Some((_, props)) = self.formats.iter().find(|(f, _)| *f == format) {
+            return *props;
+        }
+        unsafe {
+            self.instance
+                .handle
+                .get_physical_device_format_properties(self.handle, format)
+        }
+    }
 }
 
 impl Adapter {
+    /// Raw `VkPhysicalDevice`, for interop with other Vulkan libraries.
+    pub fn raw_handle(&self) -> vk::PhysicalDevice {
+        self.inner.handle
+    }
+
     pub fn features(&self) -> AdapterFeatures {
         self.inner.features
     }
@@ -209,6 +313,71 @@ impl Adapter {
     pub fn default_score(&self) -> u64 {
         self.info().default_score()
     }
+
+    pub fn memory_heaps(&self) -> Vec<MemoryHeap> {
+        let properties = &self.inner.memory_properties;
+        properties.memory_heaps[..properties.memory_heap_count as usize]
+            .iter()
+            .map(|&heap| MemoryHeap::from(heap))
+            .collect()
+    }
+
+    pub fn memory_types(&self) -> Vec<MemoryType> {
+        let properties = &self.inner.memory_properties;
+        properties.memory_types[..properties.memory_type_count as usize]
+            .iter()
+            .map(|&ty| MemoryType::from(ty))
+            .collect()
+    }
+
+    pub fn format_properties(&self, format: vk::Format) -> vk::FormatProperties {
+        self.inner.format_properties(format)
+    }
+
+    pub fn supports_sampling(&self, format: vk::Format) -> bool {
+        self.supports_optimal(format, vk::FormatFeatureFlags::SAMPLED_IMAGE)
+    }
+
+    pub fn supports_storage(&self, format: vk::Format) -> bool {
+        self.supports_optimal(format, vk::FormatFeatureFlags::STORAGE_IMAGE)
+    }
+
+    pub fn supports_color_attachment(&self, format: vk::Format) -> bool {
+        self.supports_optimal(format, vk::FormatFeatureFlags::COLOR_ATTACHMENT)
+    }
+
+    /// Whether `surface` can be presented to with `present_mode` on this adapter.
+    ///
+    /// # Safety
+    /// `surface` must be a valid surface created from this adapter's instance.
+    pub unsafe fn supports_present_mode(
+        &self,
+        surface: vk::SurfaceKHR,
+        present_mode: vk::PresentModeKHR,
+    ) -> Result<bool, GPUError> {
+        let instance = &self.inner.instance;
+        let loader = ash::khr::surface::Instance::new(&instance.entry, &instance.handle);
+        let modes = unsafe {
+            loader.get_physical_device_surface_present_modes(self.inner.handle, surface)?
+        };
+        Ok(modes.contains(&present_mode))
+    }
+
+    /// Whether the format can be both source and destination of a linearly filtered blit.
+    pub fn supports_blit_filter_linear(&self, format: vk::Format) -> bool {
+        self.supports_optimal(
+            format,
+            vk::FormatFeatureFlags::BLIT_SRC
+                | vk::FormatFeatureFlags::BLIT_DST
+                | vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR,
+        )
+    }
+
+    fn supports_optimal(&self, format: vk::Format, features: vk::FormatFeatureFlags) -> bool {
+        self.format_properties(format)
+            .optimal_tiling_features
+            .contains(features)
+    }
 }
 
 fn adapter_info_from_properties(
@@ -233,6 +402,7 @@ fn adapter_info_from_properties(
             max_per_stage_descriptor_sampled_images: properties
                 .limits
                 .max_per_stage_descriptor_sampled_images,
+            max_viewports: properties.limits.max_viewports,
         },
         queue_family_count,
     }
@@ -246,7 +416,10 @@ fn physical_device_name(properties: &vk::PhysicalDeviceProperties) -> String {
 }
 
 impl InstanceImpl {
-    pub unsafe fn adapters(&self, formats: &[vk::Format]) -> Result<Vec<AdapterImpl>, GPUError> {
+    pub unsafe fn adapters(
+        self: &RawInstance,
+        formats: &[vk::Format],
+    ) -> Result<Vec<AdapterImpl>, GPUError> {
         let pdevs = unsafe {
             self.handle
                 .enumerate_physical_devices()
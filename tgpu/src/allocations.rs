@@ -1,6 +1,338 @@
-use std::{mem::ManuallyDrop, sync::Arc};
+use std::{
+    ffi::{CStr, CString, c_void},
+    sync::Arc,
+};
+
+use ash::vk;
+
+use crate::{
+    Device, ExternalHandle, GPUError, HostAccess, Label, MemoryPreset,
+    raw::{DeviceImpl, RawDevice},
+};
+
+/// Extension exporting and importing memory as OS handles on this platform.
+#[cfg(unix)]
+pub(crate) const EXTERNAL_MEMORY_EXTENSION: &CStr = ash::khr::external_memory_fd::NAME;
+#[cfg(windows)]
+pub(crate) const EXTERNAL_MEMORY_EXTENSION: &CStr = ash::khr::external_memory_win32::NAME;
+
+/// Opaque fds on unix and NT handles on windows; both only work between the same driver.
+#[cfg(unix)]
+pub(crate) const EXTERNAL_MEMORY_HANDLE_TYPE: vk::ExternalMemoryHandleTypeFlags =
+    vk::ExternalMemoryHandleTypeFlags::OPAQUE_FD;
+#[cfg(windows)]
+pub(crate) const EXTERNAL_MEMORY_HANDLE_TYPE: vk::ExternalMemoryHandleTypeFlags =
+    vk::ExternalMemoryHandleTypeFlags::OPAQUE_WIN32;
 
 pub struct Allocation {
     pub handle: vkm::Allocation,
-    pub allocator: Arc<ManuallyDrop<vkm::Allocator>>,
+    pub allocator: Arc<vkm::Allocator>,
+    pub pool: Option<MemoryPool>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MemoryPoolInfo<'a> {
+    pub memory: MemoryPreset,
+    pub host_access: HostAccess,
+    /// Overrides the memory type picked from `memory` and `host_access`.
+    pub memory_type_index: Option<u32>,
+    /// Size of each device memory block; 0 lets the allocator choose.
+    pub block_size: u64,
+    pub min_block_count: usize,
+    /// Upper bound on blocks; 0 means unlimited.
+    pub max_block_count: usize,
+    pub label: Option<Label<'a>>,
+}
+
+impl Default for MemoryPoolInfo<'_> {
+    fn default() -> Self {
+        Self {
+            memory: MemoryPreset::GpuOnly,
+            host_access: HostAccess::None,
+            memory_type_index: None,
+            block_size: 0,
+            min_block_count: 0,
+            max_block_count: 0,
+            label: None,
+        }
+    }
+}
+
+/// Memory shared by images from `Device::create_aliased_images`, freed with the last of them.
+pub struct AliasedMemory {
+    pub allocation: vkm::Allocation,
+    pub device: RawDevice,
+}
+
+impl Drop for AliasedMemory {
+    fn drop(&mut self) {
+        unsafe { self.device.allocator.free_memory(&mut self.allocation) };
+    }
+}
+
+/// Dedicated VMA pool that buffers and images can target through their `pool` field.
+#[derive(Clone)]
+pub struct MemoryPool {
+    pub inner: Arc<MemoryPoolImpl>,
+}
+
+pub struct MemoryPoolImpl {
+    pub handle: vkm::AllocatorPool,
+    pub memory: MemoryPreset,
+    pub memory_type_index: u32,
+    pub device: RawDevice,
+    /// Chained into every allocation of the pool; dropped after `handle`.
+    pub(crate) external: Option<Box<ExternalMemory>>,
+}
+
+/// `pMemoryAllocateNext` chain of a pool that backs a single external buffer or image.
+pub(crate) enum ExternalMemory {
+    Export(vk::ExportMemoryAllocateInfo<'static>),
+    #[cfg(unix)]
+    Import(vk::ImportMemoryFdInfoKHR<'static>),
+    #[cfg(windows)]
+    Import(vk::ImportMemoryWin32HandleInfoKHR<'static>),
+}
+
+// SAFETY: the chained structs are plain data with a null `p_next`.
+unsafe impl Send for ExternalMemory {}
+unsafe impl Sync for ExternalMemory {}
+
+impl ExternalMemory {
+    pub(crate) fn export() -> Self {
+        Self::Export(
+            vk::ExportMemoryAllocateInfo::default().handle_types(EXTERNAL_MEMORY_HANDLE_TYPE),
+        )
+    }
+
+    /// Borrows `handle` for the import; pass it to `release_imported` once its memory is allocated.
+    pub(crate) fn import(handle: &ExternalHandle) -> Self {
+        match handle {
+            #[cfg(unix)]
+            ExternalHandle::Fd(fd) => {
+                use std::os::fd::AsRawFd;
+
+                Self::Import(
+                    vk::ImportMemoryFdInfoKHR::default()
+                        .handle_type(EXTERNAL_MEMORY_HANDLE_TYPE)
+                        .fd(fd.as_raw_fd()),
+                )
+            }
+            #[cfg(windows)]
+            ExternalHandle::Win32(handle) => {
+                use std::os::windows::io::AsRawHandle;
+
+                Self::Import(
+                    vk::ImportMemoryWin32HandleInfoKHR::default()
+                        .handle_type(EXTERNAL_MEMORY_HANDLE_TYPE)
+                        .handle(handle.as_raw_handle() as _),
+                )
+            }
+        }
+    }
+
+    fn as_ptr(&self) -> *const c_void {
+        match self {
+            Self::Export(info) => info as *const _ as *const c_void,
+            #[cfg(unix)]
+            Self::Import(info) => info as *const _ as *const c_void,
+            #[cfg(windows)]
+            Self::Import(info) => info as *const _ as *const c_void,
+        }
+    }
+}
+
+/// Resource an external pool backs, including its `VkExternalMemory*CreateInfo` chain.
+pub(crate) enum ExternalResource<'a> {
+    Buffer(&'a vk::BufferCreateInfo<'a>),
+    Image(&'a vk::ImageCreateInfo<'a>),
+}
+
+/// Creates a single-allocation pool whose memory is exported or imported through `external`.
+///
+/// The memory type is picked from the ones `resource` supports. Opaque handles can't be queried
+/// for their own memory types, so imports rely on the exporter having created the same resource.
+pub(crate) fn create_external_pool(
+    device: &RawDevice,
+    memory: MemoryPreset,
+    create_info: &vkm::AllocationCreateInfo,
+    resource: ExternalResource<'_>,
+    external: ExternalMemory,
+) -> Result<MemoryPool, GPUError> {
+    if !device.features.external_memory {
+        return Err(GPUError::Validation(
+            "external memory requires the external_memory device feature",
+        ));
+    }
+
+    let allocator = &**device.allocator;
+    let memory_type_index = unsafe {
+        match resource {
+            ExternalResource::Buffer(info) => {
+                vkm::Alloc::find_memory_type_index_for_buffer_info(allocator, info, create_info)
+            }
+            ExternalResource::Image(info) => {
+                vkm::Alloc::find_memory_type_index_for_image_info(allocator, *info, create_info)
+            }
+        }
+    }?;
+
+    let external = Box::new(external);
+    let handle = device.allocator.create_pool(&vkm::PoolCreateInfo {
+        memory_type_index,
+        max_block_count: 1,
+        memory_allocate_next: external.as_ptr(),
+        ..Default::default()
+    })?;
+
+    Ok(MemoryPool {
+        inner: Arc::new(MemoryPoolImpl {
+            handle,
+            memory,
+            memory_type_index,
+            device: device.clone(),
+            external: Some(external),
+        }),
+    })
+}
+
+/// Gives up `handle` after a successful import.
+///
+/// A successful fd import transfers the fd to the driver, so it must not be closed here. Win32
+/// imports only reference the handle, so it is closed. A failed import leaves the handle with the
+/// caller either way, so on error `handle` should simply be dropped.
+pub(crate) fn release_imported(handle: ExternalHandle) {
+    match handle {
+        #[cfg(unix)]
+        ExternalHandle::Fd(fd) => {
+            let _ = std::os::fd::IntoRawFd::into_raw_fd(fd);
+        }
+        #[cfg(windows)]
+        ExternalHandle::Win32(handle) => drop(handle),
+    }
+}
+
+/// Size of the memory behind `handle`, when the platform can tell.
+///
+/// Only fds that support seeking to their end report a size, and drivers may round it up, so it
+/// only catches imports that cannot fit rather than checking for an exact match.
+pub(crate) fn imported_size(handle: &ExternalHandle) -> Option<u64> {
+    match handle {
+        #[cfg(unix)]
+        ExternalHandle::Fd(fd) => {
+            use std::io::{Seek, SeekFrom};
+
+            let mut file = std::fs::File::from(fd.try_clone().ok()?);
+            file.seek(SeekFrom::End(0)).ok().filter(|&size| size > 0)
+        }
+        #[cfg(windows)]
+        ExternalHandle::Win32(_) => None,
+    }
+}
+
+/// Exports `memory`, which must have been allocated from an exporting external pool.
+pub(crate) unsafe fn export_memory_handle(
+    device: &DeviceImpl,
+    memory: vk::DeviceMemory,
+) -> Result<ExternalHandle, GPUError> {
+    let ext = device
+        .ext
+        .external_memory
+        .as_ref()
+        .ok_or(GPUError::Validation(
+            "external memory requires the external_memory device feature",
+        ))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::fd::FromRawFd;
+
+        let info = vk::MemoryGetFdInfoKHR::default()
+            .memory(memory)
+            .handle_type(EXTERNAL_MEMORY_HANDLE_TYPE);
+        let fd = unsafe { ext.get_memory_fd(&info) }?;
+        Ok(ExternalHandle::Fd(unsafe {
+            std::os::fd::OwnedFd::from_raw_fd(fd)
+        }))
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::io::FromRawHandle;
+
+        let info = vk::MemoryGetWin32HandleInfoKHR::default()
+            .memory(memory)
+            .handle_type(EXTERNAL_MEMORY_HANDLE_TYPE);
+        let handle = unsafe { ext.get_memory_win32_handle(&info) }?;
+        Ok(ExternalHandle::Win32(unsafe {
+            std::os::windows::io::OwnedHandle::from_raw_handle(handle as _)
+        }))
+    }
+}
+
+impl MemoryPool {
+    pub fn memory(&self) -> MemoryPreset {
+        self.inner.memory
+    }
+
+    pub fn memory_type_index(&self) -> u32 {
+        self.inner.memory_type_index
+    }
+}
+
+impl std::fmt::Debug for MemoryPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MemoryPool")
+            .field("memory", &self.inner.memory)
+            .field("memory_type_index", &self.inner.memory_type_index)
+            .finish()
+    }
+}
+
+impl Device {
+    pub fn create_memory_pool(&self, info: &MemoryPoolInfo<'_>) -> Result<MemoryPool, GPUError> {
+        if info.memory == MemoryPreset::TransientAttachment {
+            return Err(GPUError::Validation(
+                "TransientAttachment memory cannot back a memory pool",
+            ));
+        }
+        if info.max_block_count != 0 && info.max_block_count < info.min_block_count {
+            return Err(GPUError::Validation(
+                "memory pool max_block_count must be at least min_block_count",
+            ));
+        }
+
+        let memory_type_index = match info.memory_type_index {
+            Some(index) => index,
+            None => unsafe {
+                vkm::Alloc::find_memory_type_index(
+                    &**self.inner.allocator,
+                    u32::MAX,
+                    &crate::buffer::allocation_create_info(info.memory, info.host_access),
+                )?
+            },
+        };
+
+        let handle = self.inner.allocator.create_pool(&vkm::PoolCreateInfo {
+            memory_type_index,
+            block_size: info.block_size,
+            min_block_count: info.min_block_count,
+            max_block_count: info.max_block_count,
+            ..Default::default()
+        })?;
+
+        if let Some(Label::Name(name) | Label::Both((name, _))) = &info.label {
+            let name = CString::new(*name).unwrap();
+            handle.set_name(Some(&name));
+        }
+
+        Ok(MemoryPool {
+            inner: Arc::new(MemoryPoolImpl {
+                handle,
+                memory: info.memory,
+                memory_type_index,
+                device: self.inner.clone(),
+                external: None,
+            }),
+        })
+    }
 }
@@ -0,0 +1,319 @@
+use ash::vk;
+use raw_window_handle::{HasDisplayHandle, HasWindowHandle};
+use winit::{
+    application::ApplicationHandler,
+    event::WindowEvent,
+    event_loop::{ActiveEventLoop, EventLoop},
+    window::{Window, WindowId},
+};
+
+use crate::{
+    CommandRecorder, Device, DeviceCreateInfo, GPUError, Image, ImageLayoutTransition,
+    ImageTransition, ImageView, Instance, InstanceCreateInfo, Queue, QueueFlags, QueueRequest,
+    SubmitInfo, Swapchain, SwapchainCreateInfo,
+};
+
+pub use ::winit;
+
+/// A window with the device, queue and swapchain presenting to it.
+pub struct AppContext {
+    pub window: Window,
+    pub instance: Instance,
+    pub device: Device,
+    pub queue: Queue,
+    pub swapchain: Swapchain,
+}
+
+/// Everything needed to record one frame.
+///
+/// `image` is in `ImageLayoutTransition::COLOR` when `RenderApp::render` is called and must be
+/// left in that layout; the transition to present is recorded afterwards.
+pub struct FrameContext<'a> {
+    pub device: &'a Device,
+    pub queue: &'a Queue,
+    pub window: &'a Window,
+    pub image: &'a Image,
+    pub view: &'a ImageView,
+    pub extent: vk::Extent2D,
+    pub format: vk::Format,
+    /// Number of frames submitted before this one.
+    pub frame_number: u64,
+    pub recorder: &'a mut CommandRecorder,
+}
+
+/// Per-frame drawing for `run`, which owns the window, swapchain and frame loop.
+pub trait RenderApp: Sized {
+    /// Creates pipelines and other resources once the device and swapchain exist.
+    fn init(ctx: &AppContext) -> Result<Self, GPUError>;
+
+    fn render(&mut self, frame: FrameContext<'_>);
+
+    /// Called after the swapchain was recreated, e.g. because the window was resized.
+    fn resized(&mut self, _ctx: &AppContext) {}
+
+    /// Sees every window event first; returning `true` skips the default handling.
+    fn window_event(
+        &mut self,
+        _ctx: &AppContext,
+        _event_loop: &ActiveEventLoop,
+        _event: &WindowEvent,
+    ) -> bool {
+        false
+    }
+}
+
+pub struct RenderAppInfo {
+    pub title: &'static str,
+    pub device: DeviceCreateInfo,
+    pub preferred_image_count: usize,
+    pub preferred_present_mode: vk::PresentModeKHR,
+}
+
+impl Default for RenderAppInfo {
+    fn default() -> Self {
+        Self {
+            title: "tgpu",
+            device: DeviceCreateInfo::default(),
+            preferred_image_count: 3,
+            preferred_present_mode: vk::PresentModeKHR::MAILBOX,
+        }
+    }
+}
+
+/// Opens a window and drives `A` until it is closed.
+pub fn run<A: RenderApp>(info: RenderAppInfo) -> Result<(), Box<dyn std::error::Error>> {
+    let event_loop = EventLoop::new()?;
+    let mut runner = Runner::<A> {
+        info,
+        state: None,
+        error: None,
+    };
+    event_loop.run_app(&mut runner)?;
+    match runner.error {
+        Some(error) => Err(error.into()),
+        None => Ok(()),
+    }
+}
+
+impl AppContext {
+    fn new(window: Window, info: &RenderAppInfo) -> Result<Self, GPUError> {
+        let display = window.display_handle().unwrap().as_raw();
+        let instance = Instance::new_with_display(
+            &InstanceCreateInfo {
+                app_name: info.title,
+                engine_name: "tgpu",
+                debug_printf: false,
+            },
+            display,
+        )?;
+
+        let adapter = instance
+            .adapters(&[])?
+            .next()
+            .ok_or(GPUError::Validation("no Vulkan adapter available"))?;
+        let (device, mut queues) = instance.request_device(
+            &info.device,
+            adapter,
+            &[QueueRequest {
+                required_flags: QueueFlags::GRAPHICS,
+                exclude_flags: QueueFlags::empty(),
+                strict: false,
+                allow_fallback_share: true,
+            }],
+        )?;
+        let queue = queues
+            .next()
+            .ok_or(GPUError::Validation("no graphics queue available"))?;
+
+        let swapchain = device.create_swapchain(&SwapchainCreateInfo {
+            display,
+            window: window.window_handle().unwrap().as_raw(),
+            preferred_extent: window_extent(&window),
+            preferred_image_count: info.preferred_image_count,
+            preferred_present_mode: info.preferred_present_mode,
+            require_present_mode: false,
+            storage: false,
+            format_selector: Box::new(|formats| {
+                formats
+                    .iter()
+                    .find(|f| {
+                        f.format == vk::Format::B8G8R8A8_SRGB
+                            && f.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR
+                    })
+                    .copied()
+                    .unwrap_or(formats[0])
+            }),
+        })?;
+
+        Ok(Self {
+            window,
+            instance,
+            device,
+            queue,
+            swapchain,
+        })
+    }
+}
+
+fn window_extent(window: &Window) -> vk::Extent2D {
+    let size = window.inner_size();
+    vk::Extent2D {
+        width: size.width,
+        height: size.height,
+    }
+}
+
+struct RunnerState<A> {
+    ctx: AppContext,
+    app: A,
+    frame_number: u64,
+}
+
+struct Runner<A> {
+    info: RenderAppInfo,
+    state: Option<RunnerState<A>>,
+    error: Option<GPUError>,
+}
+
+impl<A: RenderApp> RunnerState<A> {
+    fn recreate(&mut self) -> Result<(), GPUError> {
+        let extent = window_extent(&self.ctx.window);
+        if extent.width == 0 || extent.height == 0 {
+            return Ok(());
+        }
+        self.ctx.swapchain.set_preferred_extent(extent);
+        self.ctx.swapchain.recreate()?;
+        self.app.resized(&self.ctx);
+        Ok(())
+    }
+
+    fn render_frame(&mut self) -> Result<(), GPUError> {
+        let extent = window_extent(&self.ctx.window);
+        if extent.width == 0 || extent.height == 0 {
+            return Ok(());
+        }
+
+        let frame = self.ctx.swapchain.acquire_next(None)?;
+        if frame.suboptimal {
+            return self.recreate();
+        }
+
+        let ctx = &self.ctx;
+        let image = ctx.swapchain.image(frame);
+        let mut recorder = ctx.queue.record();
+        recorder.image_transition(
+            image,
+            ImageTransition {
+                from: ImageLayoutTransition::UNDEFINED,
+                to: ImageLayoutTransition::COLOR,
+                aspect: vk::ImageAspectFlags::COLOR,
+                ..Default::default()
+            },
+        );
+
+        self.app.render(FrameContext {
+            device: &ctx.device,
+            queue: &ctx.queue,
+            window: &ctx.window,
+            image,
+            view: ctx.swapchain.view(frame),
+            extent: ctx.swapchain.extent(),
+            format: ctx.swapchain.format(),
+            frame_number: self.frame_number,
+            recorder: &mut recorder,
+        });
+
+        recorder.image_transition(
+            image,
+            ImageTransition {
+                from: ImageLayoutTransition::COLOR,
+                to: ImageLayoutTransition::PRESENT,
+                aspect: vk::ImageAspectFlags::COLOR,
+                ..Default::default()
+            },
+        );
+
+        ctx.queue.submit(SubmitInfo {
+            records: &[recorder.finish()],
+            wait_binary: &[(
+                ctx.swapchain.inner.available_semaphore(frame),
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            )],
+            signal_binary: &[ctx.swapchain.inner.finished_semaphore(frame)],
+            fence: Some(ctx.swapchain.inner.fence(frame)),
+            ..Default::default()
+        })?;
+        self.frame_number += 1;
+
+        let ctx = &mut self.ctx;
+        if ctx.swapchain.present(&ctx.queue, frame)? {
+            self.recreate()?;
+        }
+        Ok(())
+    }
+}
+
+impl<A: RenderApp> ApplicationHandler for Runner<A> {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if self.state.is_some() {
+            return;
+        }
+
+        let attributes = Window::default_attributes().with_title(self.info.title);
+        let window = match event_loop.create_window(attributes) {
+            Ok(window) => window,
+            Err(e) => {
+                log::error!("create window failed: {e}");
+                event_loop.exit();
+                return;
+            }
+        };
+
+        let state = AppContext::new(window, &self.info).and_then(|ctx| {
+            let app = A::init(&ctx)?;
+            Ok(RunnerState {
+                ctx,
+                app,
+                frame_number: 0,
+            })
+        });
+        match state {
+            Ok(state) => {
+                state.ctx.window.request_redraw();
+                self.state = Some(state);
+            }
+            Err(e) => {
+                self.error = Some(e);
+                event_loop.exit();
+            }
+        }
+    }
+
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, _id: WindowId, event: WindowEvent) {
+        let Some(state) = &mut self.state else {
+            return;
+        };
+        if state.app.window_event(&state.ctx, event_loop, &event) {
+            return;
+        }
+
+        let result = match event {
+            WindowEvent::CloseRequested => {
+                event_loop.exit();
+                Ok(())
+            }
+            WindowEvent::Resized(_) => state.recreate(),
+            WindowEvent::RedrawRequested => {
+                let result = state.render_frame();
+                state.ctx.window.request_redraw();
+                result
+            }
+            _ => Ok(()),
+        };
+
+        if let Err(e) = result {
+            self.error = Some(e);
+            event_loop.exit();
+        }
+    }
+}
@@ -108,19 +108,18 @@ impl SlotAllocator {
         slot
     }
 
+    /// Ignores handles that are out of range or already freed, asserting in debug builds.
     fn free(&self, slot: u32) {
         let mut inner = self.inner.lock();
-        assert!(
-            slot < self.capacity,
-            "Bindless {} handle {} is out of range",
-            self.kind,
-            slot
-        );
-        assert!(
-            inner.used[slot as usize],
-            "Bindless {} handle {} is not allocated",
+        let allocated = slot < self.capacity && inner.used[slot as usize];
+        debug_assert!(
+            allocated,
+            "Bindless {} handle {} is out of range or not allocated",
             self.kind, slot
         );
+        if !allocated {
+            return;
+        }
 
         inner.used[slot as usize] = false;
         inner.free.push(slot);
@@ -204,7 +203,7 @@ impl Device {
             label: None,
         });
 
-        let set = self.create_descriptor_set(pool, &layout);
+        let set = pool.allocate_set(&layout);
 
         BindlessHeap {
             set,
@@ -1,8 +1,20 @@
 use ash::vk;
-use std::{cell::UnsafeCell, ptr, sync::Arc};
+use std::{
+    cell::UnsafeCell,
+    ops::{Deref, DerefMut},
+    ptr,
+    sync::Arc,
+};
 use vkm::Alloc;
 
-use crate::{Device, GPUError, HostAccess, Label, MemoryPreset, raw::RawDevice};
+use crate::{
+    Device, ExternalHandle, GPUError, HostAccess, Label, MemoryPool, MemoryPreset, Queue,
+    allocations::{
+        EXTERNAL_MEMORY_HANDLE_TYPE, ExternalMemory, ExternalResource, create_external_pool,
+        export_memory_handle, imported_size, release_imported,
+    },
+    raw::RawDevice,
+};
 
 bitflags::bitflags! {
     #[derive(Debug, Clone, Copy, Default)]
@@ -14,6 +26,15 @@ pub struct BufferUses: u32 {
         const UNIFORM = 1 << 4;
         const STORAGE = 1 << 5;
         const DEVICE_ADDRESS = 1 << 6;
+        const INDIRECT = 1 << 7;
+        const UNIFORM_TEXEL = 1 << 8;
+        const STORAGE_TEXEL = 1 << 9;
+        /// Memory can be shared with other APIs or processes; see `Buffer::export_memory_handle`.
+        const EXTERNAL = 1 << 10;
+        /// Memory is bound later with `Queue::bind_sparse_buffer` instead of at creation.
+        ///
+        /// Requires `sparse_binding` on the device and `GpuOnly` memory.
+        const SPARSE_BINDING = 1 << 11;
     }
 }
 
@@ -30,7 +51,12 @@ bitflags::bitflags! {
         const STORAGE = 1 << 7;
         const QUERY = 1 << 8;
         const DEVICE_ADDRESS = 1 << 9;
+        const INDIRECT = 1 << 10;
+        const UNIFORM_TEXEL = 1 << 11;
         const SHARE = 1 << 12;
+        const STORAGE_TEXEL = 1 << 13;
+        const EXTERNAL = 1 << 14;
+        const SPARSE_BINDING = 1 << 15;
 
         const DEVICE = 1 << 16;
         const HOST = 1 << 17;
@@ -66,6 +92,15 @@ impl From<BufferUsage> for vk::BufferUsageFlags {
         if usage.contains(BufferUsage::DEVICE_ADDRESS) {
             vk_usage |= vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS;
         }
+        if usage.contains(BufferUsage::INDIRECT) {
+            vk_usage |= vk::BufferUsageFlags::INDIRECT_BUFFER;
+        }
+        if usage.contains(BufferUsage::UNIFORM_TEXEL) {
+            vk_usage |= vk::BufferUsageFlags::UNIFORM_TEXEL_BUFFER;
+        }
+        if usage.contains(BufferUsage::STORAGE_TEXEL) {
+            vk_usage |= vk::BufferUsageFlags::STORAGE_TEXEL_BUFFER;
+        }
         vk_usage
     }
 }
@@ -134,6 +169,12 @@ pub struct BufferDesc<'a> {
     pub memory: MemoryPreset,
     pub host_access: HostAccess,
     pub sharing: vk::SharingMode,
+    /// Allocate from this pool instead of the device allocator; `memory` must match it.
+    pub pool: Option<&'a MemoryPool>,
+    /// Minimum alignment of the buffer's offset in its memory block; must be a power of two.
+    ///
+    /// Applied on top of the alignment Vulkan requires for the buffer's usage.
+    pub alignment: Option<u64>,
     pub label: Option<Label<'a>>,
 }
 
@@ -145,6 +186,8 @@ impl Default for BufferDesc<'_> {
             memory: MemoryPreset::GpuOnly,
             host_access: HostAccess::None,
             sharing: vk::SharingMode::EXCLUSIVE,
+            pool: None,
+            alignment: None,
             label: None,
         }
     }
@@ -154,15 +197,19 @@ impl Default for BufferDesc<'_> {
 pub(crate) struct BufferInfo<'a> {
     pub size: usize,
     pub usage: BufferUsage,
+    pub pool: Option<MemoryPool>,
+    pub alignment: Option<u64>,
     pub label: Option<Label<'a>>,
 }
 
 #[derive(Debug)]
 pub struct BufferImpl {
     pub handle: vk::Buffer,
-    pub(crate) allocation: UnsafeCell<vkm::Allocation>,
+    /// `None` for sparse buffers, whose memory is bound through the queue.
+    pub(crate) allocation: UnsafeCell<Option<vkm::Allocation>>,
     pub(crate) usage: BufferUsage,
     pub(crate) device: RawDevice,
+    pub pool: Option<MemoryPool>,
 }
 
 #[derive(Debug, Clone)]
@@ -174,7 +221,7 @@ pub struct Buffer {
     pub host_access: HostAccess,
 }
 
-#[derive(Debug, Copy, Clone, Default)]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
 pub struct BufferAccessTransition {
     pub stage: vk::PipelineStageFlags2,
     pub access: vk::AccessFlags2,
@@ -219,6 +266,13 @@ impl BufferAccessTransition {
             vk::AccessFlags2::SHADER_READ | vk::AccessFlags2::SHADER_WRITE,
         )
     }
+
+    pub fn fragment_read() -> Self {
+        Self::new(
+            vk::PipelineStageFlags2::FRAGMENT_SHADER,
+            vk::AccessFlags2::SHADER_READ,
+        )
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -253,6 +307,42 @@ pub struct CopyBufferInfo<'a> {
 
 impl Device {
     pub fn create_buffer(&self, desc: &BufferDesc<'_>) -> Result<Buffer, GPUError> {
+        let external = desc
+            .usage
+            .contains(BufferUses::EXTERNAL)
+            .then(ExternalMemory::export);
+        self.create_buffer_in(desc, external, None)
+    }
+
+    /// Creates a `GpuOnly` buffer backed by memory exported from another API or process.
+    ///
+    /// `handle` is an opaque fd on unix or an NT handle on windows, exported by the same driver.
+    /// Opaque handles must be imported with exactly the allocation size they were exported with,
+    /// so `size` has to be the exporting buffer's size; an fd known to be smaller is rejected,
+    /// anything else is undefined behavior. Once the driver has taken the memory an fd belongs to
+    /// it and a windows handle is closed, even if binding the buffer fails afterwards; an error
+    /// before that closes the handle.
+    pub fn import_external_buffer(
+        &self,
+        handle: ExternalHandle,
+        size: usize,
+        usage: BufferUses,
+    ) -> Result<Buffer, GPUError> {
+        let desc = BufferDesc {
+            size,
+            usage: usage | BufferUses::EXTERNAL,
+            ..Default::default()
+        };
+        let external = ExternalMemory::import(&handle);
+        self.create_buffer_in(&desc, Some(external), Some(handle))
+    }
+
+    fn create_buffer_in(
+        &self,
+        desc: &BufferDesc<'_>,
+        external: Option<ExternalMemory>,
+        imported: Option<ExternalHandle>,
+    ) -> Result<Buffer, GPUError> {
         if desc.size == 0 {
             return Err(GPUError::Validation(
                 "buffer size must be greater than zero",
@@ -271,6 +361,7 @@ impl Device {
             (MemoryPreset::Upload, HostAccess::None) => HostAccess::WriteSequential,
             (MemoryPreset::Readback, HostAccess::None) => HostAccess::ReadRandom,
             (MemoryPreset::Dynamic, HostAccess::None) => HostAccess::WriteSequential,
+            (MemoryPreset::DeviceUpload, HostAccess::None) => HostAccess::WriteSequential,
             (_, host_access) => host_access,
         };
 
@@ -287,6 +378,38 @@ impl Device {
             ));
         }
 
+        if let Some(pool) = desc.pool
+            && pool.memory() != desc.memory
+        {
+            return Err(GPUError::Validation(
+                "buffer memory preset must match the memory pool it is allocated from",
+            ));
+        }
+
+        if desc.usage.contains(BufferUses::SPARSE_BINDING) {
+            if !self.inner.features.sparse_binding {
+                return Err(GPUError::Validation(
+                    "BufferUses::SPARSE_BINDING requires sparse_binding to be enabled on the device",
+                ));
+            }
+            if desc.memory != MemoryPreset::GpuOnly
+                || desc.pool.is_some()
+                || desc.usage.contains(BufferUses::EXTERNAL)
+            {
+                return Err(GPUError::Validation(
+                    "sparse buffers must use GpuOnly memory without a pool or external memory",
+                ));
+            }
+        }
+
+        if desc.usage.contains(BufferUses::EXTERNAL) && desc.pool.is_some() {
+            return Err(GPUError::Validation(
+                "BufferUses::EXTERNAL buffers allocate their own memory and cannot use a pool",
+            ));
+        }
+
+        validate_alignment(desc.alignment)?;
+
         let mut usage: BufferUsage = desc.usage.into();
 
         match desc.memory {
@@ -295,7 +418,9 @@ impl Device {
             MemoryPreset::Readback => {
                 usage |= BufferUsage::HOST | BufferUsage::HOST_VISIBLE | BufferUsage::CACHED
             }
-            MemoryPreset::Dynamic => usage |= BufferUsage::DEVICE | BufferUsage::HOST_VISIBLE,
+            MemoryPreset::Dynamic | MemoryPreset::DeviceUpload => {
+                usage |= BufferUsage::DEVICE | BufferUsage::HOST_VISIBLE
+            }
             MemoryPreset::TransientAttachment => unreachable!(),
         }
 
@@ -312,16 +437,28 @@ impl Device {
             usage |= BufferUsage::SHARE;
         }
 
-        let info = BufferInfo {
+        let create_info = allocation_create_info(desc.memory, host_access);
+        let mut info = BufferInfo {
             size: desc.size,
             usage,
+            pool: desc.pool.cloned(),
+            alignment: desc.alignment,
             label: desc.label.clone(),
         };
-        let inner = BufferImpl::new_with_allocation(
-            self.inner.clone(),
-            &info,
-            allocation_create_info(desc.memory, host_access),
-        )?;
+        if let Some(external) = external {
+            let mut external_info = vk::ExternalMemoryBufferCreateInfo::default()
+                .handle_types(EXTERNAL_MEMORY_HANDLE_TYPE);
+            let buffer_info = BufferImpl::raw_info(&info).push_next(&mut external_info);
+            info.pool = Some(create_external_pool(
+                &self.inner,
+                desc.memory,
+                &create_info,
+                ExternalResource::Buffer(&buffer_info),
+                external,
+            )?);
+        }
+        let inner =
+            BufferImpl::new_with_allocation(self.inner.clone(), &info, create_info, imported)?;
 
         Ok(Buffer {
             inner: Arc::new(inner),
@@ -335,10 +472,158 @@ impl Device {
     pub fn create_buffer_with(&self, desc: &BufferDesc<'_>) -> Result<Buffer, GPUError> {
         self.create_buffer(desc)
     }
+
+    /// Creates a buffer and uploads `data` into it before returning.
+    ///
+    /// A `desc.size` of zero sizes the buffer to `data.len()`. Host-writable buffers are
+    /// written through a mapping; everything else goes through a staging buffer and a copy
+    /// on `queue`, which is waited on before returning.
+    pub fn create_buffer_init(
+        &self,
+        queue: &Queue,
+        desc: &BufferDesc<'_>,
+        data: &[u8],
+    ) -> Result<Buffer, GPUError> {
+        if data.is_empty() {
+            return Err(GPUError::Validation("buffer init data must not be empty"));
+        }
+        if desc.size != 0 && desc.size < data.len() {
+            return Err(GPUError::Validation(
+                "buffer size must be at least the length of the init data",
+            ));
+        }
+
+        let size = if desc.size == 0 {
+            data.len()
+        } else {
+            desc.size
+        };
+        let host_writable = match desc.host_access {
+            HostAccess::None => {
+                matches!(
+                    desc.memory,
+                    MemoryPreset::Upload | MemoryPreset::Dynamic | MemoryPreset::DeviceUpload
+                )
+            }
+            HostAccess::WriteSequential | HostAccess::ReadWriteRandom => true,
+            HostAccess::ReadRandom => false,
+        };
+
+        if host_writable && desc.memory != MemoryPreset::DeviceUpload {
+            let buffer = self.create_buffer(&BufferDesc {
+                size,
+                ..desc.clone()
+            })?;
+            buffer.write(data, 0);
+            return Ok(buffer);
+        }
+
+        let buffer = self.create_buffer(&BufferDesc {
+            size,
+            usage: desc.usage | BufferUses::COPY_DST,
+            ..desc.clone()
+        })?;
+        if host_writable && buffer.is_host_visible() {
+            buffer.write(data, 0);
+            return Ok(buffer);
+        }
+
+        let staging = self.create_buffer(&BufferDesc {
+            size: data.len(),
+            usage: BufferUses::COPY_SRC,
+            memory: MemoryPreset::Upload,
+            label: Some(Label::Name("Buffer Init Staging")),
+            ..Default::default()
+        })?;
+        staging.write(data, 0);
+
+        self.one_time(queue, |recorder| {
+            recorder.copy_buffer(&CopyBufferInfo {
+                src: &staging,
+                dst: &buffer,
+                regions: &[vk::BufferCopy::default().size(data.len() as vk::DeviceSize)],
+            });
+        })?;
+
+        Ok(buffer)
+    }
 }
 
 impl Buffer {
+    /// Names the buffer for debuggers such as RenderDoc.
+    pub fn set_name(&self, name: &str) {
+        unsafe { self.inner.device.set_object_name(self.inner.handle, name) };
+    }
+
+    /// Copies the whole buffer into a new `GpuOnly` buffer with `usage` (plus `COPY_DST`),
+    /// e.g. to promote a host-built buffer to device-local memory.
+    ///
+    /// The copy is submitted on `queue` and waited on before returning.
+    pub fn clone_to(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        usage: BufferUses,
+    ) -> Result<Buffer, GPUError> {
+        if !self.uses.contains(BufferUses::COPY_SRC) {
+            return Err(GPUError::Validation(
+                "clone_to requires COPY_SRC usage on the source buffer",
+            ));
+        }
+
+        let buffer = device.create_buffer(&BufferDesc {
+            size: self.size,
+            usage: usage | BufferUses::COPY_DST,
+            memory: MemoryPreset::GpuOnly,
+            ..Default::default()
+        })?;
+        device.one_time(queue, |recorder| {
+            recorder.copy_buffer(&CopyBufferInfo {
+                src: self,
+                dst: &buffer,
+                regions: &[vk::BufferCopy::default().size(self.size as vk::DeviceSize)],
+            });
+        })?;
+
+        Ok(buffer)
+    }
+
+    /// Exports the buffer's memory as an OS handle, e.g. for CUDA or another process.
+    ///
+    /// Only buffers created with `BufferUses::EXTERNAL` can be exported. The memory is a
+    /// dedicated allocation of `allocation_size` bytes starting at offset zero.
+    pub fn export_memory_handle(&self) -> Result<ExternalHandle, GPUError> {
+        let exportable = self.inner.pool.as_ref().is_some_and(|pool| {
+            matches!(
+                pool.inner.external.as_deref(),
+                Some(ExternalMemory::Export(_))
+            )
+        });
+        if !exportable {
+            return Err(GPUError::Validation(
+                "only buffers created with BufferUses::EXTERNAL can export their memory",
+            ));
+        }
+        let memory = self.inner.allocation_info().device_memory;
+        unsafe { export_memory_handle(&self.inner.device, memory) }
+    }
+
+    /// Size of the memory backing the buffer, which may exceed `size` due to alignment.
+    ///
+    /// For sparse buffers this is the size the bound memory has to cover.
+    pub fn allocation_size(&self) -> vk::DeviceSize {
+        if self.uses.contains(BufferUses::SPARSE_BINDING) {
+            let device = &self.inner.device.handle;
+            return unsafe { device.get_buffer_memory_requirements(self.inner.handle) }.size;
+        }
+        self.inner.allocation_info().size
+    }
+
     pub fn map(&self, offset: usize) -> *mut u8 {
+        assert!(
+            offset <= self.size,
+            "map offset is past the end of the buffer"
+        );
         unsafe { self.inner.map(offset) }
     }
 
@@ -352,6 +637,10 @@ impl Buffer {
             "Writing requires MAP_WRITE"
         );
         let size = data.len();
+        assert!(
+            host_range_in_bounds(offset, size, self.size),
+            "write range is out of the buffer's bounds"
+        );
         unsafe {
             let mapping = self.map(offset);
 
@@ -367,6 +656,10 @@ impl Buffer {
             self.inner.usage.contains(BufferUsage::MAP_READ),
             "Reading requires MAP_READ"
         );
+        assert!(
+            host_range_in_bounds(offset, size, self.size) && size <= buffer.len(),
+            "read range is out of the buffer's bounds"
+        );
         unsafe {
             let mapping = self.map(offset);
             self.inner.invalidate(offset, size);
@@ -384,6 +677,64 @@ impl Buffer {
         self.read(bytemuck::cast_slice_mut(data), 0, size);
     }
 
+    /// Maps the buffer as `size / size_of::<T>()` elements of `T` until the view is dropped.
+    ///
+    /// Fails if the buffer is not host-visible, `T` is zero-sized, or the mapping is not
+    /// aligned for `T`; use `alignment` in `BufferDesc` for over-aligned element types.
+    pub fn mapped_slice<T: bytemuck::Pod>(&self) -> Result<MappedSlice<'_, T>, GPUError> {
+        let (mapping, size) = self.map_typed::<T>()?;
+        unsafe { self.inner.invalidate(0, size) };
+        let bytes = unsafe { std::slice::from_raw_parts(mapping, size) };
+        match bytemuck::try_cast_slice(bytes) {
+            Ok(data) => Ok(MappedSlice { buffer: self, data }),
+            Err(_) => {
+                self.unmap();
+                Err(GPUError::Validation("buffer mapping is not aligned for T"))
+            }
+        }
+    }
+
+    /// Like `mapped_slice`, but writable; the written range is flushed when the view is dropped.
+    ///
+    /// `&mut self` only stops this handle from mapping the buffer twice. Clones of a `Buffer`
+    /// share its memory, so writes through another clone or by the GPU while the view is alive
+    /// race with it; keeping them apart is up to the caller.
+    pub fn mapped_slice_mut<T: bytemuck::Pod>(
+        &mut self,
+    ) -> Result<MappedSliceMut<'_, T>, GPUError> {
+        let (mapping, size) = self.map_typed::<T>()?;
+        let bytes = unsafe { std::slice::from_raw_parts_mut(mapping, size) };
+        match bytemuck::try_cast_slice_mut(bytes) {
+            Ok(data) => Ok(MappedSliceMut { buffer: self, data }),
+            Err(_) => {
+                self.unmap();
+                Err(GPUError::Validation("buffer mapping is not aligned for T"))
+            }
+        }
+    }
+
+    fn map_typed<T>(&self) -> Result<(*mut u8, usize), GPUError> {
+        if !self.is_host_visible() {
+            return Err(GPUError::Validation(
+                "mapped_slice requires a host-visible buffer; use Upload, Readback or Dynamic memory",
+            ));
+        }
+        if std::mem::size_of::<T>() == 0 {
+            return Err(GPUError::Validation(
+                "mapped_slice requires a non-zero-sized element type",
+            ));
+        }
+        let size = self.size / std::mem::size_of::<T>() * std::mem::size_of::<T>();
+        Ok((self.map(0), size))
+    }
+
+    /// Whether the allocation can be mapped; `DeviceUpload` buffers may land in memory that can't.
+    pub fn is_host_visible(&self) -> bool {
+        self.inner
+            .memory_properties()
+            .contains(vk::MemoryPropertyFlags::HOST_VISIBLE)
+    }
+
     pub fn device_address(&self) -> vk::DeviceAddress {
         assert!(
             self.uses.contains(BufferUses::DEVICE_ADDRESS),
@@ -393,6 +744,53 @@ impl Buffer {
     }
 }
 
+/// Read-only typed view of a mapped buffer; unmaps when dropped.
+pub struct MappedSlice<'a, T> {
+    buffer: &'a Buffer,
+    data: &'a [T],
+}
+
+impl<T> Deref for MappedSlice<'_, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        self.data
+    }
+}
+
+impl<T> Drop for MappedSlice<'_, T> {
+    fn drop(&mut self) {
+        self.buffer.unmap();
+    }
+}
+
+/// Writable typed view of a mapped buffer; flushes and unmaps when dropped.
+pub struct MappedSliceMut<'a, T> {
+    buffer: &'a Buffer,
+    data: &'a mut [T],
+}
+
+impl<T> Deref for MappedSliceMut<'_, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        self.data
+    }
+}
+
+impl<T> DerefMut for MappedSliceMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        self.data
+    }
+}
+
+impl<T> Drop for MappedSliceMut<'_, T> {
+    fn drop(&mut self) {
+        unsafe { self.buffer.inner.flush(0, std::mem::size_of_val(self.data)) };
+        self.buffer.unmap();
+    }
+}
+
 impl From<BufferUses> for BufferUsage {
     fn from(usage: BufferUses) -> Self {
         let mut raw = BufferUsage::empty();
@@ -417,11 +815,26 @@ impl From<BufferUses> for BufferUsage {
         if usage.contains(BufferUses::DEVICE_ADDRESS) {
             raw |= BufferUsage::DEVICE_ADDRESS;
         }
+        if usage.contains(BufferUses::INDIRECT) {
+            raw |= BufferUsage::INDIRECT;
+        }
+        if usage.contains(BufferUses::UNIFORM_TEXEL) {
+            raw |= BufferUsage::UNIFORM_TEXEL;
+        }
+        if usage.contains(BufferUses::STORAGE_TEXEL) {
+            raw |= BufferUsage::STORAGE_TEXEL;
+        }
+        if usage.contains(BufferUses::EXTERNAL) {
+            raw |= BufferUsage::EXTERNAL;
+        }
+        if usage.contains(BufferUses::SPARSE_BINDING) {
+            raw |= BufferUsage::SPARSE_BINDING;
+        }
         raw
     }
 }
 
-fn allocation_create_info(
+pub(crate) fn allocation_create_info(
     memory: MemoryPreset,
     host_access: HostAccess,
 ) -> vkm::AllocationCreateInfo {
@@ -429,6 +842,7 @@ fn allocation_create_info(
         MemoryPreset::GpuOnly => vkm::MemoryUsage::AutoPreferDevice,
         MemoryPreset::Upload | MemoryPreset::Readback => vkm::MemoryUsage::AutoPreferHost,
         MemoryPreset::Dynamic => vkm::MemoryUsage::AutoPreferDevice,
+        MemoryPreset::DeviceUpload => vkm::MemoryUsage::Auto,
         MemoryPreset::TransientAttachment => vkm::MemoryUsage::GpuLazy,
     };
 
@@ -446,6 +860,12 @@ fn allocation_create_info(
         }
     }
 
+    if memory == MemoryPreset::DeviceUpload {
+        flags |= vkm::AllocationCreateFlags::HOST_ACCESS_SEQUENTIAL_WRITE
+            | vkm::AllocationCreateFlags::HOST_ACCESS_ALLOW_TRANSFER_INSTEAD;
+        preferred_flags |= vk::MemoryPropertyFlags::DEVICE_LOCAL;
+    }
+
     vkm::AllocationCreateInfo {
         usage,
         flags,
@@ -459,20 +879,72 @@ impl BufferImpl {
         device: RawDevice,
         info: &BufferInfo<'_>,
         create_info: vkm::AllocationCreateInfo,
+        imported: Option<ExternalHandle>,
     ) -> Result<BufferImpl, GPUError> {
-        let sharing = if info.usage.contains(BufferUsage::SHARE) {
-            vk::SharingMode::CONCURRENT
-        } else {
-            vk::SharingMode::EXCLUSIVE
-        };
+        let mut external_info =
+            vk::ExternalMemoryBufferCreateInfo::default().handle_types(EXTERNAL_MEMORY_HANDLE_TYPE);
+        let mut buffer_info = Self::raw_info(info);
 
-        let buffer_info = vk::BufferCreateInfo::default()
-            .size(info.size as u64)
-            .sharing_mode(sharing)
-            .usage(info.usage.into());
+        let mut create_info = create_info;
+        if info.usage.contains(BufferUsage::EXTERNAL) {
+            buffer_info = buffer_info.push_next(&mut external_info);
+            create_info.flags |= vkm::AllocationCreateFlags::DEDICATED_MEMORY;
+        }
+
+        // sparse buffers get their memory through `Queue::bind_sparse_buffer` instead
+        if info.usage.contains(BufferUsage::SPARSE_BINDING) {
+            let handle = unsafe { device.handle.create_buffer(&buffer_info, None) }?;
+            if let Some(label) = &info.label {
+                unsafe { device.attach_label(handle, label) };
+            }
+            return Ok(BufferImpl {
+                handle,
+                allocation: UnsafeCell::new(None),
+                usage: info.usage,
+                device,
+                pool: None,
+            });
+        }
 
-        let (handle, allocation) =
-            unsafe { device.allocator.create_buffer(&buffer_info, &create_info)? };
+        if let (Some(pool), Some(imported)) = (&info.pool, imported) {
+            let handle = unsafe { device.handle.create_buffer(&buffer_info, None) }?;
+            let allocation =
+                unsafe { Self::import_memory(&device, pool, handle, imported, &create_info) }
+                    .inspect_err(|_| unsafe { device.handle.destroy_buffer(handle, None) })?;
+            if let Some(label) = &info.label {
+                unsafe { device.attach_label(handle, label) };
+            }
+            return Ok(BufferImpl {
+                handle,
+                allocation: UnsafeCell::new(Some(allocation)),
+                usage: info.usage,
+                device,
+                pool: Some(pool.clone()),
+            });
+        }
+
+        let (handle, allocation) = match (&info.pool, info.alignment) {
+            (Some(pool), None) => unsafe {
+                pool.inner
+                    .handle
+                    .create_buffer(&buffer_info, &create_info)?
+            },
+            (Some(pool), Some(alignment)) => unsafe {
+                pool.inner.handle.create_buffer_with_alignment(
+                    &buffer_info,
+                    &create_info,
+                    alignment,
+                )?
+            },
+            (None, None) => unsafe { device.allocator.create_buffer(&buffer_info, &create_info)? },
+            (None, Some(alignment)) => unsafe {
+                device.allocator.create_buffer_with_alignment(
+                    &buffer_info,
+                    &create_info,
+                    alignment,
+                )?
+            },
+        };
 
         if let Some(label) = &info.label {
             unsafe { device.attach_label(handle, label) };
@@ -480,14 +952,72 @@ impl BufferImpl {
 
         Ok(BufferImpl {
             handle,
-            allocation: UnsafeCell::new(allocation),
+            allocation: UnsafeCell::new(Some(allocation)),
             usage: info.usage,
             device,
+            pool: info.pool.clone(),
         })
     }
 
+    /// Allocates `buffer`'s memory from the importing `pool` and binds it.
+    ///
+    /// Allocation and binding are split so `imported` is given up as soon as the driver owns it;
+    /// a failed bind then frees the memory, which closes an fd, instead of the caller doing so.
+    unsafe fn import_memory(
+        device: &RawDevice,
+        pool: &MemoryPool,
+        buffer: vk::Buffer,
+        imported: ExternalHandle,
+        create_info: &vkm::AllocationCreateInfo,
+    ) -> Result<vkm::Allocation, GPUError> {
+        let requirements = unsafe { device.handle.get_buffer_memory_requirements(buffer) };
+        if imported_size(&imported).is_some_and(|size| size < requirements.size) {
+            return Err(GPUError::Validation(
+                "imported memory is smaller than the buffer's allocation size",
+            ));
+        }
+
+        let mut allocation = unsafe {
+            pool.inner
+                .handle
+                .allocate_memory_for_buffer(buffer, create_info)
+        }?;
+        release_imported(imported);
+        if let Err(err) = unsafe { device.allocator.bind_buffer_memory(&allocation, buffer) } {
+            unsafe { device.allocator.free_memory(&mut allocation) };
+            return Err(err.into());
+        }
+        Ok(allocation)
+    }
+
+    fn raw_info(info: &BufferInfo<'_>) -> vk::BufferCreateInfo<'static> {
+        let sharing = if info.usage.contains(BufferUsage::SHARE) {
+            vk::SharingMode::CONCURRENT
+        } else {
+            vk::SharingMode::EXCLUSIVE
+        };
+        let flags = if info.usage.contains(BufferUsage::SPARSE_BINDING) {
+            vk::BufferCreateFlags::SPARSE_BINDING
+        } else {
+            vk::BufferCreateFlags::empty()
+        };
+        vk::BufferCreateInfo::default()
+            .flags(flags)
+            .size(info.size as u64)
+            .sharing_mode(sharing)
+            .usage(info.usage.into())
+    }
+
+    fn allocation(&self) -> &vkm::Allocation {
+        unsafe { &*self.allocation.get() }
+            .as_ref()
+            .expect("sparse buffers have no allocation")
+    }
+
     pub unsafe fn map(&self, offset: usize) -> *mut u8 {
-        let allocation = unsafe { self.allocation.get().as_mut().unwrap() };
+        let allocation = unsafe { self.allocation.get().as_mut().unwrap() }
+            .as_mut()
+            .expect("sparse buffers cannot be mapped");
         unsafe {
             self.device
                 .allocator
@@ -498,12 +1028,33 @@ impl BufferImpl {
     }
 
     pub unsafe fn unmap(&self) {
-        let allocation = unsafe { self.allocation.get().as_mut().unwrap() };
+        let allocation = unsafe { self.allocation.get().as_mut().unwrap() }
+            .as_mut()
+            .expect("sparse buffers cannot be mapped");
         unsafe { self.device.allocator.unmap_memory(allocation) };
     }
 
+    pub(crate) fn allocation_info(&self) -> vkm::AllocationInfo {
+        let allocation = self.allocation();
+        self.device.allocator.get_allocation_info(allocation)
+    }
+
+    /// Empty for sparse buffers, which may be backed by several memory types.
+    pub fn memory_properties(&self) -> vk::MemoryPropertyFlags {
+        let Some(allocation) = (unsafe { &*self.allocation.get() }) else {
+            return vk::MemoryPropertyFlags::empty();
+        };
+        let memory_type = self
+            .device
+            .allocator
+            .get_allocation_info(allocation)
+            .memory_type;
+        let properties = unsafe { self.device.allocator.get_memory_properties() };
+        properties.memory_types[memory_type as usize].property_flags
+    }
+
     pub unsafe fn flush(&self, offset: usize, size: usize) {
-        let allocation = unsafe { &*self.allocation.get() };
+        let allocation = self.allocation();
         self.device
             .allocator
             .flush_allocation(allocation, offset as vk::DeviceSize, size as vk::DeviceSize)
@@ -511,7 +1062,7 @@ impl BufferImpl {
     }
 
     pub unsafe fn invalidate(&self, offset: usize, size: usize) {
-        let allocation = unsafe { &*self.allocation.get() };
+        let allocation = self.allocation();
         self.device
             .allocator
             .invalidate_allocation(allocation, offset as vk::DeviceSize, size as vk::DeviceSize)
@@ -519,13 +1070,224 @@ impl BufferImpl {
     }
 }
 
+fn validate_alignment(alignment: Option<u64>) -> Result<(), GPUError> {
+    if alignment.is_some_and(|alignment| !alignment.is_power_of_two()) {
+        return Err(GPUError::Validation(
+            "buffer alignment must be a power of two",
+        ));
+    }
+    Ok(())
+}
+
+/// Whether `offset..offset + size` fits in `len` bytes without overflowing `usize`.
+fn host_range_in_bounds(offset: usize, size: usize, len: usize) -> bool {
+    offset.checked_add(size).is_some_and(|end| end <= len)
+}
+
 impl Drop for BufferImpl {
     fn drop(&mut self) {
-        let allocation = self.allocation.get_mut();
+        let device = &self.device;
         unsafe {
-            self.device
-                .allocator
-                .destroy_buffer(self.handle, allocation);
+            match self.allocation.get_mut() {
+                Some(allocation) => device.allocator.destroy_buffer(self.handle, allocation),
+                None => device.handle.destroy_buffer(self.handle, None),
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn host_range_rejects_overflow_and_overrun() {
+        assert!(host_range_in_bounds(0, 16, 16));
+        assert!(host_range_in_bounds(16, 0, 16));
+        assert!(!host_range_in_bounds(8, 9, 16));
+        assert!(!host_range_in_bounds(usize::MAX, 2, 16));
+    }
+
+    #[test]
+    fn alignment_must_be_a_power_of_two() {
+        assert!(validate_alignment(None).is_ok());
+        assert!(validate_alignment(Some(1)).is_ok());
+        assert!(validate_alignment(Some(4096)).is_ok());
+        assert!(validate_alignment(Some(0)).is_err());
+        assert!(validate_alignment(Some(48)).is_err());
+    }
+
+    #[test]
+    #[ignore = "requires a Vulkan device"]
+    fn aligned_buffers_start_on_the_alignment() {
+        use crate::{MemoryPoolInfo, QueueFlags, device::test_device};
+
+        const ALIGNMENT: u64 = 4096;
+
+        let (device, _queue) = test_device(QueueFlags::COMPUTE);
+        let pool = device
+            .create_memory_pool(&MemoryPoolInfo {
+                block_size: 1 << 20,
+                ..Default::default()
+            })
+            .unwrap();
+
+        for pool in [None, Some(&pool)] {
+            // A small buffer first, so the aligned one cannot land at offset 0 by accident.
+            let _padding = device
+                .create_buffer(&BufferDesc {
+                    size: 16,
+                    usage: BufferUses::STORAGE,
+                    pool,
+                    ..Default::default()
+                })
+                .unwrap();
+            let buffer = device
+                .create_buffer(&BufferDesc {
+                    size: 16,
+                    usage: BufferUses::STORAGE,
+                    pool,
+                    alignment: Some(ALIGNMENT),
+                    ..Default::default()
+                })
+                .unwrap();
+            assert_eq!(buffer.inner.allocation_info().offset % ALIGNMENT, 0);
+        }
+    }
+
+    #[test]
+    #[ignore = "requires a Vulkan device"]
+    fn external_buffers_round_trip() {
+        use crate::{QueueFlags, device::test_device_with};
+
+        let (device, _queue) = test_device_with(QueueFlags::COMPUTE, |adapter| {
+            let mut info = crate::DeviceCreateInfo::default();
+            info.features = info
+                .features
+                .external_memory(adapter.features().external_memory);
+            info
+        });
+        if !device.inner.features.external_memory {
+            return;
+        }
+
+        let desc = BufferDesc {
+            size: 4096,
+            usage: BufferUses::STORAGE,
+            ..Default::default()
+        };
+        let plain = device.create_buffer(&desc).unwrap();
+        assert!(plain.export_memory_handle().is_err());
+
+        let exported = device
+            .create_buffer(&BufferDesc {
+                usage: BufferUses::STORAGE | BufferUses::EXTERNAL,
+                ..desc
+            })
+            .unwrap();
+        let handle = exported.export_memory_handle().unwrap();
+        let imported = device
+            .import_external_buffer(handle, 4096, BufferUses::STORAGE)
+            .unwrap();
+        assert_eq!(imported.allocation_size(), exported.allocation_size());
+    }
+
+    #[test]
+    #[ignore = "requires a Vulkan device"]
+    fn mapped_slices_validate_instead_of_panicking() {
+        use crate::{QueueFlags, device::test_device};
+
+        let (device, _queue) = test_device(QueueFlags::COMPUTE);
+        let gpu_only = device
+            .create_buffer(&BufferDesc {
+                size: 64,
+                usage: BufferUses::STORAGE,
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(gpu_only.mapped_slice::<u32>().is_err());
+
+        let mut dynamic = device
+            .create_buffer(&BufferDesc {
+                size: 64,
+                usage: BufferUses::STORAGE,
+                memory: MemoryPreset::Dynamic,
+                host_access: HostAccess::ReadWriteRandom,
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(dynamic.mapped_slice::<()>().is_err());
+
+        dynamic.mapped_slice_mut::<u32>().unwrap().fill(7);
+        let values = dynamic.mapped_slice::<u32>().unwrap();
+        assert_eq!(values.len(), 16);
+        assert!(values.iter().all(|&value| value == 7));
+    }
+
+    #[test]
+    #[ignore = "requires a Vulkan device"]
+    fn sparse_buffers_bind_memory_through_the_queue() {
+        use crate::{QueueFlags, device::test_device_with};
+
+        let (device, queue) = test_device_with(QueueFlags::COMPUTE, |adapter| {
+            let mut info = crate::DeviceCreateInfo::default();
+            info.features = info
+                .features
+                .sparse_binding(adapter.features().sparse_binding);
+            info
+        });
+        let desc = BufferDesc {
+            size: 1 << 16,
+            usage: BufferUses::STORAGE | BufferUses::SPARSE_BINDING,
+            ..Default::default()
+        };
+        if !device.inner.features.sparse_binding {
+            assert!(device.create_buffer(&desc).is_err());
+            return;
+        }
+        if !queue.supports(vk::QueueFlags::SPARSE_BINDING) {
+            return;
+        }
+
+        assert!(
+            device
+                .create_buffer(&BufferDesc {
+                    memory: MemoryPreset::Upload,
+                    ..desc.clone()
+                })
+                .is_err()
+        );
+        let buffer = device.create_buffer(&desc).unwrap();
+        assert!(!buffer.is_host_visible());
+
+        let plain = device
+            .create_buffer(&BufferDesc {
+                usage: BufferUses::STORAGE,
+                ..desc.clone()
+            })
+            .unwrap();
+        assert!(queue.bind_sparse_buffer(&plain, &[]).is_err());
+
+        let raw = &device.inner.handle;
+        let requirements = unsafe { raw.get_buffer_memory_requirements(buffer.inner.handle) };
+        assert_eq!(buffer.allocation_size(), requirements.size);
+        let memory = unsafe {
+            raw.allocate_memory(
+                &vk::MemoryAllocateInfo::default()
+                    .allocation_size(requirements.size)
+                    .memory_type_index(requirements.memory_type_bits.trailing_zeros()),
+                None,
+            )
+        }
+        .unwrap();
+
+        let bind = vk::SparseMemoryBind::default()
+            .size(requirements.size)
+            .memory(memory);
+        let submission = queue.bind_sparse_buffer(&buffer, &[bind]).unwrap();
+        queue.wait_for(submission).unwrap();
+
+        drop(buffer);
+        unsafe { raw.free_memory(memory, None) };
+    }
+}
@@ -1,26 +1,59 @@
 use ash::vk;
 use parking_lot::Mutex;
 use std::{
+    any::Any,
     cell::{Cell, RefCell, UnsafeCell},
     collections::HashMap,
     ops,
     rc::Rc,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
     thread::{self, ThreadId},
 };
 
 use crate::{
-    BlitImageInfo, Buffer, BufferTransition, ComputePipeline, CopyBufferInfo,
-    CopyBufferToImageInfo, CopyImageInfo, DescriptorSet, GPUError, Image, ImageTransition, Queue,
-    RenderPipeline, Semaphore,
+    BlitImageInfo, Buffer, BufferAccessTransition, BufferTransition, BufferUses, ComputeGraph,
+    ComputePipeline, CopyBufferInfo, CopyBufferToImageInfo, CopyImageInfo, CopyImageToBufferInfo,
+    DescriptorSet, Device, Event, Frame, GPUError, Image, ImageLayoutTransition, ImageTransition,
+    ImageView, Queue, RenderPipeline, Semaphore, SubmitToken, Swapchain,
+    image::{format_aspect, validate_blit},
     raw::{ComputePipelineImpl, QueueImpl, RawDevice, RenderPipelineImpl},
 };
 
 #[derive(Debug)]
 pub struct CommandPools {
     pub device: RawDevice,
+    pub config: CommandPoolConfig,
     pub pools: Mutex<HashMap<ThreadId, Rc<ThreadCommandPool>>>,
 }
 
+/// How each per-thread command pool grows and how many idle buffers it keeps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommandPoolConfig {
+    /// Buffers allocated at once when the ready list runs dry.
+    pub batch_size: u32,
+    /// Completed buffers beyond this many idle ones are freed back to the driver.
+    pub max_cached: usize,
+}
+
+impl Default for CommandPoolConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: 5,
+            max_cached: 10,
+        }
+    }
+}
+
+/// Command buffer allocations and frees across a queue's pools, for tuning `CommandPoolConfig`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CommandPoolStats {
+    pub allocated: u64,
+    pub freed: u64,
+}
+
 unsafe impl Send for CommandPools {}
 unsafe impl Sync for CommandPools {}
 
@@ -28,8 +61,11 @@ unsafe impl Sync for CommandPools {}
 pub struct ThreadCommandPool {
     pub handle: vk::CommandPool,
     pub device: RawDevice,
+    pub config: CommandPoolConfig,
     pub ready: RefCell<Vec<CommandBufferImpl>>,
     pub dropped: RefCell<Vec<DroppedCommandBuffer>>,
+    pub allocated: AtomicU64,
+    pub freed: AtomicU64,
 }
 
 #[derive(Debug)]
@@ -43,12 +79,19 @@ pub struct CommandBufferImpl {
     pub submission: Rc<Cell<u64>>,
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Clone)]
 pub struct DroppedCommandBuffer {
     pub handle: vk::CommandBuffer,
     pub submission: u64,
+    /// Released once `submission` completes.
+    pub resources: Vec<KeepAlive>,
 }
 
+/// A resource referenced by recorded commands, held until the GPU is done with them.
+pub type KeepAlive = Arc<dyn Any>;
+
+/// Holds the buffers, images, pipelines, events, descriptor sets and render attachments its
+/// commands reference until the submission completes.
 #[derive(Debug)]
 pub struct CommandRecorder {
     pub inner: Rc<UnsafeCell<CommandRecorderImpl>>,
@@ -59,29 +102,210 @@ pub struct CommandRecorderImpl {
     pub buffer: CommandBufferImpl,
     pub pool: Rc<ThreadCommandPool>,
     pub device: RawDevice,
+    pub queue_flags: vk::QueueFlags,
+    /// Whether the currently bound compute pipeline was created with `DISPATCH_BASE`.
+    pub dispatch_base: bool,
+    /// Viewports and scissors of the currently bound render pipeline.
+    pub viewport_count: u32,
+    /// Everything recorded commands reference, keyed by address so each is held once.
+    pub resources: HashMap<usize, KeepAlive>,
 }
 
 #[derive(Debug, Copy, Clone)]
 pub struct RenderInfo<'a> {
     pub area: vk::Rect2D,
     pub layers: u32,
+    /// Bitmask of views to broadcast to; non-zero requires the `multiview` device feature.
+    pub view_mask: u32,
     pub colors: &'a [vk::RenderingAttachmentInfo<'a>],
     pub depth: Option<vk::RenderingAttachmentInfo<'a>>,
     pub stencil: Option<vk::RenderingAttachmentInfo<'a>>,
+    /// Views behind `colors`, `depth` and `stencil`, including resolve targets; `begin_render`
+    /// holds them until the submission completes.
+    pub attachment_views: &'a [&'a ImageView],
+    /// Attachments read back in this pass need `vk::ImageLayout::RENDERING_LOCAL_READ_KHR`;
+    /// requires the `dynamic_rendering_local_read` device feature.
+    pub local_read: Option<LocalReadInfo<'a>>,
+}
+
+/// A pixel rectangle of an attachment, e.g. one half of a split-screen frame.
+///
+/// Use the same region for `RenderInfo::area` and `RenderRecorder::region` so the viewport and
+/// scissor line up with the render area.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Region {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Region {
+    pub fn new(x: i32, y: i32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn rect(&self) -> vk::Rect2D {
+        vk::Rect2D {
+            offset: vk::Offset2D {
+                x: self.x,
+                y: self.y,
+            },
+            extent: vk::Extent2D {
+                width: self.width,
+                height: self.height,
+            },
+        }
+    }
+
+    /// Viewport covering the region with the full `0..1` depth range.
+    pub fn viewport(&self) -> vk::Viewport {
+        vk::Viewport {
+            x: self.x as f32,
+            y: self.y as f32,
+            width: self.width as f32,
+            height: self.height as f32,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        }
+    }
+}
+
+impl From<vk::Extent2D> for Region {
+    fn from(extent: vk::Extent2D) -> Self {
+        Self::new(0, 0, extent.width, extent.height)
+    }
+}
+
+impl From<vk::Rect2D> for Region {
+    fn from(rect: vk::Rect2D) -> Self {
+        Self::new(
+            rect.offset.x,
+            rect.offset.y,
+            rect.extent.width,
+            rect.extent.height,
+        )
+    }
+}
+
+/// Color attachments in `COLOR_ATTACHMENT_OPTIMAL` that store their result.
+///
+/// Chain further `vk::RenderingAttachmentInfo` setters, e.g. `store_op`, to customize.
+pub struct ColorAttachment;
+
+impl ColorAttachment {
+    pub fn clear(view: &ImageView, color: [f32; 4]) -> vk::RenderingAttachmentInfo<'static> {
+        Self::load(view)
+            .load_op(vk::AttachmentLoadOp::CLEAR)
+            .clear_value(vk::ClearValue {
+                color: vk::ClearColorValue { float32: color },
+            })
+    }
+
+    pub fn load(view: &ImageView) -> vk::RenderingAttachmentInfo<'static> {
+        vk::RenderingAttachmentInfo::default()
+            .image_view(view.inner.handle)
+            .image_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+            .load_op(vk::AttachmentLoadOp::LOAD)
+            .store_op(vk::AttachmentStoreOp::STORE)
+    }
+}
+
+/// Depth attachments in `DEPTH_STENCIL_ATTACHMENT_OPTIMAL` that store their result.
+pub struct DepthAttachment;
+
+impl DepthAttachment {
+    pub fn clear(view: &ImageView, depth: f32) -> vk::RenderingAttachmentInfo<'static> {
+        Self::load(view)
+            .load_op(vk::AttachmentLoadOp::CLEAR)
+            .clear_value(vk::ClearValue {
+                depth_stencil: vk::ClearDepthStencilValue { depth, stencil: 0 },
+            })
+    }
+
+    pub fn load(view: &ImageView) -> vk::RenderingAttachmentInfo<'static> {
+        vk::RenderingAttachmentInfo::default()
+            .image_view(view.inner.handle)
+            .image_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
+            .load_op(vk::AttachmentLoadOp::LOAD)
+            .store_op(vk::AttachmentStoreOp::STORE)
+    }
+}
+
+/// Attachment remapping for reading attachments as input attachments inside one render pass.
+///
+/// Must match the `RenderPipelineInfo::local_read` of pipelines drawn in that pass.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct LocalReadInfo<'a> {
+    /// Fragment output location per color attachment; empty keeps the identity mapping.
+    pub color_locations: &'a [u32],
+    /// `input_attachment_index` per color attachment; empty keeps the identity mapping.
+    pub color_input_indices: &'a [u32],
+    pub depth_input_index: Option<u32>,
+    pub stencil_input_index: Option<u32>,
+}
+
+impl<'a> LocalReadInfo<'a> {
+    pub(crate) fn locations(
+        &self,
+        color_count: usize,
+    ) -> vk::RenderingAttachmentLocationInfoKHR<'a> {
+        let mut info = vk::RenderingAttachmentLocationInfoKHR::default();
+        if self.color_locations.is_empty() {
+            info.color_attachment_count = color_count as u32;
+        } else {
+            info = info.color_attachment_locations(self.color_locations);
+        }
+        info
+    }
+
+    pub(crate) fn input_indices(
+        &self,
+        color_count: usize,
+    ) -> vk::RenderingInputAttachmentIndexInfoKHR<'_> {
+        let mut info = vk::RenderingInputAttachmentIndexInfoKHR::default();
+        if self.color_input_indices.is_empty() {
+            info.color_attachment_count = color_count as u32;
+        } else {
+            info = info.color_attachment_input_indices(self.color_input_indices);
+        }
+        if let Some(index) = &self.depth_input_index {
+            info = info.depth_input_attachment_index(index);
+        }
+        if let Some(index) = &self.stencil_input_index {
+            info = info.stencil_input_attachment_index(index);
+        }
+        info
+    }
 }
 
 pub struct RenderRecorder<'a> {
     pub command_recorder: &'a mut CommandRecorder,
 }
 
+/// Recorder limited to copies, fills and barriers, for dedicated transfer queues.
+///
+/// Created by `Queue::record_transfer`; compute and graphics commands aren't reachable.
+pub struct TransferRecorder {
+    recorder: CommandRecorder,
+}
+
 impl Default for RenderInfo<'_> {
     fn default() -> Self {
         Self {
             area: vk::Rect2D::default(),
             layers: 1,
+            view_mask: 0,
             colors: &[],
             depth: None,
             stencil: None,
+            attachment_views: &[],
+            local_read: None,
         }
     }
 }
@@ -93,25 +317,146 @@ impl CommandRecorder {
         CommandBuffer { inner: buffer }
     }
 
-    pub fn image_transition(&mut self, image: &Image, transition: ImageTransition) {
+    /// Discards everything recorded so far and starts recording again on the same buffer.
+    pub fn reset(&mut self) {
+        let inner = unsafe { &mut *self.inner.get() };
+        assert!(
+            !inner.is_finished(),
+            "CommandRecorder::reset called after finish"
+        );
+        unsafe { inner.reset() };
+    }
+
+    /// Drops the recording and hands the buffer straight back to the pool.
+    ///
+    /// Falls back to the regular drop path if the recording was already finished.
+    pub fn abandon(self) {
+        let inner = unsafe { &mut *self.inner.get() };
+        if !inner.is_finished() {
+            unsafe { inner.abandon() };
+        }
+    }
+
+    /// Records `vkCmdPipelineBarrier2` as given; the typed transition helpers are built on this.
+    ///
+    /// # Safety
+    /// Every handle referenced by `info` must be live and owned by this device, and queue family
+    /// indices must match the queues the resources are used on.
+    pub unsafe fn raw_barrier(&mut self, info: &vk::DependencyInfo<'_>) {
         let inner = unsafe { &mut *self.inner.get() };
+        unsafe { inner.pipeline_barrier(info) };
+    }
+
+    pub fn image_transition(&mut self, image: &Image, mut transition: ImageTransition) {
+        if transition.aspect.is_empty() {
+            transition.aspect = format_aspect(image.format);
+        }
+        let inner = unsafe { &mut *self.inner.get() };
+        inner.keep_alive(&image.inner);
         unsafe { inner.image_transition(image.inner.handle, transition) };
     }
 
     pub fn buffer_transition(&mut self, buffer: &Buffer, transition: BufferTransition) {
         let inner = unsafe { &mut *self.inner.get() };
+        inner.keep_alive(&buffer.inner);
         unsafe { inner.buffer_transition(buffer.inner.handle, transition) };
     }
 
+    /// Moves an acquired swapchain image to `GENERAL` so compute can write it as a storage image.
+    ///
+    /// The previous contents are discarded. The swapchain must be created with `storage`, and the
+    /// submission should wait on the acquire semaphore at `COMPUTE_SHADER`.
+    pub fn transition_swapchain_for_compute(&mut self, swapchain: &Swapchain, frame: Frame) {
+        debug_assert!(
+            swapchain.is_storage(),
+            "swapchain images need `storage` to be written by compute"
+        );
+        self.image_transition(
+            swapchain.image(frame),
+            ImageTransition {
+                from: ImageLayoutTransition::UNDEFINED,
+                to: ImageLayoutTransition::COMPUTE,
+                ..Default::default()
+            },
+        );
+    }
+
+    /// Makes compute writes to a swapchain image visible to presentation.
+    pub fn transition_swapchain_for_present(&mut self, swapchain: &Swapchain, frame: Frame) {
+        self.image_transition(
+            swapchain.image(frame),
+            ImageTransition {
+                from: ImageLayoutTransition::COMPUTE,
+                to: ImageLayoutTransition::PRESENT,
+                ..Default::default()
+            },
+        );
+    }
+
+    /// Orders two passes with a global memory barrier, making `from`'s writes visible to `to`.
+    ///
+    /// Covers every buffer and image at once; images that change layout still need
+    /// `image_transition`.
+    pub fn barrier_between_passes(
+        &mut self,
+        from: BufferAccessTransition,
+        to: BufferAccessTransition,
+    ) {
+        let inner = unsafe { &mut *self.inner.get() };
+        unsafe { inner.memory_barrier(from, to) };
+    }
+
+    /// Signals `event` once the work before it reaches `from`; a later `wait_event` makes
+    /// those accesses visible to `to`.
+    pub fn set_event(
+        &mut self,
+        event: &Event,
+        from: BufferAccessTransition,
+        to: BufferAccessTransition,
+    ) {
+        let inner = unsafe { &mut *self.inner.get() };
+        inner.keep_alive(&event.inner);
+        unsafe { inner.set_event(event.inner.handle, from, to) };
+    }
+
+    /// Waits for `event`, making the accesses before it visible to `to`.
+    ///
+    /// `from` and `to` must match the `set_event` that signals it. For an event set with
+    /// `Event::set`, `from` has to be the `HOST` stage with `HOST_WRITE` access.
+    pub fn wait_event(
+        &mut self,
+        event: &Event,
+        from: BufferAccessTransition,
+        to: BufferAccessTransition,
+    ) {
+        let inner = unsafe { &mut *self.inner.get() };
+        inner.keep_alive(&event.inner);
+        unsafe { inner.wait_event(event.inner.handle, from, to) };
+    }
+
+    /// Unsignals `event` after the work before it reaches `stage`.
+    pub fn reset_event(&mut self, event: &Event, stage: vk::PipelineStageFlags2) {
+        let inner = unsafe { &mut *self.inner.get() };
+        inner.keep_alive(&event.inner);
+        unsafe { inner.reset_event(event.inner.handle, stage) };
+    }
+
+    /// Records every node of `graph` in order with the barriers it inferred.
+    pub fn execute(&mut self, graph: ComputeGraph<'_>) {
+        graph.record(self);
+    }
+
     pub fn bind_render_pipeline(&mut self, pipeline: &RenderPipeline) {
         let inner = unsafe { &mut *self.inner.get() };
         let inner_pipeline = &pipeline.inner;
+        inner.keep_alive(inner_pipeline);
         unsafe { inner.bind_render_pipeline(inner_pipeline) };
     }
 
     pub fn bind_compute_pipeline(&mut self, pipeline: &ComputePipeline) {
         let inner = unsafe { &mut *self.inner.get() };
         let inner_pipeline = &pipeline.inner;
+        inner.keep_alive(inner_pipeline);
         unsafe { inner.bind_compute_pipeline(inner_pipeline) };
     }
 
@@ -120,6 +465,34 @@ impl CommandRecorder {
         F: FnOnce(&mut RenderRecorder<'a>),
     {
         let inner = unsafe { &mut *self.inner.get() };
+        debug_assert!(
+            inner.queue_flags.contains(vk::QueueFlags::GRAPHICS),
+            "begin_render recorded on a queue without GRAPHICS support"
+        );
+        assert!(
+            info.view_mask == 0 || inner.device.features.multiview,
+            "RenderInfo::view_mask requires the multiview device feature"
+        );
+        assert!(
+            info.local_read.is_none() || inner.device.features.dynamic_rendering_local_read,
+            "RenderInfo::local_read requires the dynamic_rendering_local_read device feature"
+        );
+        debug_assert!(
+            info.colors
+                .iter()
+                .chain(&info.depth)
+                .chain(&info.stencil)
+                .flat_map(|attachment| [attachment.image_view, attachment.resolve_image_view])
+                .filter(|&view| view != vk::ImageView::null())
+                .all(|view| info
+                    .attachment_views
+                    .iter()
+                    .any(|listed| listed.inner.handle == view)),
+            "every attachment view must be listed in RenderInfo::attachment_views"
+        );
+        for view in info.attachment_views {
+            inner.keep_alive(&view.inner);
+        }
         unsafe { inner.begin_render(info) };
 
         {
@@ -133,21 +506,70 @@ impl CommandRecorder {
 
     pub fn copy_image(&mut self, info: &CopyImageInfo<'_>) {
         let inner = unsafe { &mut *self.inner.get() };
+        inner.keep_alive(&info.src.inner);
+        inner.keep_alive(&info.dst.inner);
         unsafe { inner.copy_image(info) };
     }
 
     pub fn copy_buffer(&mut self, info: &CopyBufferInfo<'_>) {
         let inner = unsafe { &mut *self.inner.get() };
+        inner.keep_alive(&info.src.inner);
+        inner.keep_alive(&info.dst.inner);
         unsafe { inner.copy_buffer(info) };
     }
 
     pub fn copy_buffer_to_image(&mut self, info: &CopyBufferToImageInfo<'_>) {
         let inner = unsafe { &mut *self.inner.get() };
+        inner.keep_alive(&info.src.inner);
+        inner.keep_alive(&info.dst.inner);
         unsafe { inner.copy_buffer_to_image(info) };
     }
 
+    pub fn copy_image_to_buffer(&mut self, info: &CopyImageToBufferInfo<'_>) {
+        let inner = unsafe { &mut *self.inner.get() };
+        inner.keep_alive(&info.src.inner);
+        inner.keep_alive(&info.dst.inner);
+        unsafe { inner.copy_image_to_buffer(info) };
+    }
+
+    /// Fills `size` bytes at `offset` with the repeated 4-byte `data`; both must be multiples
+    /// of 4, or `size` may be `vk::WHOLE_SIZE`.
+    ///
+    /// Panics in debug builds if the buffer lacks COPY_DST or the range is misaligned.
+    pub fn fill_buffer(
+        &mut self,
+        buffer: &Buffer,
+        offset: vk::DeviceSize,
+        size: vk::DeviceSize,
+        data: u32,
+    ) {
+        debug_assert!(
+            buffer.uses.contains(BufferUses::COPY_DST),
+            "fill_buffer requires BufferUses::COPY_DST"
+        );
+        debug_assert!(
+            offset.is_multiple_of(4) && (size == vk::WHOLE_SIZE || size.is_multiple_of(4)),
+            "fill_buffer offset and size must be multiples of 4"
+        );
+        let inner = unsafe { &mut *self.inner.get() };
+        inner.keep_alive(&buffer.inner);
+        unsafe { inner.fill_buffer(buffer.inner.handle, offset, size, data) };
+    }
+
+    /// Panics in debug builds if the images lack COPY_SRC/COPY_DST or their formats can't blit.
     pub fn blit_image(&mut self, info: &BlitImageInfo<'_>) {
+        if cfg!(debug_assertions)
+            && let Err(err) = validate_blit(info)
+        {
+            panic!("blit_image: {err}");
+        }
         let inner = unsafe { &mut *self.inner.get() };
+        debug_assert!(
+            inner.queue_flags.contains(vk::QueueFlags::GRAPHICS),
+            "blit_image recorded on a queue without GRAPHICS support"
+        );
+        inner.keep_alive(&info.src.inner);
+        inner.keep_alive(&info.dst.inner);
         unsafe { inner.blit_image(info) };
     }
 
@@ -159,13 +581,22 @@ impl CommandRecorder {
         offsets: &[u32],
     ) {
         let inner = unsafe { &mut *self.inner.get() };
+        inner.keep_set_alive(set);
+        inner.keep_alive(&pipeline.inner);
         unsafe {
             inner.bind_compute_descriptor_set(set, &pipeline.inner, index, offsets);
         }
     }
 
     pub fn push_compute_constants<T: bytemuck::Pod>(&mut self, pipeline: &ComputePipeline, pc: T) {
+        debug_assert!(
+            size_of::<T>() <= pipeline.push_constant_size() as usize,
+            "push constants of {} bytes exceed the pipeline's {} byte range",
+            size_of::<T>(),
+            pipeline.push_constant_size()
+        );
         let inner = unsafe { &mut *self.inner.get() };
+        inner.keep_alive(&pipeline.inner);
         unsafe {
             inner.push_compute_constants(&pipeline.inner, pc);
         }
@@ -179,13 +610,22 @@ impl CommandRecorder {
         offsets: &[u32],
     ) {
         let inner = unsafe { &mut *self.inner.get() };
+        inner.keep_set_alive(set);
+        inner.keep_alive(&pipeline.inner);
         unsafe {
             inner.bind_render_descriptor_set(set, &pipeline.inner, index, offsets);
         }
     }
 
     pub fn push_render_constants<T: bytemuck::Pod>(&mut self, pipeline: &RenderPipeline, pc: T) {
+        debug_assert!(
+            size_of::<T>() <= pipeline.push_constant_size() as usize,
+            "push constants of {} bytes exceed the pipeline's {} byte range",
+            size_of::<T>(),
+            pipeline.push_constant_size()
+        );
         let inner = unsafe { &mut *self.inner.get() };
+        inner.keep_alive(&pipeline.inner);
         unsafe {
             inner.push_render_constants(&pipeline.inner, pc);
         }
@@ -193,8 +633,78 @@ impl CommandRecorder {
 
     pub fn dispatch(&mut self, x: u32, y: u32, z: u32) {
         let inner = unsafe { &mut *self.inner.get() };
+        debug_assert!(
+            inner.queue_flags.contains(vk::QueueFlags::COMPUTE),
+            "dispatch recorded on a queue without COMPUTE support"
+        );
         unsafe { inner.dispatch(x, y, z) };
     }
+
+    /// Dispatches `groups` workgroups starting at workgroup `base`.
+    ///
+    /// The bound compute pipeline must be created with `ComputePipelineInfo::dispatch_base`.
+    pub fn dispatch_base(&mut self, base: [u32; 3], groups: [u32; 3]) {
+        let inner = unsafe { &mut *self.inner.get() };
+        debug_assert!(
+            inner.queue_flags.contains(vk::QueueFlags::COMPUTE),
+            "dispatch_base recorded on a queue without COMPUTE support"
+        );
+        assert!(
+            inner.dispatch_base,
+            "dispatch_base requires a compute pipeline created with ComputePipelineInfo::dispatch_base"
+        );
+        unsafe { inner.dispatch_base(base, groups) };
+    }
+}
+
+impl TransferRecorder {
+    pub fn finish(&mut self) -> CommandBuffer {
+        self.recorder.finish()
+    }
+
+    pub fn abandon(self) {
+        self.recorder.abandon();
+    }
+
+    /// # Safety
+    /// See `CommandRecorder::raw_barrier`.
+    pub unsafe fn raw_barrier(&mut self, info: &vk::DependencyInfo<'_>) {
+        unsafe { self.recorder.raw_barrier(info) };
+    }
+
+    pub fn image_transition(&mut self, image: &Image, transition: ImageTransition) {
+        self.recorder.image_transition(image, transition);
+    }
+
+    pub fn buffer_transition(&mut self, buffer: &Buffer, transition: BufferTransition) {
+        self.recorder.buffer_transition(buffer, transition);
+    }
+
+    pub fn copy_buffer(&mut self, info: &CopyBufferInfo<'_>) {
+        self.recorder.copy_buffer(info);
+    }
+
+    pub fn copy_image(&mut self, info: &CopyImageInfo<'_>) {
+        self.recorder.copy_image(info);
+    }
+
+    pub fn copy_buffer_to_image(&mut self, info: &CopyBufferToImageInfo<'_>) {
+        self.recorder.copy_buffer_to_image(info);
+    }
+
+    pub fn copy_image_to_buffer(&mut self, info: &CopyImageToBufferInfo<'_>) {
+        self.recorder.copy_image_to_buffer(info);
+    }
+
+    pub fn fill_buffer(
+        &mut self,
+        buffer: &Buffer,
+        offset: vk::DeviceSize,
+        size: vk::DeviceSize,
+        data: u32,
+    ) {
+        self.recorder.fill_buffer(buffer, offset, size, data);
+    }
 }
 
 impl<'a> RenderRecorder<'a> {
@@ -208,6 +718,85 @@ impl<'a> RenderRecorder<'a> {
         unsafe { inner.scissor(scissor) };
     }
 
+    /// Sets viewport 0 and scissor 0 to `region`.
+    pub fn region(&mut self, region: Region) {
+        self.viewport(region.viewport());
+        self.scissor(region.rect());
+    }
+
+    /// Sets viewport 0 to cover `extent` with a negative height, so clip-space +Y points up like
+    /// in OpenGL/WGSL instead of down as in Vulkan.
+    ///
+    /// Flipping Y also reverses the winding order: triangles that are counter-clockwise in the
+    /// shader appear clockwise, so pipelines culling back faces should use `FrontFace::CLOCKWISE`.
+    pub fn viewport_flip_y(&mut self, extent: vk::Extent2D) {
+        self.viewport(flip_y_viewport(extent));
+    }
+
+    /// Sets viewports `first..first + viewports.len()`; indices past 0 require `multi_viewport`.
+    ///
+    /// Bind the render pipeline first; the range must fit its `viewport_count`.
+    pub fn viewports(&mut self, first: u32, viewports: &[vk::Viewport]) {
+        let inner = unsafe { &mut *self.command_recorder.inner.get() };
+        debug_assert!(
+            first as usize + viewports.len() <= 1 || inner.device.features.multi_viewport,
+            "viewports past index 0 require the multi_viewport device feature"
+        );
+        debug_assert!(
+            first as usize + viewports.len() <= inner.viewport_count as usize,
+            "viewports past the bound pipeline's viewport_count"
+        );
+        unsafe { inner.viewports(first, viewports) };
+    }
+
+    /// Sets scissors `first..first + scissors.len()`; indices past 0 require `multi_viewport`.
+    ///
+    /// Bind the render pipeline first; the range must fit its `viewport_count`.
+    pub fn scissors(&mut self, first: u32, scissors: &[vk::Rect2D]) {
+        let inner = unsafe { &mut *self.command_recorder.inner.get() };
+        debug_assert!(
+            first as usize + scissors.len() <= 1 || inner.device.features.multi_viewport,
+            "scissors past index 0 require the multi_viewport device feature"
+        );
+        debug_assert!(
+            first as usize + scissors.len() <= inner.viewport_count as usize,
+            "scissors past the bound pipeline's viewport_count"
+        );
+        unsafe { inner.scissors(first, scissors) };
+    }
+
+    /// Clears `rects` of the given attachments mid-pass, unlike load-op clears which cover the
+    /// whole attachment at `begin_render`.
+    pub fn clear_attachments(&mut self, clears: &[vk::ClearAttachment], rects: &[vk::ClearRect]) {
+        assert!(
+            !rects.is_empty(),
+            "clear_attachments requires at least one rect"
+        );
+        let inner = unsafe { &mut *self.command_recorder.inner.get() };
+        unsafe { inner.clear_attachments(clears, rects) };
+    }
+
+    /// Requires a pipeline created with `RenderPipelineInfo::dynamic_blend_constants`.
+    pub fn blend_constants(&mut self, constants: [f32; 4]) {
+        let inner = unsafe { &mut *self.command_recorder.inner.get() };
+        unsafe { inner.blend_constants(constants) };
+    }
+
+    pub fn stencil_reference(&mut self, faces: vk::StencilFaceFlags, reference: u32) {
+        let inner = unsafe { &mut *self.command_recorder.inner.get() };
+        unsafe { inner.stencil_reference(faces, reference) };
+    }
+
+    pub fn stencil_compare_mask(&mut self, faces: vk::StencilFaceFlags, mask: u32) {
+        let inner = unsafe { &mut *self.command_recorder.inner.get() };
+        unsafe { inner.stencil_compare_mask(faces, mask) };
+    }
+
+    pub fn stencil_write_mask(&mut self, faces: vk::StencilFaceFlags, mask: u32) {
+        let inner = unsafe { &mut *self.command_recorder.inner.get() };
+        unsafe { inner.stencil_write_mask(faces, mask) };
+    }
+
     pub fn draw(&mut self, vertex: ops::Range<u32>, instance: ops::Range<u32>) {
         let inner = unsafe { &mut *self.command_recorder.inner.get() };
         unsafe { inner.draw(vertex, instance) };
@@ -223,14 +812,61 @@ impl<'a> RenderRecorder<'a> {
         unsafe { inner.draw_indexed(index, vertex_offset, instance) };
     }
 
-    pub fn image_transition(&mut self, image: &Image, transition: ImageTransition) {
+    /// Indexed indirect draw whose draw count is read from `count` on the GPU.
+    ///
+    /// Requires `DeviceFeatures::draw_indirect_count`.
+    pub fn draw_indexed_indirect_count(
+        &mut self,
+        args: &Buffer,
+        args_offset: vk::DeviceSize,
+        count: &Buffer,
+        count_offset: vk::DeviceSize,
+        max_draws: u32,
+        stride: u32,
+    ) {
+        assert!(
+            args.uses.contains(BufferUses::INDIRECT),
+            "draw_indexed_indirect_count requires BufferUses::INDIRECT on the argument buffer"
+        );
+        assert!(
+            count.uses.contains(BufferUses::INDIRECT),
+            "draw_indexed_indirect_count requires BufferUses::INDIRECT on the count buffer"
+        );
+        let inner = unsafe { &mut *self.command_recorder.inner.get() };
+        inner.keep_alive(&args.inner);
+        inner.keep_alive(&count.inner);
+        unsafe {
+            inner.draw_indexed_indirect_count(
+                args,
+                args_offset,
+                count,
+                count_offset,
+                max_draws,
+                stride,
+            )
+        };
+    }
+
+    /// Makes attachment writes from earlier draws visible to input attachment reads in later
+    /// draws of the same pass. Requires `RenderInfo::local_read`.
+    pub fn local_read_barrier(&mut self) {
+        let inner = unsafe { &mut *self.command_recorder.inner.get() };
+        unsafe { inner.local_read_barrier() };
+    }
+
+    pub fn image_transition(&mut self, image: &Image, mut transition: ImageTransition) {
+        if transition.aspect.is_empty() {
+            transition.aspect = format_aspect(image.format);
+        }
         let inner = unsafe { &mut *self.command_recorder.inner.get() };
+        inner.keep_alive(&image.inner);
         unsafe { inner.image_transition(image.inner.handle, transition) };
     }
 
     pub fn bind_render_pipeline(&mut self, pipeline: &RenderPipeline) {
         let inner = unsafe { &mut *self.command_recorder.inner.get() };
         let inner_pipeline = &pipeline.inner;
+        inner.keep_alive(inner_pipeline);
         unsafe { inner.bind_render_pipeline(inner_pipeline) };
     }
 
@@ -242,13 +878,22 @@ impl<'a> RenderRecorder<'a> {
         offsets: &[u32],
     ) {
         let inner = unsafe { &mut *self.command_recorder.inner.get() };
+        inner.keep_set_alive(set);
+        inner.keep_alive(&pipeline.inner);
         unsafe {
             inner.bind_render_descriptor_set(set, &pipeline.inner, index, offsets);
         }
     }
 
     pub fn push_render_constants<T: bytemuck::Pod>(&mut self, pipeline: &RenderPipeline, pc: T) {
+        debug_assert!(
+            size_of::<T>() <= pipeline.push_constant_size() as usize,
+            "push constants of {} bytes exceed the pipeline's {} byte range",
+            size_of::<T>(),
+            pipeline.push_constant_size()
+        );
         let inner = unsafe { &mut *self.command_recorder.inner.get() };
+        inner.keep_alive(&pipeline.inner);
         unsafe {
             inner.push_render_constants(&pipeline.inner, pc);
         }
@@ -256,6 +901,7 @@ impl<'a> RenderRecorder<'a> {
 
     pub fn bind_vertex_buffer(&mut self, slot: u32, buffer: &Buffer, offset: vk::DeviceSize) {
         let inner = unsafe { &mut *self.command_recorder.inner.get() };
+        inner.keep_alive(&buffer.inner);
         unsafe { inner.bind_vertex_buffer(slot, buffer, offset) };
     }
 
@@ -266,17 +912,38 @@ impl<'a> RenderRecorder<'a> {
         index_type: vk::IndexType,
     ) {
         let inner = unsafe { &mut *self.command_recorder.inner.get() };
+        inner.keep_alive(&buffer.inner);
         unsafe { inner.bind_index_buffer(buffer, offset, index_type) };
     }
 
     pub fn bind_compute_pipeline(&mut self, pipeline: &ComputePipeline) {
         let inner = unsafe { &mut *self.command_recorder.inner.get() };
         let inner_pipeline = &pipeline.inner;
+        inner.keep_alive(inner_pipeline);
         unsafe { inner.bind_compute_pipeline(inner_pipeline) };
     }
 }
 
 impl CommandRecorderImpl {
+    /// Keeps `resource` alive until this buffer's submission has completed.
+    pub fn keep_alive<T: 'static>(&mut self, resource: &Arc<T>) {
+        let key = Arc::as_ptr(resource) as *const () as usize;
+        self.resources
+            .entry(key)
+            .or_insert_with(|| resource.clone());
+    }
+
+    /// Keeps `set`'s pool and every resource written into the set alive.
+    pub fn keep_set_alive(&mut self, set: &DescriptorSet) {
+        self.keep_alive(&set.pool.inner);
+        for resource in set.resources.borrow().values().flatten() {
+            let key = Arc::as_ptr(resource) as *const () as usize;
+            self.resources
+                .entry(key)
+                .or_insert_with(|| resource.clone());
+        }
+    }
+
     pub unsafe fn finish(&mut self) -> CommandBufferImpl {
         unsafe {
             let _ = self
@@ -288,7 +955,47 @@ impl CommandRecorderImpl {
         self.buffer.clone()
     }
 
-    pub unsafe fn bind_render_pipeline(&self, pipeline: &RenderPipelineImpl) {
+    /// Whether `finish` handed out the buffer or it has been submitted.
+    pub fn is_finished(&self) -> bool {
+        Rc::strong_count(&self.buffer.submission) > 1 || self.buffer.submission.get() != 0
+    }
+
+    /// # Safety
+    /// The buffer must not have been finished or submitted.
+    pub unsafe fn reset(&mut self) {
+        self.resources.clear();
+        unsafe {
+            let _ = self
+                .device
+                .handle
+                .reset_command_buffer(self.buffer.handle, vk::CommandBufferResetFlags::empty());
+            let _ = self.device.handle.begin_command_buffer(
+                self.buffer.handle,
+                &vk::CommandBufferBeginInfo::default()
+                    .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT),
+            );
+        }
+    }
+
+    /// # Safety
+    /// The buffer must not have been finished or submitted.
+    pub unsafe fn abandon(&mut self) {
+        self.resources.clear();
+        unsafe {
+            let _ = self
+                .device
+                .handle
+                .reset_command_buffer(self.buffer.handle, vk::CommandBufferResetFlags::empty());
+        }
+        self.pool.ready.borrow_mut().push(CommandBufferImpl {
+            handle: self.buffer.handle,
+            submission: Rc::new(Cell::new(0)),
+        });
+        self.buffer.handle = vk::CommandBuffer::null();
+    }
+
+    pub unsafe fn bind_render_pipeline(&mut self, pipeline: &RenderPipelineImpl) {
+        self.viewport_count = pipeline.viewport_count;
         unsafe {
             self.device.handle.cmd_bind_pipeline(
                 self.buffer.handle,
@@ -298,7 +1005,8 @@ impl CommandRecorderImpl {
         }
     }
 
-    pub unsafe fn bind_compute_pipeline(&self, pipeline: &ComputePipelineImpl) {
+    pub unsafe fn bind_compute_pipeline(&mut self, pipeline: &ComputePipelineImpl) {
+        self.dispatch_base = pipeline.dispatch_base;
         unsafe {
             self.device.handle.cmd_bind_pipeline(
                 self.buffer.handle,
@@ -324,10 +1032,92 @@ impl CommandRecorderImpl {
         }
     }
 
+    /// # Safety
+    /// The command buffer must be recording and `first + viewports.len()` must not exceed the
+    /// bound pipeline's `viewport_count`.
+    pub unsafe fn viewports(&self, first: u32, viewports: &[vk::Viewport]) {
+        unsafe {
+            self.device
+                .handle
+                .cmd_set_viewport(self.buffer.handle, first, viewports);
+        }
+    }
+
+    /// # Safety
+    /// The command buffer must be recording and `first + scissors.len()` must not exceed the
+    /// bound pipeline's `viewport_count`.
+    pub unsafe fn scissors(&self, first: u32, scissors: &[vk::Rect2D]) {
+        unsafe {
+            self.device
+                .handle
+                .cmd_set_scissor(self.buffer.handle, first, scissors);
+        }
+    }
+
+    /// # Safety
+    /// Must be recorded inside `begin_render`, and every attachment and rect must lie within the
+    /// current rendering area and layers.
+    pub unsafe fn clear_attachments(
+        &self,
+        clears: &[vk::ClearAttachment],
+        rects: &[vk::ClearRect],
+    ) {
+        unsafe {
+            self.device
+                .handle
+                .cmd_clear_attachments(self.buffer.handle, clears, rects);
+        }
+    }
+
+    /// # Safety
+    /// The command buffer must be recording.
+    pub unsafe fn blend_constants(&self, constants: [f32; 4]) {
+        unsafe {
+            self.device
+                .handle
+                .cmd_set_blend_constants(self.buffer.handle, &constants);
+        }
+    }
+
+    /// # Safety
+    /// The command buffer must be recording.
+    pub unsafe fn stencil_reference(&self, faces: vk::StencilFaceFlags, reference: u32) {
+        unsafe {
+            self.device
+                .handle
+                .cmd_set_stencil_reference(self.buffer.handle, faces, reference);
+        }
+    }
+
+    /// # Safety
+    /// The command buffer must be recording.
+    pub unsafe fn stencil_compare_mask(&self, faces: vk::StencilFaceFlags, mask: u32) {
+        unsafe {
+            self.device
+                .handle
+                .cmd_set_stencil_compare_mask(self.buffer.handle, faces, mask);
+        }
+    }
+
+    /// # Safety
+    /// The command buffer must be recording.
+    pub unsafe fn stencil_write_mask(&self, faces: vk::StencilFaceFlags, mask: u32) {
+        unsafe {
+            self.device
+                .handle
+                .cmd_set_stencil_write_mask(self.buffer.handle, faces, mask);
+        }
+    }
+
     pub unsafe fn begin_render(&self, info: &RenderInfo<'_>) {
+        assert!(
+            !self.device.compute_only,
+            "begin_render on a compute_only device"
+        );
         let mut rendering_info = vk::RenderingInfo::default()
             .render_area(info.area)
             .layer_count(info.layers)
+            .view_mask(info.view_mask)
             .color_attachments(info.colors);
 
         if let Some(depth) = &info.depth {
@@ -342,6 +1132,107 @@ impl CommandRecorderImpl {
                 .dynamic
                 .cmd_begin_rendering(self.buffer.handle, &rendering_info);
         }
+
+        if let Some(local_read) = &info.local_read {
+            let loader = self.device.ext.local_read.as_ref().expect(
+                "RenderInfo::local_read requires dynamic_rendering_local_read to be enabled on the device",
+            );
+            let locations = local_read.locations(info.colors.len());
+            let input_indices = local_read.input_indices(info.colors.len());
+            unsafe {
+                loader.cmd_set_rendering_attachment_locations(self.buffer.handle, &locations);
+                loader
+                    .cmd_set_rendering_input_attachment_indices(self.buffer.handle, &input_indices);
+            }
+        }
+    }
+
+    /// # Safety
+    /// Must be recorded inside a render pass begun with `RenderInfo::local_read`.
+    pub unsafe fn local_read_barrier(&self) {
+        let barriers = [vk::MemoryBarrier2::default()
+            .src_stage_mask(
+                vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT
+                    | vk::PipelineStageFlags2::LATE_FRAGMENT_TESTS,
+            )
+            .src_access_mask(
+                vk::AccessFlags2::COLOR_ATTACHMENT_WRITE
+                    | vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE,
+            )
+            .dst_stage_mask(vk::PipelineStageFlags2::FRAGMENT_SHADER)
+            .dst_access_mask(vk::AccessFlags2::INPUT_ATTACHMENT_READ)];
+        let dependency_info = vk::DependencyInfo::default()
+            .dependency_flags(vk::DependencyFlags::BY_REGION)
+            .memory_barriers(&barriers);
+
+        unsafe { self.pipeline_barrier(&dependency_info) };
+    }
+
+    /// # Safety
+    /// `event` must be a live event owned by this device.
+    pub unsafe fn set_event(
+        &self,
+        event: vk::Event,
+        from: BufferAccessTransition,
+        to: BufferAccessTransition,
+    ) {
+        let barriers = [global_barrier(from, to)];
+        let dependency_info = vk::DependencyInfo::default().memory_barriers(&barriers);
+        unsafe {
+            self.device
+                .ext
+                .sync2
+                .cmd_set_event2(self.buffer.handle, event, &dependency_info)
+        };
+    }
+
+    /// # Safety
+    /// `event` must be a live event owned by this device, set with the same `from` and `to`.
+    pub unsafe fn wait_event(
+        &self,
+        event: vk::Event,
+        from: BufferAccessTransition,
+        to: BufferAccessTransition,
+    ) {
+        let barriers = [global_barrier(from, to)];
+        let dependency_infos = [vk::DependencyInfo::default().memory_barriers(&barriers)];
+        unsafe {
+            self.device
+                .ext
+                .sync2
+                .cmd_wait_events2(self.buffer.handle, &[event], &dependency_infos)
+        };
+    }
+
+    /// # Safety
+    /// `event` must be a live event owned by this device.
+    pub unsafe fn reset_event(&self, event: vk::Event, stage: vk::PipelineStageFlags2) {
+        unsafe {
+            self.device
+                .ext
+                .sync2
+                .cmd_reset_event2(self.buffer.handle, event, stage)
+        };
+    }
+
+    /// # Safety
+    /// The buffer must be in the recording state.
+    pub unsafe fn memory_barrier(&self, from: BufferAccessTransition, to: BufferAccessTransition) {
+        let barriers = [global_barrier(from, to)];
+        let dependency_info = vk::DependencyInfo::default().memory_barriers(&barriers);
+
+        unsafe { self.pipeline_barrier(&dependency_info) };
+    }
+
+    /// # Safety
+    /// Every handle referenced by `info` must be live and owned by this device.
+    pub unsafe fn pipeline_barrier(&self, info: &vk::DependencyInfo<'_>) {
+        unsafe {
+            self.device
+                .ext
+                .sync2
+                .cmd_pipeline_barrier2(self.buffer.handle, info);
+        }
     }
 
     pub unsafe fn end_rendering(&self) {
@@ -383,6 +1274,35 @@ impl CommandRecorderImpl {
         }
     }
 
+    /// # Safety
+    /// Must be recorded inside a render pass, and both buffers must stay alive until the
+    /// submission completes.
+    pub unsafe fn draw_indexed_indirect_count(
+        &self,
+        args: &Buffer,
+        args_offset: vk::DeviceSize,
+        count: &Buffer,
+        count_offset: vk::DeviceSize,
+        max_draws: u32,
+        stride: u32,
+    ) {
+        let loader = self.device.ext.draw_indirect_count.as_ref().expect(
+            "draw_indexed_indirect_count requires draw_indirect_count to be enabled on the device",
+        );
+
+        unsafe {
+            loader.cmd_draw_indexed_indirect_count(
+                self.buffer.handle,
+                args.inner.handle,
+                args_offset,
+                count.inner.handle,
+                count_offset,
+                max_draws,
+                stride,
+            );
+        }
+    }
+
     pub unsafe fn bind_vertex_buffer(&self, slot: u32, buffer: &Buffer, offset: vk::DeviceSize) {
         unsafe {
             self.device.handle.cmd_bind_vertex_buffers(
@@ -440,16 +1360,11 @@ impl CommandRecorderImpl {
         }
 
         let image_memory_barriers = [barrier];
-        let dependency_info = vk::DependencyInfo::default()
-            .dependency_flags(transition.dependency)
-            .image_memory_barriers(&image_memory_barriers);
-
-        unsafe {
-            self.device
-                .ext
-                .sync2
-                .cmd_pipeline_barrier2(self.buffer.handle, &dependency_info);
-        }
+        let dependency_info = vk::DependencyInfo::default()
+            .dependency_flags(transition.dependency)
+            .image_memory_barriers(&image_memory_barriers);
+
+        unsafe { self.pipeline_barrier(&dependency_info) };
     }
 
     pub unsafe fn buffer_transition(&self, buffer: vk::Buffer, transition: BufferTransition) {
@@ -475,12 +1390,15 @@ impl CommandRecorderImpl {
             .dependency_flags(transition.dependency)
             .buffer_memory_barriers(&buffer_memory_barriers);
 
-        unsafe {
-            self.device
-                .ext
-                .sync2
-                .cmd_pipeline_barrier2(self.buffer.handle, &dependency_info);
-        }
+        unsafe { self.pipeline_barrier(&dependency_info) };
+    }
+
+    /// # Safety
+    /// Every barrier must reference a live buffer owned by this device.
+    pub unsafe fn buffer_barriers(&self, barriers: &[vk::BufferMemoryBarrier2<'_>]) {
+        let dependency_info = vk::DependencyInfo::default().buffer_memory_barriers(barriers);
+
+        unsafe { self.pipeline_barrier(&dependency_info) };
     }
 
     pub unsafe fn copy_image(&self, info: &CopyImageInfo<'_>) {
@@ -608,6 +1526,40 @@ impl CommandRecorderImpl {
         }
     }
 
+    /// # Safety
+    /// `buffer` must be live with TRANSFER_DST usage and the range must lie within it.
+    pub unsafe fn fill_buffer(
+        &self,
+        buffer: vk::Buffer,
+        offset: vk::DeviceSize,
+        size: vk::DeviceSize,
+        data: u32,
+    ) {
+        unsafe {
+            self.device
+                .handle
+                .cmd_fill_buffer(self.buffer.handle, buffer, offset, size, data);
+        }
+    }
+
+    /// # Safety
+    /// `info.src` must be in `info.src_layout` and every region must lie within both resources.
+    pub unsafe fn copy_image_to_buffer(&self, info: &CopyImageToBufferInfo<'_>) {
+        if info.regions.is_empty() {
+            return;
+        }
+
+        unsafe {
+            self.device.handle.cmd_copy_image_to_buffer(
+                self.buffer.handle,
+                info.src.inner.handle,
+                info.src_layout.into(),
+                info.dst.inner.handle,
+                info.regions,
+            );
+        }
+    }
+
     pub unsafe fn copy_buffer_to_image(&self, info: &CopyBufferToImageInfo<'_>) {
         if info.regions.is_empty() {
             return;
@@ -699,10 +1651,28 @@ impl CommandRecorderImpl {
             self.device.handle.cmd_dispatch(self.buffer.handle, x, y, z);
         }
     }
+
+    /// # Safety
+    /// The command buffer must be recording and the bound compute pipeline must have been
+    /// created with `DISPATCH_BASE`.
+    pub unsafe fn dispatch_base(&mut self, base: [u32; 3], groups: [u32; 3]) {
+        unsafe {
+            self.device.handle.cmd_dispatch_base(
+                self.buffer.handle,
+                base[0],
+                base[1],
+                base[2],
+                groups[0],
+                groups[1],
+                groups[2],
+            );
+        }
+    }
 }
 
 #[derive(Debug, Default)]
 pub struct SubmitInfo<'a> {
+    /// May be empty to only wait on and signal semaphores; the queue timeline still advances.
     pub records: &'a [CommandBuffer],
     pub wait_binary: &'a [(&'a Semaphore, vk::PipelineStageFlags)],
     pub wait_timeline: &'a [(&'a Semaphore, u64, vk::PipelineStageFlags)],
@@ -730,6 +1700,10 @@ impl Queue {
             buffer,
             pool: pool.clone(),
             device: pool.device.clone(),
+            queue_flags: self.inner.info.flags,
+            dispatch_base: false,
+            viewport_count: 1,
+            resources: HashMap::new(),
         };
 
         CommandRecorder {
@@ -737,15 +1711,134 @@ impl Queue {
         }
     }
 
+    /// Allocates command buffers up front so the first `count` recordings on this thread don't.
+    ///
+    /// Pools are per thread; call this from each thread that will record.
+    pub fn prewarm(&self, count: usize) {
+        let tid = thread::current().id();
+        self.pools.get(tid, &self.inner).prewarm(count);
+    }
+
+    /// Allocation counts across every thread's pool on this queue.
+    pub fn command_pool_stats(&self) -> CommandPoolStats {
+        self.pools.stats()
+    }
+
+    /// Like `record`, but only exposes transfer commands, e.g. for an async upload queue.
+    ///
+    /// Graphics and compute queues support transfers too, so any queue can record them.
+    pub fn record_transfer(&self) -> TransferRecorder {
+        TransferRecorder {
+            recorder: self.record(),
+        }
+    }
+
     // TODO: we can merge here already, do that maybe
-    pub fn submit(&self, info: SubmitInfo<'_>) -> u64 {
+    /// Submits `info`, returning its submission index.
+    ///
+    /// Fails with `GPUError::DeviceLost` once the device is lost; the index is only consumed
+    /// if the submit succeeds.
+    pub fn submit(&self, info: SubmitInfo<'_>) -> Result<u64, GPUError> {
         let _lock = self.lock();
-        let submission_index = self
+        unsafe { self.submit_unsynchronized(info) }
+    }
+
+    /// Like `submit`, but returns a token that can be waited on directly.
+    pub fn submit_token(&self, info: SubmitInfo<'_>) -> Result<SubmitToken, GPUError> {
+        self.submit(info).map(|submission| self.token(submission))
+    }
+
+    #[deprecated(note = "`Queue::submit` now returns the same result")]
+    pub fn try_submit(&self, info: SubmitInfo<'_>) -> Result<u64, GPUError> {
+        self.submit(info)
+    }
+
+    /// Submits without taking the queue lock.
+    ///
+    /// # Safety
+    /// No other thread may submit to or present on this queue while this call is running.
+    /// Single-threaded render loops satisfy this trivially.
+    pub unsafe fn submit_unsynchronized(&self, info: SubmitInfo<'_>) -> Result<u64, GPUError> {
+        let submissions = unsafe { self.submit_batch_unsynchronized(std::slice::from_ref(&info))? };
+        Ok(submissions[0])
+    }
+
+    /// Submits every info with one queue lock and one `vkQueueSubmit`, returning their
+    /// submission indices in order.
+    ///
+    /// At most one info may set a fence; it signals once the whole batch has completed.
+    pub fn submit_batch(&self, infos: &[SubmitInfo<'_>]) -> Result<Vec<u64>, GPUError> {
+        let _lock = self.lock();
+        unsafe { self.submit_batch_unsynchronized(infos) }
+    }
+
+    #[deprecated(note = "`Queue::submit_batch` now returns the same result")]
+    pub fn try_submit_batch(&self, infos: &[SubmitInfo<'_>]) -> Result<Vec<u64>, GPUError> {
+        self.submit_batch(infos)
+    }
+
+    /// Like `submit_batch`, without taking the queue lock.
+    ///
+    /// # Safety
+    /// See `submit_unsynchronized`.
+    pub unsafe fn submit_batch_unsynchronized(
+        &self,
+        infos: &[SubmitInfo<'_>],
+    ) -> Result<Vec<u64>, GPUError> {
+        if infos.iter().filter(|info| info.fence.is_some()).count() > 1 {
+            return Err(GPUError::Validation(
+                "at most one SubmitInfo in a batch may set a fence",
+            ));
+        }
+        if infos.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // only advanced once the submit succeeds, so failed submits don't leave holes in the
+        // timeline
+        let first_index = self
             .submission_counter
-            .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
-        let timeline = self.timeline.clone();
-        let pools = &self.pools;
+            .load(std::sync::atomic::Ordering::SeqCst);
+        profile_span!(
+            "queue_submit",
+            submission = first_index,
+            submits = infos.len(),
+            records = infos.iter().map(|info| info.records.len()).sum::<usize>()
+        );
+
+        let submits = infos.iter().map(RawSubmit::new).collect::<Vec<_>>();
+        let fence = infos
+            .iter()
+            .find_map(|info| info.fence)
+            .unwrap_or(vk::Fence::null());
+
+        let submissions = self.inner.submit_batch(
+            first_index,
+            self.timeline.clone(),
+            &self.pools,
+            &submits,
+            fence,
+        )?;
+        self.submission_counter.store(
+            first_index + infos.len() as u64,
+            std::sync::atomic::Ordering::SeqCst,
+        );
+        Ok(submissions)
+    }
+}
 
+/// Raw handles of one `SubmitInfo`, as passed to `QueueImpl::submit_batch`.
+#[derive(Debug, Default)]
+pub struct RawSubmit {
+    pub command_buffers: Vec<CommandBufferImpl>,
+    pub wait_binary: Vec<(vk::Semaphore, vk::PipelineStageFlags)>,
+    pub wait_timeline: Vec<(vk::Semaphore, u64, vk::PipelineStageFlags)>,
+    pub signal_binary: Vec<vk::Semaphore>,
+    pub signal_timeline: Vec<(vk::Semaphore, u64)>,
+}
+
+impl RawSubmit {
+    fn new(info: &SubmitInfo<'_>) -> Self {
         let command_buffers = info
             .records
             .iter()
@@ -755,125 +1848,218 @@ impl Queue {
         let wait_binary = info
             .wait_binary
             .iter()
-            .map(|(s, f)| (s.inner.handle, *f))
+            .map(|(s, f)| {
+                debug_assert!(!s.is_timeline(), "timeline semaphore in wait_binary");
+                (s.inner.handle, *f)
+            })
             .collect::<Vec<_>>();
 
         let wait_timeline = info
             .wait_timeline
             .iter()
-            .map(|(s, v, f)| (s.inner.handle, *v, *f))
+            .map(|(s, v, f)| {
+                debug_assert!(s.is_timeline(), "binary semaphore in wait_timeline");
+                (s.inner.handle, *v, *f)
+            })
             .collect::<Vec<_>>();
 
         let signal_binary = info
             .signal_binary
             .iter()
-            .map(|s| s.inner.handle)
+            .map(|s| {
+                debug_assert!(!s.is_timeline(), "timeline semaphore in signal_binary");
+                s.inner.handle
+            })
             .collect::<Vec<_>>();
 
         let signal_timeline = info
             .signal_timeline
             .iter()
-            .map(|(s, v)| (s.inner.handle, *v))
+            .map(|(s, v)| {
+                debug_assert!(s.is_timeline(), "binary semaphore in signal_timeline");
+                (s.inner.handle, *v)
+            })
             .collect::<Vec<_>>();
 
-        self.inner
-            .submit(
-                submission_index,
-                timeline,
-                pools,
-                &command_buffers,
-                &wait_binary,
-                &wait_timeline,
-                &signal_binary,
-                &signal_timeline,
-                info.fence.unwrap_or(vk::Fence::null()),
-            )
-            .unwrap()
+        Self {
+            command_buffers,
+            wait_binary,
+            wait_timeline,
+            signal_binary,
+            signal_timeline,
+        }
     }
 }
 
-impl QueueImpl {
-    pub fn submit(
-        &self,
-        submission_index: u64,
-        timeline: Semaphore,
-        pools: &CommandPools,
-        command_buffers: &[CommandBufferImpl],
-        wait_binary: &[(vk::Semaphore, vk::PipelineStageFlags)],
-        wait_timeline: &[(vk::Semaphore, u64, vk::PipelineStageFlags)],
-        signal_binary: &[vk::Semaphore],
-        signal_timeline: &[(vk::Semaphore, u64)],
-        fence: vk::Fence,
-    ) -> Result<u64, GPUError> {
-        let timeline_index = timeline.get();
-
-        pools.try_cleanup(timeline_index);
+/// Semaphore arrays of one `vk::SubmitInfo`, kept alive until the submit call.
+struct SubmitStorage {
+    buffers: Vec<vk::CommandBuffer>,
+    wait_semaphores: Vec<vk::Semaphore>,
+    wait_stages: Vec<vk::PipelineStageFlags>,
+    wait_values: Vec<u64>,
+    signal_semaphores: Vec<vk::Semaphore>,
+    signal_values: Vec<u64>,
+}
 
-        let submit_buffers = command_buffers
+impl SubmitStorage {
+    fn new(submit: &RawSubmit, submission_index: u64, timeline: vk::Semaphore) -> Self {
+        let buffers = submit
+            .command_buffers
             .iter()
-            .map(|b| {
-                b.submission.set(submission_index);
-                b.handle
-            })
+            .map(|b| b.handle)
             .collect::<Vec<_>>();
 
-        let mut signal_timeline = signal_timeline.to_vec();
-        signal_timeline.push((timeline.inner.handle, submission_index));
+        let wait_count = submit.wait_binary.len() + submit.wait_timeline.len();
+        let mut wait_semaphores = Vec::with_capacity(wait_count);
+        let mut wait_stages = Vec::with_capacity(wait_count);
+        let mut wait_values = Vec::with_capacity(wait_count);
 
-        let mut wait_semaphores = Vec::with_capacity(wait_binary.len() + wait_timeline.len());
-        let mut wait_stages = Vec::with_capacity(wait_binary.len() + wait_timeline.len());
-        let mut wait_values = Vec::with_capacity(wait_timeline.len());
-
-        for &(sem, stage) in wait_binary {
+        for &(sem, stage) in &submit.wait_binary {
             wait_semaphores.push(sem);
             wait_stages.push(stage);
             wait_values.push(0);
         }
 
-        for &(sem, value, stage) in wait_timeline {
+        for &(sem, value, stage) in &submit.wait_timeline {
             wait_semaphores.push(sem);
             wait_stages.push(stage);
             wait_values.push(value);
         }
 
-        let mut signal_semaphores = Vec::with_capacity(signal_binary.len() + signal_timeline.len());
-        let mut signal_values = Vec::with_capacity(signal_timeline.len());
+        let signal_count = submit.signal_binary.len() + submit.signal_timeline.len() + 1;
+        let mut signal_semaphores = Vec::with_capacity(signal_count);
+        let mut signal_values = Vec::with_capacity(signal_count);
 
-        for &sem in signal_binary {
+        for &sem in &submit.signal_binary {
             signal_semaphores.push(sem);
             signal_values.push(0);
         }
 
-        for &(sem, value) in &signal_timeline {
+        for &(sem, value) in &submit.signal_timeline {
             signal_semaphores.push(sem);
             signal_values.push(value);
         }
 
-        let mut timeline_info = vk::TimelineSemaphoreSubmitInfo::default()
-            .wait_semaphore_values(&wait_values)
-            .signal_semaphore_values(&signal_values);
+        signal_semaphores.push(timeline);
+        signal_values.push(submission_index);
+
+        Self {
+            buffers,
+            wait_semaphores,
+            wait_stages,
+            wait_values,
+            signal_semaphores,
+            signal_values,
+        }
+    }
+}
+
+impl Device {
+    /// Records `f` on `queue`, submits it and blocks until the GPU has finished.
+    pub fn one_time<F>(&self, queue: &Queue, f: F) -> Result<(), GPUError>
+    where
+        F: FnOnce(&mut CommandRecorder),
+    {
+        let mut recorder = queue.record();
+        f(&mut recorder);
+
+        let submission = queue.submit(SubmitInfo {
+            records: &[recorder.finish()],
+            ..Default::default()
+        })?;
+        queue.wait_for(submission)
+    }
+}
+
+impl QueueImpl {
+    pub fn submit(
+        &self,
+        submission_index: u64,
+        timeline: Semaphore,
+        pools: &CommandPools,
+        command_buffers: &[CommandBufferImpl],
+        wait_binary: &[(vk::Semaphore, vk::PipelineStageFlags)],
+        wait_timeline: &[(vk::Semaphore, u64, vk::PipelineStageFlags)],
+        signal_binary: &[vk::Semaphore],
+        signal_timeline: &[(vk::Semaphore, u64)],
+        fence: vk::Fence,
+    ) -> Result<u64, GPUError> {
+        let submit = RawSubmit {
+            command_buffers: command_buffers.to_vec(),
+            wait_binary: wait_binary.to_vec(),
+            wait_timeline: wait_timeline.to_vec(),
+            signal_binary: signal_binary.to_vec(),
+            signal_timeline: signal_timeline.to_vec(),
+        };
+        let submissions = self.submit_batch(submission_index, timeline, pools, &[submit], fence)?;
+        Ok(submissions[0])
+    }
+
+    /// Submits `submits` in one `vkQueueSubmit`, signaling `timeline` with consecutive
+    /// submission indices starting at `first_index`.
+    pub fn submit_batch(
+        &self,
+        first_index: u64,
+        timeline: Semaphore,
+        pools: &CommandPools,
+        submits: &[RawSubmit],
+        fence: vk::Fence,
+    ) -> Result<Vec<u64>, GPUError> {
+        let timeline_index = timeline.get();
+
+        pools.try_cleanup(timeline_index);
+
+        let submissions = (first_index..first_index + submits.len() as u64).collect::<Vec<_>>();
+
+        let storages = submits
+            .iter()
+            .zip(&submissions)
+            .map(|(submit, &index)| SubmitStorage::new(submit, index, timeline.inner.handle))
+            .collect::<Vec<_>>();
+
+        let mut timeline_infos = storages
+            .iter()
+            .map(|storage| {
+                vk::TimelineSemaphoreSubmitInfo::default()
+                    .wait_semaphore_values(&storage.wait_values)
+                    .signal_semaphore_values(&storage.signal_values)
+            })
+            .collect::<Vec<_>>();
 
-        let submit_info = vk::SubmitInfo::default()
-            .wait_semaphores(&wait_semaphores)
-            .wait_dst_stage_mask(&wait_stages)
-            .command_buffers(&submit_buffers)
-            .signal_semaphores(&signal_semaphores)
-            .push_next(&mut timeline_info);
+        let submit_infos = storages
+            .iter()
+            .zip(&mut timeline_infos)
+            .map(|(storage, timeline_info)| {
+                vk::SubmitInfo::default()
+                    .wait_semaphores(&storage.wait_semaphores)
+                    .wait_dst_stage_mask(&storage.wait_stages)
+                    .command_buffers(&storage.buffers)
+                    .signal_semaphores(&storage.signal_semaphores)
+                    .push_next(timeline_info)
+            })
+            .collect::<Vec<_>>();
 
         unsafe {
             self.device
                 .handle
-                .queue_submit(self.handle, &[submit_info], fence)?;
+                .queue_submit(self.handle, &submit_infos, fence)
+                .map_err(|e| self.device.vk_error(e))?;
         }
 
-        Ok(submission_index)
+        for (submit, &index) in submits.iter().zip(&submissions) {
+            for buffer in &submit.command_buffers {
+                buffer.submission.set(index);
+            }
+        }
+        Ok(submissions)
     }
 }
 
 impl CommandPools {
-    pub fn new(device: RawDevice) -> Self {
+    pub fn new(device: RawDevice, config: CommandPoolConfig) -> Self {
         Self {
             device,
+            config,
             pools: Mutex::new(HashMap::new()),
         }
     }
@@ -887,8 +2073,11 @@ impl CommandPools {
         let pool = ThreadCommandPool {
             handle,
             device: self.device.clone(),
+            config: self.config,
             ready: RefCell::new(Vec::new()),
             dropped: RefCell::new(Vec::new()),
+            allocated: AtomicU64::new(0),
+            freed: AtomicU64::new(0),
         };
 
         let pool = Rc::new(pool);
@@ -899,10 +2088,22 @@ impl CommandPools {
     pub fn try_cleanup(&self, completed_index: u64) {
         let pools = self.pools.lock();
 
-        let thread_id = thread::current().id();
-        let pool = pools.get(&thread_id).unwrap();
+        // Threads that only submit semaphore operations never created a pool.
+        if let Some(pool) = pools.get(&thread::current().id()) {
+            pool.try_cleanup(completed_index);
+        }
+    }
 
-        pool.try_cleanup(completed_index);
+    pub fn stats(&self) -> CommandPoolStats {
+        let pools = self.pools.lock();
+        pools
+            .values()
+            .fold(CommandPoolStats::default(), |stats, pool| {
+                CommandPoolStats {
+                    allocated: stats.allocated + pool.allocated.load(Ordering::Relaxed),
+                    freed: stats.freed + pool.freed.load(Ordering::Relaxed),
+                }
+            })
     }
 }
 
@@ -913,26 +2114,40 @@ impl ThreadCommandPool {
             return buffer;
         }
 
+        let mut buffers = self.allocate(self.config.batch_size.max(1));
+        let buffer = buffers.pop().unwrap();
+
+        ready.extend(buffers);
+
+        buffer
+    }
+
+    /// Tops the ready list up to `count` buffers.
+    pub fn prewarm(&self, count: usize) {
+        let mut ready = self.ready.borrow_mut();
+        if ready.len() < count {
+            let missing = count - ready.len();
+            ready.extend(self.allocate(missing as u32));
+        }
+    }
+
+    fn allocate(&self, count: u32) -> Vec<CommandBufferImpl> {
         let info = vk::CommandBufferAllocateInfo::default()
             .command_pool(self.handle)
             .level(vk::CommandBufferLevel::PRIMARY)
-            .command_buffer_count(5);
+            .command_buffer_count(count);
 
         let buffer_handles = unsafe { self.device.handle.allocate_command_buffers(&info).unwrap() };
+        self.allocated
+            .fetch_add(buffer_handles.len() as u64, Ordering::Relaxed);
 
-        let mut buffers = buffer_handles
+        buffer_handles
             .into_iter()
             .map(|buffer| CommandBufferImpl {
                 handle: buffer,
                 submission: Rc::new(Cell::new(0)),
             })
-            .collect::<Vec<_>>();
-
-        let buffer = buffers.pop().unwrap();
-
-        ready.extend(buffers);
-
-        buffer
+            .collect()
     }
 
     pub fn retire(&self, buffer: DroppedCommandBuffer) {
@@ -958,26 +2173,31 @@ impl ThreadCommandPool {
 
         if !freeable.is_empty() {
             let mut ready = self.ready.borrow_mut();
-            if ready.len() < 10 {
-                let new_ready_buffers = freeable.into_iter().map(|b| {
-                    unsafe {
-                        let _ = self.device.handle.reset_command_buffer(
-                            b,
-                            vk::CommandBufferResetFlags::RELEASE_RESOURCES,
-                        );
-                    }
-                    CommandBufferImpl {
-                        handle: b,
-                        submission: Rc::new(Cell::new(0)),
-                    }
-                });
-                ready.extend(new_ready_buffers);
-            } else {
+            let reuse = self
+                .config
+                .max_cached
+                .saturating_sub(ready.len())
+                .min(freeable.len());
+            let new_ready_buffers = freeable[..reuse].iter().map(|&b| {
                 unsafe {
-                    self.device
+                    let _ = self
+                        .device
                         .handle
-                        .free_command_buffers(self.handle, &freeable);
+                        .reset_command_buffer(b, vk::CommandBufferResetFlags::RELEASE_RESOURCES);
+                }
+                CommandBufferImpl {
+                    handle: b,
+                    submission: Rc::new(Cell::new(0)),
+                }
+            });
+            ready.extend(new_ready_buffers);
+
+            let excess = &freeable[reuse..];
+            if !excess.is_empty() {
+                unsafe {
+                    self.device.handle.free_command_buffers(self.handle, excess);
                 }
+                self.freed.fetch_add(excess.len() as u64, Ordering::Relaxed);
             }
         }
     }
@@ -985,9 +2205,13 @@ impl ThreadCommandPool {
 
 impl Drop for CommandRecorderImpl {
     fn drop(&mut self) {
+        if self.buffer.handle == vk::CommandBuffer::null() {
+            return;
+        }
         let buffer = DroppedCommandBuffer {
             handle: self.buffer.handle,
             submission: self.buffer.submission.get(),
+            resources: std::mem::take(&mut self.resources).into_values().collect(),
         };
         self.pool.retire(buffer);
     }
@@ -1018,3 +2242,577 @@ impl Drop for CommandPools {
         }
     }
 }
+
+fn global_barrier(
+    from: BufferAccessTransition,
+    to: BufferAccessTransition,
+) -> vk::MemoryBarrier2<'static> {
+    vk::MemoryBarrier2::default()
+        .src_stage_mask(from.stage)
+        .src_access_mask(from.access)
+        .dst_stage_mask(to.stage)
+        .dst_access_mask(to.access)
+}
+
+fn flip_y_viewport(extent: vk::Extent2D) -> vk::Viewport {
+    vk::Viewport {
+        x: 0.0,
+        y: extent.height as f32,
+        width: extent.width as f32,
+        height: -(extent.height as f32),
+        min_depth: 0.0,
+        max_depth: 1.0,
+    }
+}
+
+/// Clears an RGBA8 target of `extent`, records `render` into its `area` and returns the pixels
+/// with their row pitch, for tests that need a GPU.
+#[cfg(test)]
+pub(crate) fn test_render(
+    device: &Device,
+    queue: &Queue,
+    extent: vk::Extent2D,
+    area: Region,
+    render: impl FnOnce(&mut RenderRecorder<'_>),
+) -> (Vec<u8>, u32) {
+    use crate::{ImageDesc, ImageUses, ViewImageDesc};
+
+    let target = device
+        .create_view_image(&ViewImageDesc {
+            image: ImageDesc {
+                format: vk::Format::R8G8B8A8_UNORM,
+                extent: vk::Extent3D {
+                    width: extent.width,
+                    height: extent.height,
+                    depth: 1,
+                },
+                usage: ImageUses::COLOR_ATTACHMENT | ImageUses::COPY_SRC,
+                ..Default::default()
+            },
+            aspect: Some(vk::ImageAspectFlags::COLOR),
+            ..Default::default()
+        })
+        .unwrap();
+
+    let mut recorder = queue.record();
+    recorder.image_transition(
+        &target.image,
+        ImageTransition {
+            from: ImageLayoutTransition::UNDEFINED,
+            to: ImageLayoutTransition::COLOR,
+            aspect: vk::ImageAspectFlags::COLOR,
+            ..Default::default()
+        },
+    );
+    // Clearing covers only the render area, so clear the whole target in a pass of its own.
+    recorder.begin_render(
+        &RenderInfo {
+            area: Region::from(extent).rect(),
+            colors: &[ColorAttachment::clear(&target.view, [0.0; 4])],
+            attachment_views: &[&target.view],
+            ..Default::default()
+        },
+        |_| {},
+    );
+    recorder.image_transition(
+        &target.image,
+        ImageTransition {
+            from: ImageLayoutTransition::COLOR,
+            to: ImageLayoutTransition::COLOR,
+            aspect: vk::ImageAspectFlags::COLOR,
+            ..Default::default()
+        },
+    );
+    recorder.begin_render(
+        &RenderInfo {
+            area: area.rect(),
+            colors: &[ColorAttachment::load(&target.view)],
+            attachment_views: &[&target.view],
+            ..Default::default()
+        },
+        render,
+    );
+    let submission = queue
+        .submit(SubmitInfo {
+            records: &[recorder.finish()],
+            ..Default::default()
+        })
+        .unwrap();
+    queue.wait_for(submission).unwrap();
+
+    target
+        .image
+        .read_back(
+            queue,
+            ImageLayoutTransition::COLOR,
+            vk::ImageAspectFlags::COLOR,
+        )
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn region_viewport_and_scissor_share_the_offset() {
+        let region = Region::new(320, 240, 320, 240);
+        let rect = region.rect();
+        let viewport = region.viewport();
+
+        assert_eq!((rect.offset.x, rect.offset.y), (320, 240));
+        assert_eq!((viewport.x, viewport.y), (320.0, 240.0));
+        assert_eq!((viewport.width, viewport.height), (320.0, 240.0));
+        assert_eq!(Region::from(rect), region);
+    }
+
+    #[test]
+    #[ignore = "requires a Vulkan device"]
+    fn region_renders_only_into_its_rectangle() {
+        use crate::{QueueFlags, RenderPipelineInfo, ShaderSource, device::test_device};
+
+        // Covers all of clip space.
+        const SHADER: &str = r#"
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    var positions = array(vec2<f32>(-1.0, -1.0), vec2<f32>(3.0, -1.0), vec2<f32>(-1.0, 3.0));
+    return vec4<f32>(positions[index], 0.0, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return vec4<f32>(1.0);
+}
+"#;
+
+        let (device, queue) = test_device(QueueFlags::GRAPHICS);
+        let shader = device
+            .create_shader(None, ShaderSource::Wgsl(SHADER))
+            .unwrap();
+        let pipeline = device.create_render_pipeline(&RenderPipelineInfo {
+            vertex_shader: shader.entry("vs_main"),
+            fragment_shader: shader.entry("fs_main"),
+            color_formats: &[vk::Format::R8G8B8A8_UNORM],
+            cull: vk::CullModeFlags::NONE,
+            ..Default::default()
+        });
+
+        let extent = vk::Extent2D {
+            width: 4,
+            height: 4,
+        };
+        let quadrant = Region::new(2, 2, 2, 2);
+        let (pixels, row_pitch) = test_render(&device, &queue, extent, quadrant, |render| {
+            render.bind_render_pipeline(&pipeline);
+            render.region(quadrant);
+            render.draw(0..3, 0..1);
+        });
+
+        for y in 0..4 {
+            for x in 0..4 {
+                let red = pixels[y * row_pitch as usize + x * 4];
+                let inside = x >= 2 && y >= 2;
+                assert_eq!(red, if inside { 255 } else { 0 }, "pixel ({x}, {y})");
+            }
+        }
+    }
+
+    #[test]
+    fn flip_y_viewport_maps_top_to_bottom() {
+        let viewport = flip_y_viewport(vk::Extent2D {
+            width: 640,
+            height: 480,
+        });
+
+        // Clip-space y = -1 maps to framebuffer y = viewport.y + (y + 1) / 2 * viewport.height.
+        let to_framebuffer = |y: f32| viewport.y + (y + 1.0) * 0.5 * viewport.height;
+        assert_eq!(to_framebuffer(1.0), 0.0);
+        assert_eq!(to_framebuffer(-1.0), 480.0);
+        assert_eq!(viewport.width, 640.0);
+    }
+
+    #[test]
+    #[ignore = "requires a Vulkan device"]
+    fn flip_y_viewport_renders_upper_half_on_top() {
+        use crate::{
+            ColorAttachment, ImageDesc, ImageLayoutTransition, ImageTransition, ImageUses,
+            QueueFlags, RenderPipelineInfo, ShaderSource, ViewImageDesc, device::test_device,
+        };
+
+        // Covers clip-space y >= 0, the upper half in GL/WGSL convention.
+        const SHADER: &str = r#"
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    var positions = array(vec2<f32>(-1.0, 0.0), vec2<f32>(3.0, 0.0), vec2<f32>(-1.0, 4.0));
+    return vec4<f32>(positions[index], 0.0, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return vec4<f32>(1.0);
+}
+"#;
+
+        let (device, queue) = test_device(QueueFlags::GRAPHICS);
+        let extent = vk::Extent2D {
+            width: 4,
+            height: 4,
+        };
+        let format = vk::Format::R8G8B8A8_UNORM;
+        let shader = device
+            .create_shader(None, ShaderSource::Wgsl(SHADER))
+            .unwrap();
+        let pipeline = device.create_render_pipeline(&RenderPipelineInfo {
+            vertex_shader: shader.entry("vs_main"),
+            fragment_shader: shader.entry("fs_main"),
+            color_formats: &[format],
+            cull: vk::CullModeFlags::NONE,
+            ..Default::default()
+        });
+
+        // Red channel of the top-left and bottom-left pixels.
+        let render = |flip: bool| {
+            let target = device
+                .create_view_image(&ViewImageDesc {
+                    image: ImageDesc {
+                        format,
+                        extent: vk::Extent3D {
+                            width: extent.width,
+                            height: extent.height,
+                            depth: 1,
+                        },
+                        usage: ImageUses::COLOR_ATTACHMENT | ImageUses::COPY_SRC,
+                        ..Default::default()
+                    },
+                    aspect: Some(vk::ImageAspectFlags::COLOR),
+                    ..Default::default()
+                })
+                .unwrap();
+
+            let mut recorder = queue.record();
+            recorder.image_transition(
+                &target.image,
+                ImageTransition {
+                    from: ImageLayoutTransition::UNDEFINED,
+                    to: ImageLayoutTransition::COLOR,
+                    aspect: vk::ImageAspectFlags::COLOR,
+                    ..Default::default()
+                },
+            );
+            recorder.begin_render(
+                &RenderInfo {
+                    area: vk::Rect2D {
+                        extent,
+                        ..Default::default()
+                    },
+                    colors: &[ColorAttachment::clear(&target.view, [0.0; 4])],
+                    attachment_views: &[&target.view],
+                    ..Default::default()
+                },
+                |render| {
+                    render.bind_render_pipeline(&pipeline);
+                    if flip {
+                        render.viewport_flip_y(extent);
+                    } else {
+                        render.viewport(Region::from(extent).viewport());
+                    }
+                    render.scissor(vk::Rect2D {
+                        extent,
+                        ..Default::default()
+                    });
+                    render.draw(0..3, 0..1);
+                },
+            );
+            let submission = queue
+                .submit(SubmitInfo {
+                    records: &[recorder.finish()],
+                    ..Default::default()
+                })
+                .unwrap();
+            queue.wait_for(submission).unwrap();
+
+            let (pixels, row_pitch) = target
+                .image
+                .read_back(
+                    &queue,
+                    ImageLayoutTransition::COLOR,
+                    vk::ImageAspectFlags::COLOR,
+                )
+                .unwrap();
+            let bottom = (extent.height - 1) as usize * row_pitch as usize;
+            (pixels[0], pixels[bottom])
+        };
+
+        assert_eq!(render(false), (0, 255));
+        assert_eq!(render(true), (255, 0));
+    }
+
+    #[test]
+    #[ignore = "requires a Vulkan device"]
+    fn recorded_buffers_outlive_their_handles() {
+        use crate::{BufferDesc, QueueFlags, device::test_device};
+
+        let (device, queue) = test_device(QueueFlags::GRAPHICS);
+
+        let buffer = device
+            .create_buffer(&BufferDesc {
+                size: 256,
+                usage: BufferUses::COPY_DST,
+                ..Default::default()
+            })
+            .unwrap();
+        let weak = Arc::downgrade(&buffer.inner);
+
+        let mut recorder = queue.record();
+        recorder.fill_buffer(&buffer, 0, vk::WHOLE_SIZE, 0);
+        drop(buffer);
+        let token = queue
+            .submit_token(SubmitInfo {
+                records: &[recorder.finish()],
+                ..Default::default()
+            })
+            .unwrap();
+        drop(recorder);
+        assert!(weak.upgrade().is_some());
+
+        token.wait().unwrap();
+        // Retired buffers are cleaned up on the next submission from this thread.
+        queue.submit(SubmitInfo::default()).unwrap();
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test]
+    #[ignore = "requires a Vulkan device"]
+    fn descriptor_set_resources_outlive_their_handles() {
+        use crate::{
+            BufferDesc, ComputePipelineInfo, DescriptorBinding, DescriptorPoolInfo,
+            DescriptorSetLayoutInfo, DescriptorType, DescriptorWrite, QueueFlags, ShaderSource,
+            device::test_device,
+        };
+
+        const SHADER: &str = r#"
+@group(0) @binding(0) var<storage, read_write> data: array<u32>;
+
+@compute @workgroup_size(1)
+fn main() {
+    data[0] = 7u;
+}
+"#;
+
+        let (device, queue) = test_device(QueueFlags::COMPUTE);
+        let layout = device.create_descriptor_set_layout(&DescriptorSetLayoutInfo {
+            bindings: &[DescriptorBinding::unique(
+                0,
+                DescriptorType::StorageBuffer,
+                vk::ShaderStageFlags::COMPUTE,
+            )],
+            ..Default::default()
+        });
+        let pool = device.create_descriptor_pool(&DescriptorPoolInfo {
+            max_sets: 1,
+            layouts: &[&layout],
+            ..Default::default()
+        });
+        let set = pool.allocate_set(&layout);
+        let shader = device
+            .create_shader(None, ShaderSource::Wgsl(SHADER))
+            .unwrap();
+        let pipeline = device.create_compute_pipeline(&ComputePipelineInfo {
+            shader: shader.entry("main"),
+            descriptor_layouts: &[&layout],
+            ..Default::default()
+        });
+
+        let buffer = device
+            .create_buffer(&BufferDesc {
+                size: 256,
+                usage: BufferUses::STORAGE,
+                ..Default::default()
+            })
+            .unwrap();
+        let weak = Arc::downgrade(&buffer.inner);
+        set.write(&[DescriptorWrite::StorageBuffer {
+            binding: 0,
+            buffer: &buffer,
+            offset: 0,
+            range: vk::WHOLE_SIZE,
+            array_element: None,
+        }]);
+        drop(buffer);
+
+        let mut recorder = queue.record();
+        recorder.bind_compute_pipeline(&pipeline);
+        recorder.bind_compute_descriptor_set(&set, &pipeline, 0, &[]);
+        recorder.dispatch(1, 1, 1);
+        let token = queue
+            .submit_token(SubmitInfo {
+                records: &[recorder.finish()],
+                ..Default::default()
+            })
+            .unwrap();
+        drop(recorder);
+        drop(set);
+        assert!(weak.upgrade().is_some());
+
+        token.wait().unwrap();
+        queue.submit(SubmitInfo::default()).unwrap();
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test]
+    #[ignore = "requires a Vulkan device"]
+    fn transfer_recorder_fills_and_copies() {
+        use crate::{BufferDesc, BufferUses, HostAccess, MemoryPreset, QueueFlags};
+
+        let (device, queue) = crate::device::test_device(QueueFlags::TRANSFER);
+        let src = device
+            .create_buffer(&BufferDesc {
+                size: 64,
+                usage: BufferUses::COPY_SRC | BufferUses::COPY_DST,
+                ..Default::default()
+            })
+            .unwrap();
+        let dst = device
+            .create_buffer(&BufferDesc {
+                size: 64,
+                usage: BufferUses::COPY_DST,
+                memory: MemoryPreset::Readback,
+                host_access: HostAccess::ReadRandom,
+                ..Default::default()
+            })
+            .unwrap();
+
+        let mut recorder = queue.record_transfer();
+        recorder.fill_buffer(&src, 0, vk::WHOLE_SIZE, 0);
+        recorder.buffer_transition(
+            &src,
+            BufferTransition {
+                from: BufferAccessTransition::TRANSFER_DST,
+                to: BufferAccessTransition::TRANSFER_DST,
+                ..Default::default()
+            },
+        );
+        recorder.fill_buffer(&src, 16, 32, 0xdead_beef);
+        recorder.buffer_transition(
+            &src,
+            BufferTransition {
+                from: BufferAccessTransition::TRANSFER_DST,
+                to: BufferAccessTransition::TRANSFER_SRC,
+                ..Default::default()
+            },
+        );
+        recorder.copy_buffer(&CopyBufferInfo {
+            src: &src,
+            dst: &dst,
+            regions: &[vk::BufferCopy::default().size(64)],
+        });
+        let submission = queue
+            .submit(SubmitInfo {
+                records: &[recorder.finish()],
+                ..Default::default()
+            })
+            .unwrap();
+        queue.wait_for(submission).unwrap();
+
+        let mut words = [0u32; 16];
+        dst.read_slice(&mut words);
+        assert_eq!(words[..4], [0; 4]);
+        assert_eq!(words[4..12], [0xdead_beef; 8]);
+        assert_eq!(words[12..], [0; 4]);
+    }
+
+    #[test]
+    #[ignore = "requires a Vulkan device"]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "fill_buffer requires BufferUses::COPY_DST")]
+    fn fill_buffer_rejects_buffers_without_copy_dst() {
+        use crate::{BufferDesc, BufferUses, QueueFlags};
+
+        let (device, queue) = crate::device::test_device(QueueFlags::TRANSFER);
+        let buffer = device
+            .create_buffer(&BufferDesc {
+                size: 64,
+                usage: BufferUses::STORAGE,
+                ..Default::default()
+            })
+            .unwrap();
+        let mut recorder = queue.record_transfer();
+        recorder.fill_buffer(&buffer, 0, vk::WHOLE_SIZE, 0);
+    }
+
+    #[test]
+    #[ignore = "requires a Vulkan device"]
+    fn batched_submits_match_individual_submits() {
+        use crate::{QueueFlags, device::test_device_with};
+        use std::time::{Duration, Instant};
+
+        const SUBMITS: usize = 256;
+        const ROUNDS: u32 = 8;
+
+        let (_device, queue) = test_device_with(QueueFlags::COMPUTE, |_| {
+            crate::DeviceCreateInfo::default().compute_only(true)
+        });
+
+        let record = || {
+            (0..SUBMITS)
+                .map(|_| queue.record().finish())
+                .collect::<Vec<_>>()
+        };
+        // Both return the time spent submitting, excluding recording and the wait.
+        let individual = |buffers: &[CommandBuffer]| {
+            let start = Instant::now();
+            let mut last = 0;
+            for buffer in buffers {
+                last = queue
+                    .submit(SubmitInfo {
+                        records: std::slice::from_ref(buffer),
+                        ..Default::default()
+                    })
+                    .unwrap();
+            }
+            let elapsed = start.elapsed();
+            queue.wait_for(last).unwrap();
+            (elapsed, last)
+        };
+        let batched = |buffers: &[CommandBuffer]| {
+            let infos = buffers
+                .iter()
+                .map(|buffer| SubmitInfo {
+                    records: std::slice::from_ref(buffer),
+                    ..Default::default()
+                })
+                .collect::<Vec<_>>();
+            let start = Instant::now();
+            let submissions = queue.submit_batch(&infos).unwrap();
+            let elapsed = start.elapsed();
+            queue.wait_for(*submissions.last().unwrap()).unwrap();
+            (elapsed, submissions)
+        };
+
+        let (_, last) = individual(&record());
+        let (_, submissions) = batched(&record());
+        assert_eq!(submissions.len(), SUBMITS);
+        assert!(submissions.windows(2).all(|pair| pair[1] == pair[0] + 1));
+        assert!(submissions[0] > last);
+
+        // Both paths are warm after the checks above; alternate which one goes first.
+        let mut individual_time = Duration::ZERO;
+        let mut batched_time = Duration::ZERO;
+        for round in 0..ROUNDS {
+            let (first, second) = (record(), record());
+            if round % 2 == 0 {
+                individual_time += individual(&first).0;
+                batched_time += batched(&second).0;
+            } else {
+                batched_time += batched(&first).0;
+                individual_time += individual(&second).0;
+            }
+        }
+        let per_submit = |total: Duration| total / (ROUNDS * SUBMITS as u32);
+        eprintln!(
+            "{SUBMITS} submits x {ROUNDS} rounds: individual {:?}, batched {:?} per submit",
+            per_submit(individual_time),
+            per_submit(batched_time)
+        );
+    }
+}
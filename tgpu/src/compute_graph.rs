@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+
+use ash::vk;
+
+use crate::{Buffer, BufferAccessTransition, CommandRecorder};
+
+type NodeExec<'a> = Box<dyn FnOnce(&mut CommandRecorder) + 'a>;
+
+struct ComputeNode<'a> {
+    reads: Vec<&'a Buffer>,
+    writes: Vec<&'a Buffer>,
+    exec: NodeExec<'a>,
+}
+
+/// Ordered list of compute nodes whose buffer barriers are inferred from declared reads and writes.
+///
+/// Nodes are recorded in insertion order. Before each node, a barrier is emitted only for buffers
+/// with a read-after-write, write-after-write or write-after-read hazard on an earlier node.
+#[derive(Default)]
+pub struct ComputeGraph<'a> {
+    nodes: Vec<ComputeNode<'a>>,
+}
+
+impl<'a> ComputeGraph<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_node<B, E>(&mut self, build: B, exec: E)
+    where
+        B: FnOnce(&mut ComputeNodeBuilder<'a>),
+        E: FnOnce(&mut CommandRecorder) + 'a,
+    {
+        let mut builder = ComputeNodeBuilder {
+            reads: Vec::new(),
+            writes: Vec::new(),
+        };
+        build(&mut builder);
+        self.nodes.push(ComputeNode {
+            reads: builder.reads,
+            writes: builder.writes,
+            exec: Box::new(exec),
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn record(self, recorder: &mut CommandRecorder) {
+        let inner = unsafe { &mut *recorder.inner.get() };
+        for node in &self.nodes {
+            for buffer in node.reads.iter().chain(&node.writes) {
+                inner.keep_alive(&buffer.inner);
+            }
+        }
+
+        let accesses = self
+            .nodes
+            .iter()
+            .map(|node| {
+                (
+                    node.reads
+                        .iter()
+                        .map(|buffer| buffer.inner.handle)
+                        .collect(),
+                    node.writes
+                        .iter()
+                        .map(|buffer| buffer.inner.handle)
+                        .collect(),
+                )
+            })
+            .collect::<Vec<(Vec<_>, Vec<_>)>>();
+        let plan = plan_barriers(&accesses);
+
+        for (node, barriers) in self.nodes.into_iter().zip(plan) {
+            if !barriers.is_empty() {
+                let barriers = barriers
+                    .into_iter()
+                    .map(|barrier| {
+                        vk::BufferMemoryBarrier2::default()
+                            .buffer(barrier.buffer)
+                            .offset(0)
+                            .size(vk::WHOLE_SIZE)
+                            .src_stage_mask(barrier.from.stage)
+                            .src_access_mask(barrier.from.access)
+                            .dst_stage_mask(barrier.to.stage)
+                            .dst_access_mask(barrier.to.access)
+                    })
+                    .collect::<Vec<_>>();
+                let inner = unsafe { &mut *recorder.inner.get() };
+                unsafe { inner.buffer_barriers(&barriers) };
+            }
+            (node.exec)(recorder);
+        }
+    }
+}
+
+pub struct ComputeNodeBuilder<'a> {
+    reads: Vec<&'a Buffer>,
+    writes: Vec<&'a Buffer>,
+}
+
+impl<'a> ComputeNodeBuilder<'a> {
+    pub fn read(&mut self, buffer: &'a Buffer) {
+        self.reads.push(buffer);
+    }
+
+    pub fn write(&mut self, buffer: &'a Buffer) {
+        self.writes.push(buffer);
+    }
+
+    pub fn read_write(&mut self, buffer: &'a Buffer) {
+        self.reads.push(buffer);
+        self.writes.push(buffer);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PlannedBarrier {
+    buffer: vk::Buffer,
+    from: BufferAccessTransition,
+    to: BufferAccessTransition,
+}
+
+#[derive(Default)]
+struct BufferHazard {
+    unsynced_write: bool,
+    unsynced_read: bool,
+}
+
+fn plan_barriers(nodes: &[(Vec<vk::Buffer>, Vec<vk::Buffer>)]) -> Vec<Vec<PlannedBarrier>> {
+    let mut state: HashMap<vk::Buffer, BufferHazard> = HashMap::new();
+    let mut plan = Vec::with_capacity(nodes.len());
+
+    for (reads, writes) in nodes {
+        let mut barriers: Vec<PlannedBarrier> = Vec::new();
+        let mut buffers = reads.iter().chain(writes).copied().collect::<Vec<_>>();
+        buffers.sort_by_key(|buffer| vk::Handle::as_raw(*buffer));
+        buffers.dedup();
+
+        for buffer in buffers {
+            let reads = reads.contains(&buffer);
+            let writes = writes.contains(&buffer);
+            let hazard = state.entry(buffer).or_default();
+
+            let from = if hazard.unsynced_write {
+                Some(BufferAccessTransition::compute_storage_write())
+            } else if hazard.unsynced_read && writes {
+                Some(BufferAccessTransition::compute_storage_read())
+            } else {
+                None
+            };
+
+            if let Some(from) = from {
+                let to = match (reads, writes) {
+                    (true, true) => BufferAccessTransition::compute_storage_read_write(),
+                    (false, true) => BufferAccessTransition::compute_storage_write(),
+                    _ => BufferAccessTransition::compute_storage_read(),
+                };
+                barriers.push(PlannedBarrier { buffer, from, to });
+                *hazard = BufferHazard::default();
+            }
+
+            hazard.unsynced_read |= reads;
+            hazard.unsynced_write |= writes;
+        }
+
+        plan.push(barriers);
+    }
+
+    plan
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ash::vk::Handle;
+
+    fn buffer(raw: u64) -> vk::Buffer {
+        vk::Buffer::from_raw(raw)
+    }
+
+    #[test]
+    fn read_after_write_inserts_barrier() {
+        let (a, b) = (buffer(1), buffer(2));
+        let plan = plan_barriers(&[(vec![], vec![a]), (vec![a], vec![b])]);
+
+        assert!(plan[0].is_empty());
+        assert_eq!(plan[1].len(), 1);
+        assert_eq!(plan[1][0].buffer, a);
+        assert_eq!(
+            plan[1][0].from,
+            BufferAccessTransition::compute_storage_write()
+        );
+    }
+
+    #[test]
+    fn independent_and_read_only_nodes_skip_barriers() {
+        let (a, b, c) = (buffer(1), buffer(2), buffer(3));
+        let plan = plan_barriers(&[(vec![a], vec![b]), (vec![a], vec![c]), (vec![a], vec![])]);
+
+        assert!(plan.iter().all(Vec::is_empty));
+    }
+
+    #[test]
+    fn write_after_read_needs_one_barrier() {
+        let (a, b) = (buffer(1), buffer(2));
+        let plan = plan_barriers(&[
+            (vec![], vec![a]),
+            (vec![a], vec![]),
+            (vec![a], vec![]),
+            (vec![b], vec![a]),
+        ]);
+
+        assert_eq!(plan[1].len(), 1);
+        assert!(plan[2].is_empty());
+        assert_eq!(plan[3].len(), 1);
+        assert_eq!(
+            plan[3][0].from,
+            BufferAccessTransition::compute_storage_read()
+        );
+    }
+}
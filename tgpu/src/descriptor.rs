@@ -1,7 +1,10 @@
 use ash::vk;
-use std::sync::Arc;
+use std::{cell::RefCell, collections::HashMap, sync::Arc};
 
-use crate::{Buffer, Device, ImageView, Label, Sampler, raw::RawDevice};
+use crate::{
+    Buffer, BufferUses, Device, GPUError, ImageView, Label, Sampler, command::KeepAlive,
+    image::ImageUsage, raw::RawDevice,
+};
 
 #[derive(Clone, Copy, Debug)]
 pub enum DescriptorType {
@@ -102,17 +105,22 @@ impl Default for DescriptorPoolInfo<'_> {
     }
 }
 
+/// Descriptor pool; cloning is cheap and every allocated set keeps the pool alive.
 #[derive(Debug, Clone)]
 pub struct DescriptorPool {
+    pub inner: Arc<DescriptorPoolImpl>,
+}
+
+#[derive(Debug)]
+pub struct DescriptorPoolImpl {
     pub handle: vk::DescriptorPool,
     pub device: RawDevice,
 }
 
 #[derive(Debug, Clone)]
 pub struct DescriptorArena {
-    device: Device,
     layout: DescriptorSetLayout,
-    pool: Arc<DescriptorPool>,
+    pool: DescriptorPool,
 }
 
 #[derive(Debug, Clone)]
@@ -159,8 +167,11 @@ pub enum DescriptorWrite<'a> {
 
 pub struct DescriptorSet {
     pub handle: vk::DescriptorSet,
-    pub pool: Arc<DescriptorPool>,
+    pub pool: DescriptorPool,
     pub device: RawDevice,
+    /// What each `(binding, array_element)` was last written with; recorders binding the set
+    /// hold these until their submission completes.
+    pub(crate) resources: RefCell<HashMap<(u32, u32), Vec<KeepAlive>>>,
 }
 
 impl Device {
@@ -229,7 +240,7 @@ impl Device {
         }
     }
 
-    pub fn create_descriptor_pool(&self, info: &DescriptorPoolInfo) -> Arc<DescriptorPool> {
+    pub fn create_descriptor_pool(&self, info: &DescriptorPoolInfo) -> DescriptorPool {
         let mut type_counts: std::collections::HashMap<vk::DescriptorType, u32> =
             std::collections::HashMap::new();
 
@@ -265,36 +276,22 @@ impl Device {
             unsafe { self.inner.attach_label(handle, label) };
         }
 
-        Arc::new(DescriptorPool {
-            handle,
-            device: self.inner.clone(),
-        })
+        DescriptorPool {
+            inner: Arc::new(DescriptorPoolImpl {
+                handle,
+                device: self.inner.clone(),
+            }),
+        }
     }
 }
 
 impl Device {
     pub fn create_descriptor_set(
         &self,
-        pool: Arc<DescriptorPool>,
+        pool: &DescriptorPool,
         layout: &DescriptorSetLayout,
     ) -> DescriptorSet {
-        let layouts = [layout.handle];
-        let alloc_info = vk::DescriptorSetAllocateInfo::default()
-            .descriptor_pool(pool.handle)
-            .set_layouts(&layouts);
-
-        let handle = unsafe {
-            self.inner
-                .handle
-                .allocate_descriptor_sets(&alloc_info)
-                .unwrap()[0]
-        };
-
-        DescriptorSet {
-            handle,
-            device: layout.device.clone(),
-            pool,
-        }
+        pool.allocate_set(layout)
     }
 
     pub fn create_descriptor_arena(
@@ -312,11 +309,7 @@ impl Device {
             label: pool_label,
         });
 
-        DescriptorArena {
-            device: self.clone(),
-            layout,
-            pool,
-        }
+        DescriptorArena { layout, pool }
     }
 }
 
@@ -326,11 +319,48 @@ impl DescriptorArena {
     }
 
     pub fn allocate_set(&self) -> DescriptorSet {
-        self.device.create_descriptor_set(self.pool.clone(), &self.layout)
+        self.pool.allocate_set(&self.layout)
+    }
+}
+
+impl DescriptorPool {
+    pub fn handle(&self) -> vk::DescriptorPool {
+        self.inner.handle
+    }
+
+    /// Fails with `vk::Result::ERROR_OUT_OF_POOL_MEMORY` once the pool is exhausted.
+    pub fn try_allocate_set(
+        &self,
+        layout: &DescriptorSetLayout,
+    ) -> Result<DescriptorSet, GPUError> {
+        let layouts = [layout.handle];
+        let alloc_info = vk::DescriptorSetAllocateInfo::default()
+            .descriptor_pool(self.inner.handle)
+            .set_layouts(&layouts);
+
+        let handle = unsafe {
+            self.inner
+                .device
+                .handle
+                .allocate_descriptor_sets(&alloc_info)?[0]
+        };
+
+        Ok(DescriptorSet {
+            handle,
+            device: layout.device.clone(),
+            pool: self.clone(),
+            resources: RefCell::default(),
+        })
+    }
+
+    pub fn allocate_set(&self, layout: &DescriptorSetLayout) -> DescriptorSet {
+        self.try_allocate_set(layout)
+            .expect("Allocate descriptor set")
     }
 }
 
 impl DescriptorSet {
+    /// Applies all `writes` with a single `vkUpdateDescriptorSets` call.
     pub fn write(&self, writes: &[DescriptorWrite]) {
         let mut vk_writes = Vec::with_capacity(writes.len());
         let mut buffer_infos = Vec::with_capacity(writes.len());
@@ -345,6 +375,22 @@ impl DescriptorSet {
                     range,
                     array_element,
                 } => {
+                    debug_assert!(
+                        buffer.uses.contains(BufferUses::UNIFORM),
+                        "UniformBuffer write to binding {binding}: buffer lacks BufferUses::UNIFORM"
+                    );
+                    if cfg!(debug_assertions) {
+                        let limits = &self.device.adapter.properties.limits;
+                        if let Err(err) = validate_buffer_binding(
+                            *offset,
+                            *range,
+                            buffer.size as vk::DeviceSize,
+                            limits.min_uniform_buffer_offset_alignment,
+                            limits.max_uniform_buffer_range,
+                        ) {
+                            panic!("UniformBuffer write to binding {binding}: {err}");
+                        }
+                    }
                     buffer_infos.push((
                         *binding,
                         vk::DescriptorType::UNIFORM_BUFFER,
@@ -362,6 +408,22 @@ impl DescriptorSet {
                     range,
                     array_element,
                 } => {
+                    debug_assert!(
+                        buffer.uses.contains(BufferUses::STORAGE),
+                        "StorageBuffer write to binding {binding}: buffer lacks BufferUses::STORAGE"
+                    );
+                    if cfg!(debug_assertions) {
+                        let limits = &self.device.adapter.properties.limits;
+                        if let Err(err) = validate_buffer_binding(
+                            *offset,
+                            *range,
+                            buffer.size as vk::DeviceSize,
+                            limits.min_storage_buffer_offset_alignment,
+                            limits.max_storage_buffer_range,
+                        ) {
+                            panic!("StorageBuffer write to binding {binding}: {err}");
+                        }
+                    }
                     buffer_infos.push((
                         *binding,
                         vk::DescriptorType::STORAGE_BUFFER,
@@ -378,6 +440,10 @@ impl DescriptorSet {
                     image_layout,
                     array_element,
                 } => {
+                    debug_assert!(
+                        image_view.inner.image.usage.contains(ImageUsage::STORAGE),
+                        "StorageImage write to binding {binding}: image lacks TextureUses::STORAGE"
+                    );
                     image_infos.push((
                         *binding,
                         vk::DescriptorType::STORAGE_IMAGE,
@@ -393,6 +459,10 @@ impl DescriptorSet {
                     image_layout,
                     array_element,
                 } => {
+                    debug_assert!(
+                        image_view.inner.image.usage.contains(ImageUsage::SAMPLED),
+                        "SampledImage write to binding {binding}: image lacks TextureUses::SAMPLED"
+                    );
                     image_infos.push((
                         *binding,
                         vk::DescriptorType::SAMPLED_IMAGE,
@@ -421,6 +491,11 @@ impl DescriptorSet {
                     sampler,
                     array_element,
                 } => {
+                    debug_assert!(
+                        image_view.inner.image.usage.contains(ImageUsage::SAMPLED),
+                        "CombinedImageSampler write to binding {binding}: image lacks \
+                         TextureUses::SAMPLED"
+                    );
                     image_infos.push((
                         *binding,
                         vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
@@ -434,31 +509,106 @@ impl DescriptorSet {
             }
         }
 
-        for (binding, descriptor_type, buffer_info, array_element) in &buffer_infos {
+        // Infos are copied into flat vectors so runs of consecutive array elements can share a
+        // single `WriteDescriptorSet`; they must outlive the `update_descriptor_sets` call below.
+        let buffer_runs = descriptor_runs(&buffer_infos);
+        let buffer_infos = buffer_infos
+            .iter()
+            .map(|(_, _, info, _)| *info)
+            .collect::<Vec<_>>();
+        let image_runs = descriptor_runs(&image_infos);
+        let image_infos = image_infos
+            .iter()
+            .map(|(_, _, info, _)| *info)
+            .collect::<Vec<_>>();
+
+        for run in &buffer_runs {
             vk_writes.push(
                 vk::WriteDescriptorSet::default()
                     .dst_set(self.handle)
-                    .dst_binding(*binding)
-                    .dst_array_element(*array_element)
-                    .descriptor_type(*descriptor_type)
-                    .buffer_info(std::slice::from_ref(buffer_info)),
+                    .dst_binding(run.binding)
+                    .dst_array_element(run.array_element)
+                    .descriptor_type(run.descriptor_type)
+                    .buffer_info(&buffer_infos[run.infos.clone()]),
             );
         }
 
-        for (binding, descriptor_type, image_info, array_element) in &image_infos {
+        for run in &image_runs {
             vk_writes.push(
                 vk::WriteDescriptorSet::default()
                     .dst_set(self.handle)
-                    .dst_binding(*binding)
-                    .dst_array_element(*array_element)
-                    .descriptor_type(*descriptor_type)
-                    .image_info(std::slice::from_ref(image_info)),
+                    .dst_binding(run.binding)
+                    .dst_array_element(run.array_element)
+                    .descriptor_type(run.descriptor_type)
+                    .image_info(&image_infos[run.infos.clone()]),
             );
         }
 
         unsafe {
             self.device.handle.update_descriptor_sets(&vk_writes, &[]);
         }
+
+        let mut resources = self.resources.borrow_mut();
+        for write in writes {
+            let (slot, held) = write.resources();
+            resources.insert(slot, held);
+        }
+    }
+}
+
+impl DescriptorWrite<'_> {
+    /// The `(binding, array_element)` this write targets and the resources it references.
+    fn resources(&self) -> ((u32, u32), Vec<KeepAlive>) {
+        match self {
+            DescriptorWrite::UniformBuffer {
+                binding,
+                buffer,
+                array_element,
+                ..
+            }
+            | DescriptorWrite::StorageBuffer {
+                binding,
+                buffer,
+                array_element,
+                ..
+            } => (
+                (*binding, array_element.unwrap_or(0)),
+                vec![buffer.inner.clone()],
+            ),
+            DescriptorWrite::StorageImage {
+                binding,
+                image_view,
+                array_element,
+                ..
+            }
+            | DescriptorWrite::SampledImage {
+                binding,
+                image_view,
+                array_element,
+                ..
+            } => (
+                (*binding, array_element.unwrap_or(0)),
+                vec![image_view.inner.clone()],
+            ),
+            DescriptorWrite::Sampler {
+                binding,
+                sampler,
+                array_element,
+            } => (
+                (*binding, array_element.unwrap_or(0)),
+                vec![sampler.inner.clone()],
+            ),
+            DescriptorWrite::CombinedImageSampler {
+                binding,
+                image_view,
+                sampler,
+                array_element,
+                ..
+            } => (
+                (*binding, array_element.unwrap_or(0)),
+                vec![image_view.inner.clone(), sampler.inner.clone()],
+            ),
+        }
     }
 }
 
@@ -472,7 +622,7 @@ impl Drop for DescriptorSetLayout {
     }
 }
 
-impl Drop for DescriptorPool {
+impl Drop for DescriptorPoolImpl {
     fn drop(&mut self) {
         unsafe {
             self.device
@@ -481,3 +631,92 @@ impl Drop for DescriptorPool {
         }
     }
 }
+
+#[derive(Debug, PartialEq, Eq)]
+struct DescriptorRun {
+    binding: u32,
+    descriptor_type: vk::DescriptorType,
+    array_element: u32,
+    infos: std::ops::Range<usize>,
+}
+
+/// Groups writes to consecutive array elements of the same binding and type.
+fn descriptor_runs<T>(writes: &[(u32, vk::DescriptorType, T, u32)]) -> Vec<DescriptorRun> {
+    let mut runs: Vec<DescriptorRun> = Vec::new();
+    for (index, &(binding, descriptor_type, _, array_element)) in writes.iter().enumerate() {
+        if let Some(run) = runs.last_mut()
+            && run.binding == binding
+            && run.descriptor_type == descriptor_type
+            && run.array_element + run.infos.len() as u32 == array_element
+        {
+            run.infos.end = index + 1;
+            continue;
+        }
+        runs.push(DescriptorRun {
+            binding,
+            descriptor_type,
+            array_element,
+            infos: index..index + 1,
+        });
+    }
+    runs
+}
+
+/// Checks a buffer descriptor's offset alignment and range against the adapter limits.
+fn validate_buffer_binding(
+    offset: vk::DeviceSize,
+    range: vk::DeviceSize,
+    buffer_size: vk::DeviceSize,
+    alignment: vk::DeviceSize,
+    max_range: u32,
+) -> Result<(), &'static str> {
+    if alignment != 0 && !offset.is_multiple_of(alignment) {
+        return Err("offset is not a multiple of the adapter's minimum offset alignment");
+    }
+    if offset > buffer_size {
+        return Err("offset is past the end of the buffer");
+    }
+    let range = if range == vk::WHOLE_SIZE {
+        buffer_size - offset
+    } else {
+        if range == 0 {
+            return Err("range must not be zero");
+        }
+        if range > buffer_size - offset {
+            return Err("range extends past the end of the buffer");
+        }
+        range
+    };
+    if range > max_range as vk::DeviceSize {
+        return Err("range exceeds the adapter's maximum buffer range");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn consecutive_array_elements_share_a_write() {
+        let image = vk::DescriptorType::SAMPLED_IMAGE;
+        let mut writes = (0..10).map(|i| (1, image, (), i)).collect::<Vec<_>>();
+        writes.push((1, image, (), 20));
+        writes.push((2, image, (), 21));
+
+        let runs = descriptor_runs(&writes);
+        assert_eq!(runs.len(), 3);
+        assert_eq!(runs[0].infos, 0..10);
+        assert_eq!(runs[1].array_element, 20);
+        assert_eq!(runs[2].binding, 2);
+    }
+
+    #[test]
+    fn buffer_binding_checks_alignment_and_range() {
+        assert!(validate_buffer_binding(256, 64, 1024, 256, 65536).is_ok());
+        assert!(validate_buffer_binding(0, vk::WHOLE_SIZE, 1024, 256, 65536).is_ok());
+        assert!(validate_buffer_binding(64, 64, 1024, 256, 65536).is_err());
+        assert!(validate_buffer_binding(768, 512, 1024, 256, 65536).is_err());
+        assert!(validate_buffer_binding(0, vk::WHOLE_SIZE, 1 << 20, 256, 65536).is_err());
+    }
+}
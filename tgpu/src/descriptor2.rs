@@ -2,13 +2,45 @@ use std::{collections::HashMap, sync::Arc};
 
 use ash::vk;
 
-use crate::{freelist::FreeList, raw::RawDevice};
+use crate::{
+    GPUError,
+    freelist::{FreeList, FreeListKey},
+    raw::RawDevice,
+};
+
+/// Shader-visible descriptor handle.
+///
+/// `index` is the slot in the bindless array, `generation` identifies which allocation of
+/// that slot the handle belongs to.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DescriptorHandle {
+    pub index: u32,
+    pub generation: u32,
+}
 
 pub struct DescriptorHandleRaw {
     index: u32,
+    generation: u32,
     ty: DescriptorType,
 }
 
+impl DescriptorHandleRaw {
+    pub fn handle(&self) -> DescriptorHandle {
+        DescriptorHandle {
+            index: self.index,
+            generation: self.generation,
+        }
+    }
+
+    fn key(&self) -> FreeListKey {
+        FreeListKey {
+            index: self.index as usize,
+            generation: self.generation,
+        }
+    }
+}
+
 pub struct BindlessPool {
     pub inner: Arc<BindlessPoolImpl>,
 }
@@ -198,19 +230,36 @@ impl BindlessPoolImpl {
 
     pub fn allocate(&self, ty: DescriptorType) -> DescriptorHandleRaw {
         let list = self.allocations.get(&ty).expect("allocations");
-        let index = list.insert(());
+        let key = list.insert(());
         let handle = DescriptorHandleRaw {
             ty: ty,
-            index: index as u32,
+            index: key.index as u32,
+            generation: key.generation,
         };
         handle
     }
 
-    pub fn free(&self, handle: DescriptorHandleRaw) {
-        let ty = handle.ty;
-        let index = handle.index as usize;
-        let list = self.allocations.get(&ty).expect("allocations");
-        let _data = list.remove(index);
+    /// Check if `handle` still refers to a live allocation.
+    pub fn contains(&self, handle: &DescriptorHandleRaw) -> bool {
+        self.allocations
+            .get(&handle.ty)
+            .is_some_and(|list| list.contains(handle.key()))
+    }
+
+    /// Number of live descriptors of `ty`.
+    pub fn allocated(&self, ty: DescriptorType) -> usize {
+        self.allocations.get(&ty).map_or(0, FreeList::len)
+    }
+
+    /// Fails with `GPUError::Validation` if `handle` is stale or was already freed.
+    pub fn free(&self, handle: DescriptorHandleRaw) -> Result<(), GPUError> {
+        let list = self.allocations.get(&handle.ty).expect("allocations");
+        match list.remove(handle.key()) {
+            Some(()) => Ok(()),
+            None => Err(GPUError::Validation(
+                "bindless handle is stale or already freed",
+            )),
+        }
     }
 }
 impl From<DescriptorType> for vk::DescriptorType {
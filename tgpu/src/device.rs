@@ -1,7 +1,10 @@
 use std::{
     collections::HashMap,
     mem::ManuallyDrop,
-    sync::{Arc, atomic::AtomicU64},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
     time::Duration,
 };
 
@@ -9,9 +12,13 @@ use ash::vk;
 use parking_lot::Mutex;
 
 use crate::{
-    Adapter, CommandPools, GPUError, Instance, Label, Queue, QueueFamilyInfo, QueueRequest,
-    Semaphore,
+    Adapter, CommandPoolConfig, CommandPools, GPUError, Instance, Label, Queue, QueueFamilyInfo,
+    QueueRequest, Semaphore,
+    allocations::EXTERNAL_MEMORY_EXTENSION,
+    image::SamplerCache,
     raw::{QueueImpl, RawAdapter, RawInstance, SemaphoreImpl},
+    shader::ShaderCache,
+    sync::EXTERNAL_SEMAPHORE_EXTENSION,
 };
 
 #[derive(Debug, Clone)]
@@ -26,6 +33,20 @@ pub struct Extensions {
     pub debug: ash::ext::debug_utils::Device,
     pub sync2: ash::khr::synchronization2::Device,
     pub dynamic: ash::khr::dynamic_rendering::Device,
+    pub draw_indirect_count: Option<ash::khr::draw_indirect_count::Device>,
+    pub local_read: Option<ash::khr::dynamic_rendering_local_read::Device>,
+    pub device_fault: Option<ash::ext::device_fault::Device>,
+    #[cfg(unix)]
+    pub external_semaphore: Option<ash::khr::external_semaphore_fd::Device>,
+    #[cfg(windows)]
+    pub external_semaphore: Option<ash::khr::external_semaphore_win32::Device>,
+    #[cfg(unix)]
+    pub external_memory: Option<ash::khr::external_memory_fd::Device>,
+    #[cfg(windows)]
+    pub external_memory: Option<ash::khr::external_memory_win32::Device>,
+    /// Shared by every swapchain created from this device.
+    pub surface: ash::khr::surface::Instance,
+    pub swapchain: ash::khr::swapchain::Device,
 }
 
 pub struct DeviceImpl {
@@ -34,14 +55,57 @@ pub struct DeviceImpl {
     pub adapter: RawAdapter,
     pub features: DeviceFeatures,
     pub ext: Extensions,
-    pub allocator: Arc<ManuallyDrop<vkm::Allocator>>,
+    // TODO: defragmentation. vk-mem 0.4 keeps `VmaDefragmentationInfo` in its private ffi
+    // module, and a move would need to rebind buffers/images whose handles are immutable once
+    // shared (and already written into descriptor sets). Needs rebindable resource handles and
+    // a vk-mem that exposes the info struct before `Device::defragment` can exist.
+    pub allocator: ManuallyDrop<Arc<vkm::Allocator>>,
+    pub lost: AtomicBool,
+    /// Created with `DeviceCreateInfo::compute_only`; graphics entry points are rejected.
+    pub compute_only: bool,
+    pub shader_cache: Mutex<ShaderCache>,
+    pub sampler_cache: Mutex<SamplerCache>,
+    /// Serializes `vkGetDeviceFaultInfoEXT`, which must not run concurrently on one device.
+    pub fault_lock: Mutex<()>,
 }
 
+/// Optional device features, validated against `AdapterFeatures` when the device is created.
+///
+/// Dynamic rendering, synchronization2 and timeline semaphores are always required.
 #[derive(Debug, Clone, Copy)]
 pub struct DeviceFeatures {
     pub fill_mode_non_solid: bool,
     pub descriptor_indexing: bool,
     pub buffer_device_address: bool,
+    pub shader_int64: bool,
+    pub fragment_stores_and_atomics: bool,
+    pub draw_indirect_count: bool,
+    pub multiview: bool,
+    pub sparse_binding: bool,
+    /// Partially resident 2D images; requires `sparse_binding`.
+    pub sparse_residency: bool,
+    /// Reading color/depth attachments as input attachments within the same `begin_render`.
+    pub dynamic_rendering_local_read: bool,
+    /// Fault reports after `ERROR_DEVICE_LOST` through `Device::get_device_fault_info`.
+    pub device_fault: bool,
+    /// More than one viewport and scissor per pipeline; see `RenderPipelineInfo::viewport_count`.
+    pub multi_viewport: bool,
+    /// Exporting semaphores as OS handles for interop; see `Semaphore::export_handle`.
+    pub external_semaphore: bool,
+    /// Sharing buffer and image memory as OS handles; see `BufferUses::EXTERNAL`.
+    pub external_memory: bool,
+    /// Out-of-bounds buffer accesses in shaders have defined results instead of undefined behavior.
+    ///
+    /// Useful while debugging shader index math; costs some performance.
+    pub robust_buffer_access: bool,
+    /// `VK_EXT_robustness2`: out-of-bounds reads return zero and writes are discarded.
+    ///
+    /// Requires `robust_buffer_access`.
+    pub robust_buffer_access2: bool,
+    /// Shader `printf` through `VK_KHR_shader_non_semantic_info`.
+    ///
+    /// Output only appears when the instance was created with `InstanceCreateInfo::debug_printf`.
+    pub debug_printf: bool,
 }
 
 impl DeviceFeatures {
@@ -50,8 +114,122 @@ impl DeviceFeatures {
             fill_mode_non_solid: false,
             descriptor_indexing: true,
             buffer_device_address: false,
+            shader_int64: false,
+            fragment_stores_and_atomics: false,
+            draw_indirect_count: false,
+            multiview: false,
+            sparse_binding: false,
+            sparse_residency: false,
+            dynamic_rendering_local_read: false,
+            device_fault: false,
+            multi_viewport: false,
+            external_semaphore: false,
+            external_memory: false,
+            robust_buffer_access: false,
+            robust_buffer_access2: false,
+            debug_printf: false,
         }
     }
+
+    /// No optional features, for adapters without full bindless support.
+    pub fn minimal() -> Self {
+        Self {
+            descriptor_indexing: false,
+            ..Self::modern_bindless_defaults()
+        }
+    }
+
+    /// Required for `PolygonMode::LINE` and `POINT`, e.g. wireframe rendering.
+    pub fn fill_mode_non_solid(mut self, enabled: bool) -> Self {
+        self.fill_mode_non_solid = enabled;
+        self
+    }
+
+    pub fn descriptor_indexing(mut self, enabled: bool) -> Self {
+        self.descriptor_indexing = enabled;
+        self
+    }
+
+    /// Also enables `shader_int64`.
+    pub fn buffer_device_address(mut self, enabled: bool) -> Self {
+        self.buffer_device_address = enabled;
+        self.shader_int64 |= enabled;
+        self
+    }
+
+    pub fn shader_int64(mut self, enabled: bool) -> Self {
+        self.shader_int64 = enabled;
+        self
+    }
+
+    pub fn fragment_stores_and_atomics(mut self, enabled: bool) -> Self {
+        self.fragment_stores_and_atomics = enabled;
+        self
+    }
+
+    pub fn draw_indirect_count(mut self, enabled: bool) -> Self {
+        self.draw_indirect_count = enabled;
+        self
+    }
+
+    pub fn multiview(mut self, enabled: bool) -> Self {
+        self.multiview = enabled;
+        self
+    }
+
+    pub fn sparse_binding(mut self, enabled: bool) -> Self {
+        self.sparse_binding = enabled;
+        self
+    }
+
+    /// Also enables `sparse_binding`.
+    pub fn sparse_residency(mut self, enabled: bool) -> Self {
+        self.sparse_residency = enabled;
+        self.sparse_binding |= enabled;
+        self
+    }
+
+    pub fn dynamic_rendering_local_read(mut self, enabled: bool) -> Self {
+        self.dynamic_rendering_local_read = enabled;
+        self
+    }
+
+    pub fn device_fault(mut self, enabled: bool) -> Self {
+        self.device_fault = enabled;
+        self
+    }
+
+    pub fn multi_viewport(mut self, enabled: bool) -> Self {
+        self.multi_viewport = enabled;
+        self
+    }
+
+    pub fn external_semaphore(mut self, enabled: bool) -> Self {
+        self.external_semaphore = enabled;
+        self
+    }
+
+    pub fn external_memory(mut self, enabled: bool) -> Self {
+        self.external_memory = enabled;
+        self
+    }
+
+    pub fn robust_buffer_access(mut self, enabled: bool) -> Self {
+        self.robust_buffer_access = enabled;
+        self
+    }
+
+    /// Also enables `robust_buffer_access`.
+    pub fn robust_buffer_access2(mut self, enabled: bool) -> Self {
+        self.robust_buffer_access2 = enabled;
+        self.robust_buffer_access |= enabled;
+        self
+    }
+
+    pub fn debug_printf(mut self, enabled: bool) -> Self {
+        self.debug_printf = enabled;
+        self
+    }
 }
 
 impl Default for DeviceFeatures {
@@ -63,12 +241,87 @@ impl Default for DeviceFeatures {
 #[derive(Debug, Clone, Copy, Default)]
 pub struct DeviceCreateInfo {
     pub features: DeviceFeatures,
+    /// Skips the swapchain and dynamic rendering extensions, e.g. for headless compute.
+    ///
+    /// Swapchains and render pipelines can't be created on such a device.
+    pub compute_only: bool,
+    /// Growth and caching of each queue's per-thread command pools.
+    pub command_pools: CommandPoolConfig,
+}
+
+impl DeviceCreateInfo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn features(mut self, features: DeviceFeatures) -> Self {
+        self.features = features;
+        self
+    }
+
+    pub fn compute_only(mut self, compute_only: bool) -> Self {
+        self.compute_only = compute_only;
+        self
+    }
+
+    pub fn command_pools(mut self, config: CommandPoolConfig) -> Self {
+        self.command_pools = config;
+        self
+    }
 }
 
 impl Device {
+    /// Rebuilds the public handle from a device a resource holds on to.
+    pub(crate) fn from_raw(inner: RawDevice) -> Self {
+        let adapter = Adapter {
+            inner: inner.adapter.clone(),
+        };
+        Self { inner, adapter }
+    }
+
+    /// Raw `VkDevice`, for interop with other Vulkan libraries; it stays owned by `self`.
+    pub fn raw_handle(&self) -> vk::Device {
+        self.inner.handle.handle()
+    }
+
     pub fn wait_idle(&self) {
         unsafe { self.inner.wait_idle() };
     }
+
+    /// Whether a submit, present or wait has reported `ERROR_DEVICE_LOST`.
+    pub fn is_lost(&self) -> bool {
+        self.inner.is_lost()
+    }
+
+    /// Queries the fault report after `ERROR_DEVICE_LOST`.
+    ///
+    /// Returns `None` without the `device_fault` feature or when the driver has nothing to report.
+    pub fn get_device_fault_info(&self) -> Option<DeviceFaultInfo> {
+        self.inner.device_fault_info()
+    }
+}
+
+/// Driver report describing why the device was lost.
+#[derive(Debug, Clone, Default)]
+pub struct DeviceFaultInfo {
+    pub description: String,
+    pub addresses: Vec<DeviceFaultAddress>,
+    pub vendor: Vec<DeviceFaultVendorInfo>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DeviceFaultAddress {
+    pub ty: vk::DeviceFaultAddressTypeEXT,
+    pub address: vk::DeviceAddress,
+    /// The faulting address lies within `address & !(precision - 1)` and the next `precision` bytes.
+    pub precision: vk::DeviceSize,
+}
+
+#[derive(Debug, Clone)]
+pub struct DeviceFaultVendorInfo {
+    pub description: String,
+    pub code: u64,
+    pub data: u64,
 }
 
 impl DeviceImpl {
@@ -104,6 +357,119 @@ impl DeviceImpl {
             ));
         }
 
+        if info.features.shader_int64 && !adapter.features.shader_int64 {
+            return Err(GPUError::Validation(
+                "shader_int64 is not supported by the selected adapter",
+            ));
+        }
+
+        if info.features.fragment_stores_and_atomics
+            && !adapter.features.fragment_stores_and_atomics
+        {
+            return Err(GPUError::Validation(
+                "fragment_stores_and_atomics is not supported by the selected adapter",
+            ));
+        }
+
+        if info.features.draw_indirect_count && !adapter.features.draw_indirect_count {
+            return Err(GPUError::Validation(
+                "draw_indirect_count is not supported by the selected adapter",
+            ));
+        }
+
+        if info.features.multiview && !adapter.features.multiview {
+            return Err(GPUError::Validation(
+                "multiview is not supported by the selected adapter",
+            ));
+        }
+
+        if info.features.sparse_binding && !adapter.features.sparse_binding {
+            return Err(GPUError::Validation(
+                "sparse_binding is not supported by the selected adapter",
+            ));
+        }
+
+        if info.features.sparse_residency && !info.features.sparse_binding {
+            return Err(GPUError::Validation(
+                "sparse_residency requires sparse_binding",
+            ));
+        }
+
+        if info.features.sparse_residency && !adapter.features.sparse_residency_image_2d {
+            return Err(GPUError::Validation(
+                "sparse_residency is not supported by the selected adapter",
+            ));
+        }
+
+        if info.features.dynamic_rendering_local_read
+            && !adapter.features.dynamic_rendering_local_read
+        {
+            return Err(GPUError::Validation(
+                "dynamic_rendering_local_read is not supported by the selected adapter",
+            ));
+        }
+
+        if info.features.device_fault && !adapter.features.device_fault {
+            return Err(GPUError::Validation(
+                "device_fault is not supported by the selected adapter",
+            ));
+        }
+
+        if info.features.multi_viewport && !adapter.features.multi_viewport {
+            return Err(GPUError::Validation(
+                "multi_viewport is not supported by the selected adapter",
+            ));
+        }
+
+        if info.features.external_semaphore && !adapter.features.external_semaphore {
+            return Err(GPUError::Validation(
+                "external_semaphore is not supported by the selected adapter",
+            ));
+        }
+
+        if info.features.external_memory && !adapter.features.external_memory {
+            return Err(GPUError::Validation(
+                "external_memory is not supported by the selected adapter",
+            ));
+        }
+
+        if info.features.robust_buffer_access && !adapter.features.robust_buffer_access {
+            return Err(GPUError::Validation(
+                "robust_buffer_access is not supported by the selected adapter",
+            ));
+        }
+
+        if info.features.robust_buffer_access2 && !info.features.robust_buffer_access {
+            return Err(GPUError::Validation(
+                "robust_buffer_access2 requires robust_buffer_access",
+            ));
+        }
+
+        if info.features.robust_buffer_access2 && !adapter.features.robust_buffer_access2 {
+            return Err(GPUError::Validation(
+                "robust_buffer_access2 is not supported by the selected adapter",
+            ));
+        }
+
+        if info.features.debug_printf && !adapter.features.shader_non_semantic_info {
+            return Err(GPUError::Validation(
+                "debug_printf is not supported by the selected adapter",
+            ));
+        }
+
+        if info.compute_only
+            && (info.features.fill_mode_non_solid
+                || info.features.fragment_stores_and_atomics
+                || info.features.draw_indirect_count
+                || info.features.multiview
+                || info.features.dynamic_rendering_local_read
+                || info.features.multi_viewport)
+        {
+            return Err(GPUError::Validation(
+                "compute_only devices can't enable graphics features",
+            ));
+        }
+
         let mut requested_features = vk::PhysicalDeviceFeatures::default();
         if info.features.descriptor_indexing {
             requested_features = requested_features
@@ -115,9 +481,24 @@ impl DeviceImpl {
         if info.features.fill_mode_non_solid {
             requested_features = requested_features.fill_mode_non_solid(true);
         }
-        if info.features.buffer_device_address {
+        if info.features.buffer_device_address || info.features.shader_int64 {
             requested_features = requested_features.shader_int64(true);
         }
+        if info.features.fragment_stores_and_atomics {
+            requested_features = requested_features.fragment_stores_and_atomics(true);
+        }
+        if info.features.sparse_binding {
+            requested_features = requested_features.sparse_binding(true);
+        }
+        if info.features.sparse_residency {
+            requested_features = requested_features.sparse_residency_image2_d(true);
+        }
+        if info.features.multi_viewport {
+            requested_features = requested_features.multi_viewport(true);
+        }
+        if info.features.robust_buffer_access {
+            requested_features = requested_features.robust_buffer_access(true);
+        }
 
         let mut pdev_features2 =
             vk::PhysicalDeviceFeatures2::default().features(requested_features);
@@ -153,20 +534,58 @@ impl DeviceImpl {
         let mut synchronization_two_features =
             vk::PhysicalDeviceSynchronization2Features::default().synchronization2(true);
 
-        let mut vulkan_1_1_features =
-            vk::PhysicalDeviceVulkan11Features::default().shader_draw_parameters(true);
+        let mut vulkan_1_1_features = vk::PhysicalDeviceVulkan11Features::default()
+            .shader_draw_parameters(!info.compute_only)
+            .multiview(info.features.multiview);
+
+        let mut local_read_features =
+            vk::PhysicalDeviceDynamicRenderingLocalReadFeaturesKHR::default()
+                .dynamic_rendering_local_read(true);
+
+        let mut fault_features = vk::PhysicalDeviceFaultFeaturesEXT::default().device_fault(true);
+
+        let mut robustness2_features =
+            vk::PhysicalDeviceRobustness2FeaturesEXT::default().robust_buffer_access2(true);
 
         // TODO: once apple engineers actually use their own stuff
         // we can remove all of them except swapchain
-        let device_extensions = vec![
-            ash::khr::swapchain::NAME.as_ptr(),
+        let mut device_extensions = vec![
             ash::khr::timeline_semaphore::NAME.as_ptr(),
-            ash::khr::dynamic_rendering::NAME.as_ptr(),
             ash::khr::synchronization2::NAME.as_ptr(),
         ];
 
-        #[cfg(target_os = "macos")]
-        let mut device_extensions = device_extensions;
+        if !info.compute_only {
+            device_extensions.push(ash::khr::swapchain::NAME.as_ptr());
+            device_extensions.push(ash::khr::dynamic_rendering::NAME.as_ptr());
+        }
+
+        if info.features.draw_indirect_count {
+            device_extensions.push(ash::khr::draw_indirect_count::NAME.as_ptr());
+        }
+
+        if info.features.dynamic_rendering_local_read {
+            device_extensions.push(ash::khr::dynamic_rendering_local_read::NAME.as_ptr());
+        }
+
+        if info.features.device_fault {
+            device_extensions.push(ash::ext::device_fault::NAME.as_ptr());
+        }
+
+        if info.features.external_semaphore {
+            device_extensions.push(EXTERNAL_SEMAPHORE_EXTENSION.as_ptr());
+        }
+
+        if info.features.external_memory {
+            device_extensions.push(EXTERNAL_MEMORY_EXTENSION.as_ptr());
+        }
+
+        if info.features.robust_buffer_access2 {
+            device_extensions.push(ash::ext::robustness2::NAME.as_ptr());
+        }
+
+        if info.features.debug_printf {
+            device_extensions.push(ash::khr::shader_non_semantic_info::NAME.as_ptr());
+        }
 
         #[cfg(target_os = "macos")]
         {
@@ -200,22 +619,35 @@ impl DeviceImpl {
             })
             .collect();
 
-        let device_info = vk::DeviceCreateInfo::default()
+        let mut device_info = vk::DeviceCreateInfo::default()
             .queue_create_infos(&queue_create_infos)
             .enabled_extension_names(&device_extensions)
             // enable this and remove all other probably once apple swes stop blueskying
             // .push_next(&mut vulkan_1_3_features)
-            .push_next(&mut dynamic_rendering_features)
             .push_next(&mut pdev_features2)
             .push_next(&mut timeline_semaphore_features)
             .push_next(&mut synchronization_two_features)
             .push_next(&mut vulkan_1_1_features)
             .push_next(&mut descriptor_indexing_features)
             .push_next(&mut buffer_device_address_features);
+        if !info.compute_only {
+            device_info = device_info.push_next(&mut dynamic_rendering_features);
+        }
+        if info.features.dynamic_rendering_local_read {
+            device_info = device_info.push_next(&mut local_read_features);
+        }
+        if info.features.device_fault {
+            device_info = device_info.push_next(&mut fault_features);
+        }
+        if info.features.robust_buffer_access2 {
+            device_info = device_info.push_next(&mut robustness2_features);
+        }
 
         let handle = unsafe { instance.create_device_handle(&device_info, adapter.handle) };
 
-        let ext = unsafe { Self::new_extensions(&instance.handle, &handle) };
+        let ext = unsafe {
+            Self::new_extensions(&instance.entry, &instance.handle, &handle, &info.features)
+        };
 
         let physical_device = unsafe { adapter.handle() };
         let mut allocator_info =
@@ -232,7 +664,12 @@ impl DeviceImpl {
             adapter,
             features: info.features,
             ext,
-            allocator: Arc::new(ManuallyDrop::new(allocator)),
+            allocator: ManuallyDrop::new(Arc::new(allocator)),
+            lost: AtomicBool::new(false),
+            compute_only: info.compute_only,
+            shader_cache: Mutex::new(ShaderCache::new()),
+            sampler_cache: Mutex::new(SamplerCache::new()),
+            fault_lock: Mutex::new(()),
         };
 
         let new = Arc::new(new);
@@ -245,15 +682,54 @@ impl DeviceImpl {
         Ok((new, queues))
     }
 
-    pub unsafe fn new_extensions(instance: &ash::Instance, device: &ash::Device) -> Extensions {
+    pub unsafe fn new_extensions(
+        entry: &ash::Entry,
+        instance: &ash::Instance,
+        device: &ash::Device,
+        features: &DeviceFeatures,
+    ) -> Extensions {
         let debug = ash::ext::debug_utils::Device::new(instance, device);
         let sync2 = ash::khr::synchronization2::Device::new(instance, device);
         let dynamic = ash::khr::dynamic_rendering::Device::new(instance, device);
+        let draw_indirect_count = features
+            .draw_indirect_count
+            .then(|| ash::khr::draw_indirect_count::Device::new(instance, device));
+        let local_read = features
+            .dynamic_rendering_local_read
+            .then(|| ash::khr::dynamic_rendering_local_read::Device::new(instance, device));
+        let device_fault = features
+            .device_fault
+            .then(|| ash::ext::device_fault::Device::new(instance, device));
+        #[cfg(unix)]
+        let external_semaphore = features
+            .external_semaphore
+            .then(|| ash::khr::external_semaphore_fd::Device::new(instance, device));
+        #[cfg(windows)]
+        let external_semaphore = features
+            .external_semaphore
+            .then(|| ash::khr::external_semaphore_win32::Device::new(instance, device));
+        #[cfg(unix)]
+        let external_memory = features
+            .external_memory
+            .then(|| ash::khr::external_memory_fd::Device::new(instance, device));
+        #[cfg(windows)]
+        let external_memory = features
+            .external_memory
+            .then(|| ash::khr::external_memory_win32::Device::new(instance, device));
+        let surface = ash::khr::surface::Instance::new(entry, instance);
+        let swapchain = ash::khr::swapchain::Device::new(instance, device);
 
         Extensions {
             debug,
             sync2,
             dynamic,
+            draw_indirect_count,
+            local_read,
+            device_fault,
+            external_semaphore,
+            external_memory,
+            surface,
+            swapchain,
         }
     }
 
@@ -275,12 +751,101 @@ impl DeviceImpl {
         }
     }
 
-    pub unsafe fn wait_fence(&self, fence: vk::Fence, timeout: Option<u64>) {
+    pub fn is_lost(&self) -> bool {
+        self.lost.load(Ordering::Acquire)
+    }
+
+    pub fn device_fault_info(&self) -> Option<DeviceFaultInfo> {
+        let fault = self.ext.device_fault.as_ref()?;
+        let get = fault.fp().get_device_fault_info_ext;
+        let _lock = self.fault_lock.lock();
+
+        let mut counts = vk::DeviceFaultCountsEXT::default();
+        unsafe { get(self.handle.handle(), &mut counts, std::ptr::null_mut()) }
+            .result()
+            .ok()?;
+
+        let mut addresses =
+            vec![vk::DeviceFaultAddressInfoEXT::default(); counts.address_info_count as usize];
+        let mut vendor =
+            vec![vk::DeviceFaultVendorInfoEXT::default(); counts.vendor_info_count as usize];
+        counts.vendor_binary_size = 0;
+
+        let mut info = vk::DeviceFaultInfoEXT {
+            p_address_infos: addresses.as_mut_ptr(),
+            p_vendor_infos: vendor.as_mut_ptr(),
+            ..Default::default()
+        };
+        let result = unsafe { get(self.handle.handle(), &mut counts, &mut info) };
+        if !matches!(result, vk::Result::SUCCESS | vk::Result::INCOMPLETE) {
+            return None;
+        }
+        addresses.truncate(counts.address_info_count as usize);
+        vendor.truncate(counts.vendor_info_count as usize);
+
+        let text = |description: Result<&std::ffi::CStr, _>| {
+            description
+                .map(|text| text.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        };
+
+        Some(DeviceFaultInfo {
+            description: text(info.description_as_c_str()),
+            addresses: addresses
+                .iter()
+                .map(|address| DeviceFaultAddress {
+                    ty: address.address_type,
+                    address: address.reported_address,
+                    precision: address.address_precision,
+                })
+                .collect(),
+            vendor: vendor
+                .iter()
+                .map(|vendor| DeviceFaultVendorInfo {
+                    description: text(vendor.description_as_c_str()),
+                    code: vendor.vendor_fault_code,
+                    data: vendor.vendor_fault_data,
+                })
+                .collect(),
+        })
+    }
+
+    /// Converts a Vulkan error, marking the device as lost on `ERROR_DEVICE_LOST`.
+    pub fn vk_error(&self, result: vk::Result) -> GPUError {
+        if result == vk::Result::ERROR_DEVICE_LOST {
+            self.lost.store(true, Ordering::Release);
+        }
+        GPUError::from(result)
+    }
+
+    pub unsafe fn wait_fence(
+        &self,
+        fence: vk::Fence,
+        timeout: Option<u64>,
+    ) -> Result<(), GPUError> {
         let timeout = timeout.unwrap_or(u64::MAX);
         unsafe {
             self.handle
                 .wait_for_fences(&[fence], true, timeout)
-                .expect("Wait for fence")
+                .map_err(|e| self.vk_error(e))
+        }
+    }
+
+    /// Returns `Ok(false)` if `timeout` elapsed before the wait condition was met.
+    ///
+    /// # Safety
+    /// Every fence must be live and owned by this device.
+    pub unsafe fn wait_fences(
+        &self,
+        fences: &[vk::Fence],
+        wait_all: bool,
+        timeout: Option<Duration>,
+    ) -> Result<bool, GPUError> {
+        let timeout_ns = timeout.map_or(u64::MAX, |d| d.as_nanos() as u64);
+        match unsafe { self.handle.wait_for_fences(fences, wait_all, timeout_ns) } {
+            Ok(()) => Ok(true),
+            Err(vk::Result::TIMEOUT) => Ok(false),
+            Err(e) => Err(self.vk_error(e)),
         }
     }
 
@@ -294,22 +859,25 @@ impl DeviceImpl {
         unsafe { self.handle.get_semaphore_counter_value(handle).unwrap() }
     }
 
-    pub unsafe fn signal_semaphore(&self, handle: vk::Semaphore, value: u64) {
+    pub unsafe fn signal_semaphore(
+        &self,
+        handle: vk::Semaphore,
+        value: u64,
+    ) -> Result<(), GPUError> {
         let info = vk::SemaphoreSignalInfo::default()
             .semaphore(handle)
             .value(value);
 
-        unsafe {
-            let _ = self.handle.signal_semaphore(&info);
-        }
+        unsafe { self.handle.signal_semaphore(&info) }.map_err(|e| self.vk_error(e))
     }
 
+    /// Returns `Ok(false)` if `timeout` elapsed before the semaphore reached `value`.
     pub unsafe fn wait_semaphore(
         &self,
         handle: vk::Semaphore,
         value: u64,
         timeout: Option<Duration>,
-    ) {
+    ) -> Result<bool, GPUError> {
         let handles = [handle];
         let values = [value];
         let timeout_ns = timeout.map_or(u64::MAX, |d| d.as_nanos() as u64);
@@ -317,8 +885,10 @@ impl DeviceImpl {
             .semaphores(&handles)
             .values(&values);
 
-        unsafe {
-            let _ = self.handle.wait_semaphores(&info, timeout_ns);
+        match unsafe { self.handle.wait_semaphores(&info, timeout_ns) } {
+            Ok(()) => Ok(true),
+            Err(vk::Result::TIMEOUT) => Ok(false),
+            Err(e) => Err(self.vk_error(e)),
         }
     }
 
@@ -326,13 +896,13 @@ impl DeviceImpl {
         &self,
         label: Option<Label<'a>>,
         spirv: &'a [u32],
-    ) -> vk::ShaderModule {
+    ) -> Result<vk::ShaderModule, GPUError> {
         let info = vk::ShaderModuleCreateInfo::default().code(spirv);
-        let module = unsafe { self.handle.create_shader_module(&info, None).unwrap() };
+        let module = unsafe { self.handle.create_shader_module(&info, None) }?;
         if let Some(label) = &label {
             unsafe { self.attach_label(module, label) };
         }
-        module
+        Ok(module)
     }
 }
 
@@ -351,6 +921,7 @@ impl Instance {
         )?;
 
         let inner = device.clone();
+        let pool_config = info.command_pools;
 
         let device = Device {
             inner: device,
@@ -359,7 +930,7 @@ impl Instance {
 
         let queues = queues.into_iter().map(move |queue| Queue {
             inner: Arc::new(queue),
-            pools: CommandPools::new(inner.clone()),
+            pools: CommandPools::new(inner.clone(), pool_config),
             state: Mutex::new(()),
             submission_counter: AtomicU64::new(1),
             timeline: Semaphore {
@@ -371,12 +942,47 @@ impl Instance {
     }
 }
 
+/// Device and queue with `flags` on the first adapter, for tests that need a GPU.
+#[cfg(test)]
+pub(crate) fn test_device(flags: crate::QueueFlags) -> (Device, Queue) {
+    test_device_with(flags, |_| DeviceCreateInfo::default())
+}
+
+/// Like `test_device`, with a `DeviceCreateInfo` chosen for the adapter, e.g. its features.
+#[cfg(test)]
+pub(crate) fn test_device_with(
+    flags: crate::QueueFlags,
+    info: impl FnOnce(&Adapter) -> DeviceCreateInfo,
+) -> (Device, Queue) {
+    let instance = Instance::new(&crate::InstanceCreateInfo {
+        app_name: "tgpu test",
+        engine_name: "tgpu",
+        ..Default::default()
+    })
+    .unwrap();
+    let adapter = instance.adapters(&[]).unwrap().next().unwrap();
+    let info = info(&adapter);
+    let (device, mut queues) = instance
+        .request_device(
+            &info,
+            adapter,
+            &[QueueRequest {
+                required_flags: flags,
+                exclude_flags: crate::QueueFlags::empty(),
+                strict: false,
+                allow_fallback_share: true,
+            }],
+        )
+        .unwrap();
+    (device, queues.next().unwrap())
+}
+
 impl Drop for DeviceImpl {
     fn drop(&mut self) {
         unsafe {
             let _ = self.handle.device_wait_idle();
-            let allocator = Arc::get_mut(&mut self.allocator).expect("Get Allocator");
-            ManuallyDrop::drop(allocator);
+            Arc::get_mut(&mut self.allocator).expect("Get Allocator");
+            ManuallyDrop::drop(&mut self.allocator);
             self.handle.destroy_device(None);
         }
     }
@@ -1,4 +1,4 @@
-use std::{collections::HashMap, fmt, ops::Range, sync::Arc};
+use std::{collections::HashMap, fmt, ops::Range};
 
 use ::egui::{self as egui_crate, TextureId, epaint};
 use ::egui_winit as egui_winit_crate;
@@ -12,69 +12,12 @@ use crate::{
     GPUError, HostAccess, ImageAccess, ImageDesc, ImageLayout, ImageLayoutTransition,
     ImageTransition, ImageUses, ImportedBufferDesc, ImportedImageDesc, Label, MemoryPreset,
     RenderGraph, RenderPipeline, RenderPipelineInfo, RenderRecorder, SamplerCreateInfo,
-    ShaderSource, ShaderStageFlags, Swapchain, ViewImage, ViewImageDesc,
+    ShaderSource, ShaderStageFlags, Swapchain, ViewImage, ViewImageDesc, ui,
 };
 
 pub use ::egui;
 pub use ::egui_winit;
 
-const SHADER_WGSL: &str = r#"
-struct PushConstants {
-    screen_size: vec2<f32>,
-};
-
-var<push_constant> pc: PushConstants;
-
-struct VertexOutput {
-    @location(0) tex_coord: vec2<f32>,
-    @location(1) color_gamma: vec4<f32>,
-    @builtin(position) position: vec4<f32>,
-};
-
-fn position_from_screen(screen_pos: vec2<f32>) -> vec4<f32> {
-    return vec4<f32>(
-        2.0 * screen_pos.x / pc.screen_size.x - 1.0,
-        2.0 * screen_pos.y / pc.screen_size.y - 1.0,
-        0.0,
-        1.0,
-    );
-}
-
-fn linear_from_gamma_rgb(srgb: vec3<f32>) -> vec3<f32> {
-    let cutoff = srgb < vec3<f32>(0.04045);
-    let lower = srgb / vec3<f32>(12.92);
-    let higher = pow((srgb + vec3<f32>(0.055)) / vec3<f32>(1.055), vec3<f32>(2.4));
-    return select(higher, lower, cutoff);
-}
-
-@vertex
-fn vs_main(
-    @location(0) pos: vec2<f32>,
-    @location(1) uv: vec2<f32>,
-    @location(2) color: vec4<f32>,
-) -> VertexOutput {
-    var out: VertexOutput;
-    out.tex_coord = uv;
-    out.color_gamma = color;
-    out.position = position_from_screen(pos);
-    return out;
-}
-
-@group(0) @binding(0) var ui_texture: texture_2d<f32>;
-@group(0) @binding(1) var ui_sampler: sampler;
-
-@fragment
-fn fs_main_gamma(in: VertexOutput) -> @location(0) vec4<f32> {
-    return in.color_gamma * textureSample(ui_texture, ui_sampler, in.tex_coord);
-}
-
-@fragment
-fn fs_main_linear(in: VertexOutput) -> @location(0) vec4<f32> {
-    let color_gamma = in.color_gamma * textureSample(ui_texture, ui_sampler, in.tex_coord);
-    return vec4<f32>(linear_from_gamma_rgb(color_gamma.rgb), color_gamma.a);
-}
-"#;
-
 #[derive(Debug)]
 pub enum Error {
     Gpu(GPUError),
@@ -126,12 +69,6 @@ impl Default for RendererCreateInfo<'_> {
     }
 }
 
-#[repr(C)]
-#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-struct PushConstants {
-    screen_size: [f32; 2],
-}
-
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum TextureKind {
     Managed,
@@ -186,7 +123,7 @@ pub struct Renderer {
     first_frame: bool,
     pipeline: RenderPipeline,
     texture_layout: DescriptorSetLayout,
-    texture_pool: Arc<DescriptorPool>,
+    texture_pool: DescriptorPool,
     textures: HashMap<TextureId, TextureBinding>,
     frames: Vec<FrameResources>,
     pending_texture_frees: Vec<TextureId>,
@@ -248,16 +185,7 @@ impl Renderer {
             max_texture_side,
         );
 
-        let texture_binding = [
-            DescriptorBinding::unique(0, DescriptorType::SampledImage, ShaderStageFlags::FRAGMENT),
-            DescriptorBinding::unique(1, DescriptorType::Sampler, ShaderStageFlags::FRAGMENT),
-        ];
-
-        let texture_layout = device.create_descriptor_set_layout(&DescriptorSetLayoutInfo {
-            bindings: &texture_binding,
-            label: info.label.clone(),
-            ..Default::default()
-        });
+        let (texture_layout, pipeline) = create_pipeline(device, info)?;
 
         let texture_pool = device.create_descriptor_pool(&DescriptorPoolInfo {
             max_sets: info.max_textures,
@@ -266,67 +194,6 @@ impl Renderer {
             ..Default::default()
         });
 
-        let shader = device
-            .create_shader(info.label.clone(), ShaderSource::Wgsl(SHADER_WGSL))
-            .map_err(Error::Shader)?;
-
-        let vertex_binding = [vk::VertexInputBindingDescription::default()
-            .binding(0)
-            .stride(std::mem::size_of::<epaint::Vertex>() as u32)
-            .input_rate(vk::VertexInputRate::VERTEX)];
-        let vertex_attributes = [
-            vk::VertexInputAttributeDescription::default()
-                .location(0)
-                .binding(0)
-                .format(vk::Format::R32G32_SFLOAT)
-                .offset(0),
-            vk::VertexInputAttributeDescription::default()
-                .location(1)
-                .binding(0)
-                .format(vk::Format::R32G32_SFLOAT)
-                .offset(8),
-            vk::VertexInputAttributeDescription::default()
-                .location(2)
-                .binding(0)
-                .format(vk::Format::R8G8B8A8_UNORM)
-                .offset(16),
-        ];
-        let vertex_input = vk::PipelineVertexInputStateCreateInfo::default()
-            .vertex_binding_descriptions(&vertex_binding)
-            .vertex_attribute_descriptions(&vertex_attributes);
-
-        let blend_state = [vk::PipelineColorBlendAttachmentState::default()
-            .blend_enable(true)
-            .src_color_blend_factor(vk::BlendFactor::ONE)
-            .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
-            .color_blend_op(vk::BlendOp::ADD)
-            .src_alpha_blend_factor(vk::BlendFactor::ONE_MINUS_DST_ALPHA)
-            .dst_alpha_blend_factor(vk::BlendFactor::ONE)
-            .alpha_blend_op(vk::BlendOp::ADD)
-            .color_write_mask(vk::ColorComponentFlags::RGBA)];
-
-        let fragment_entry = if is_srgb_format(info.color_format) {
-            "fs_main_linear"
-        } else {
-            "fs_main_gamma"
-        };
-
-        let pipeline = device.create_render_pipeline(&RenderPipelineInfo {
-            label: info.label.clone(),
-            vertex_shader: shader.entry("vs_main"),
-            fragment_shader: shader.entry(fragment_entry),
-            color_formats: &[info.color_format],
-            descriptor_layouts: &[&texture_layout],
-            push_constant_size: Some(std::mem::size_of::<PushConstants>() as u32),
-            blend_states: Some(&blend_state),
-            vertex_input_state: Some(vertex_input),
-            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
-            polygon: vk::PolygonMode::FILL,
-            cull: vk::CullModeFlags::NONE,
-            front_face: vk::FrontFace::COUNTER_CLOCKWISE,
-            ..Default::default()
-        });
-
         let mut frames = Vec::with_capacity(info.max_frames_in_flight);
         for _ in 0..info.max_frames_in_flight {
             frames.push(FrameResources::default());
@@ -639,9 +506,7 @@ impl Renderer {
             ));
         };
 
-        let descriptor_set = self
-            .device
-            .create_descriptor_set(self.texture_pool.clone(), &self.texture_layout);
+        let descriptor_set = self.texture_pool.allocate_set(&self.texture_layout);
         descriptor_set.write(&[
             DescriptorWrite::SampledImage {
                 binding: 0,
@@ -1071,6 +936,67 @@ impl Frame<'_> {
     }
 }
 
+/// Texture descriptor layout and the pipeline drawing egui meshes into `info.color_format`.
+fn create_pipeline(
+    device: &Device,
+    info: &RendererCreateInfo<'_>,
+) -> Result<(DescriptorSetLayout, RenderPipeline), Error> {
+    let texture_binding = [
+        DescriptorBinding::unique(0, DescriptorType::SampledImage, ShaderStageFlags::FRAGMENT),
+        DescriptorBinding::unique(1, DescriptorType::Sampler, ShaderStageFlags::FRAGMENT),
+    ];
+
+    let texture_layout = device.create_descriptor_set_layout(&DescriptorSetLayoutInfo {
+        bindings: &texture_binding,
+        label: info.label.clone(),
+        ..Default::default()
+    });
+
+    let shader = device
+        .create_shader(info.label.clone(), ShaderSource::Wgsl(ui::SHADER_WGSL))
+        .map_err(|err| match err {
+            GPUError::ShaderCompile(message) => Error::Shader(message),
+            err => Error::Gpu(err),
+        })?;
+
+    const _: () = assert!(std::mem::size_of::<epaint::Vertex>() == ui::VERTEX_STRIDE as usize);
+    let vertex_binding = [vk::VertexInputBindingDescription::default()
+        .binding(0)
+        .stride(ui::VERTEX_STRIDE)
+        .input_rate(vk::VertexInputRate::VERTEX)];
+    let vertex_input = vk::PipelineVertexInputStateCreateInfo::default()
+        .vertex_binding_descriptions(&vertex_binding)
+        .vertex_attribute_descriptions(&ui::VERTEX_ATTRIBUTES);
+
+    let blend_state = [vk::PipelineColorBlendAttachmentState::default()
+        .blend_enable(true)
+        .src_color_blend_factor(vk::BlendFactor::ONE)
+        .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+        .color_blend_op(vk::BlendOp::ADD)
+        .src_alpha_blend_factor(vk::BlendFactor::ONE_MINUS_DST_ALPHA)
+        .dst_alpha_blend_factor(vk::BlendFactor::ONE)
+        .alpha_blend_op(vk::BlendOp::ADD)
+        .color_write_mask(vk::ColorComponentFlags::RGBA)];
+
+    let pipeline = device.create_render_pipeline(&RenderPipelineInfo {
+        label: info.label.clone(),
+        vertex_shader: shader.entry("vs_main"),
+        fragment_shader: shader.entry(ui::fragment_entry(info.color_format)),
+        color_formats: &[info.color_format],
+        descriptor_layouts: &[&texture_layout],
+        push_constant_size: Some(std::mem::size_of::<ui::PushConstants>() as u32),
+        blend_states: Some(&blend_state),
+        vertex_input_state: Some(vertex_input),
+        topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+        polygon: vk::PolygonMode::FILL,
+        cull: vk::CullModeFlags::NONE,
+        front_face: vk::FrontFace::COUNTER_CLOCKWISE,
+        ..Default::default()
+    });
+
+    Ok((texture_layout, pipeline))
+}
+
 fn paint_render_pass(
     pipeline: &RenderPipeline,
     vertex_buffer: &Buffer,
@@ -1097,16 +1023,19 @@ fn paint_render_pass(
     });
     render.push_render_constants(
         pipeline,
-        PushConstants {
-            screen_size: [
-                extent.width as f32 / pixels_per_point,
-                extent.height as f32 / pixels_per_point,
+        ui::PushConstants {
+            scale: [
+                2.0 * pixels_per_point / extent.width as f32,
+                2.0 * pixels_per_point / extent.height as f32,
             ],
+            translate: [-1.0, -1.0],
         },
     );
 
     for draw in draws {
-        let Some(scissor) = clip_rect_to_scissor(draw.clip_rect, pixels_per_point, extent) else {
+        let clip = draw.clip_rect;
+        let rect = [clip.min.x, clip.min.y, clip.max.x, clip.max.y].map(|v| v * pixels_per_point);
+        let Some(scissor) = ui::clip_rect_to_scissor(rect, extent) else {
             continue;
         };
 
@@ -1121,40 +1050,6 @@ fn paint_render_pass(
     });
 }
 
-fn clip_rect_to_scissor(
-    clip_rect: egui_crate::Rect,
-    pixels_per_point: f32,
-    extent: vk::Extent2D,
-) -> Option<vk::Rect2D> {
-    let min_x = (clip_rect.min.x * pixels_per_point)
-        .floor()
-        .clamp(0.0, extent.width as f32);
-    let min_y = (clip_rect.min.y * pixels_per_point)
-        .floor()
-        .clamp(0.0, extent.height as f32);
-    let max_x = (clip_rect.max.x * pixels_per_point)
-        .ceil()
-        .clamp(min_x, extent.width as f32);
-    let max_y = (clip_rect.max.y * pixels_per_point)
-        .ceil()
-        .clamp(min_y, extent.height as f32);
-
-    if max_x <= min_x || max_y <= min_y {
-        return None;
-    }
-
-    Some(vk::Rect2D {
-        offset: vk::Offset2D {
-            x: min_x as i32,
-            y: min_y as i32,
-        },
-        extent: vk::Extent2D {
-            width: (max_x - min_x) as u32,
-            height: (max_y - min_y) as u32,
-        },
-    })
-}
-
 fn update_texture_sampler(
     device: &Device,
     binding: &mut TextureBinding,
@@ -1222,15 +1117,164 @@ fn texture_wrap(wrap: epaint::textures::TextureWrapMode) -> vk::SamplerAddressMo
     }
 }
 
-fn is_srgb_format(format: vk::Format) -> bool {
-    matches!(
-        format,
-        vk::Format::R8_SRGB
-            | vk::Format::R8G8_SRGB
-            | vk::Format::R8G8B8_SRGB
-            | vk::Format::B8G8R8_SRGB
-            | vk::Format::R8G8B8A8_SRGB
-            | vk::Format::B8G8R8A8_SRGB
-            | vk::Format::A8B8G8R8_SRGB_PACK32
-    )
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{QueueFlags, Region, command::test_render, device::test_device};
+
+    #[test]
+    #[ignore = "requires a Vulkan device"]
+    fn paints_a_textured_quad_in_points() {
+        let (device, queue) = test_device(QueueFlags::GRAPHICS);
+        let info = RendererCreateInfo {
+            color_format: vk::Format::R8G8B8A8_UNORM,
+            ..Default::default()
+        };
+        let (texture_layout, pipeline) = create_pipeline(&device, &info).unwrap();
+        let pool = device.create_descriptor_pool(&DescriptorPoolInfo {
+            max_sets: 1,
+            layouts: &[&texture_layout],
+            ..Default::default()
+        });
+
+        let extent = vk::Extent3D {
+            width: 1,
+            height: 1,
+            depth: 1,
+        };
+        let texture = device
+            .create_view_image(&ViewImageDesc {
+                image: ImageDesc {
+                    format: vk::Format::R8G8B8A8_UNORM,
+                    extent,
+                    usage: ImageUses::COPY_DST | ImageUses::SAMPLED,
+                    ..Default::default()
+                },
+                sampler: Some(SamplerCreateInfo::default()),
+                aspect: Some(vk::ImageAspectFlags::COLOR),
+                ..Default::default()
+            })
+            .unwrap();
+        let staging = device
+            .create_buffer(&BufferDesc {
+                size: 4,
+                usage: BufferUses::COPY_SRC,
+                memory: MemoryPreset::Dynamic,
+                host_access: HostAccess::WriteSequential,
+                ..Default::default()
+            })
+            .unwrap();
+        staging.write(&[255; 4], 0);
+        device
+            .one_time(&queue, |cmd| {
+                cmd.image_transition(
+                    &texture.image,
+                    ImageTransition {
+                        from: ImageLayoutTransition::UNDEFINED,
+                        to: ImageLayoutTransition::new(ImageLayout::TransferDst),
+                        aspect: vk::ImageAspectFlags::COLOR,
+                        ..Default::default()
+                    },
+                );
+                cmd.copy_buffer_to_image(&CopyBufferToImageInfo {
+                    src: &staging,
+                    dst: &texture.image,
+                    dst_layout: ImageLayout::TransferDst,
+                    regions: &[vk::BufferImageCopy::default()
+                        .image_subresource(
+                            vk::ImageSubresourceLayers::default()
+                                .aspect_mask(vk::ImageAspectFlags::COLOR)
+                                .layer_count(1),
+                        )
+                        .image_extent(extent)],
+                });
+                cmd.image_transition(
+                    &texture.image,
+                    ImageTransition {
+                        from: ImageLayoutTransition::new(ImageLayout::TransferDst),
+                        to: ImageLayoutTransition::FRAGMENT,
+                        aspect: vk::ImageAspectFlags::COLOR,
+                        ..Default::default()
+                    },
+                );
+            })
+            .unwrap();
+
+        let set = pool.allocate_set(&texture_layout);
+        set.write(&[
+            DescriptorWrite::SampledImage {
+                binding: 0,
+                image_view: &texture.view,
+                image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                array_element: None,
+            },
+            DescriptorWrite::Sampler {
+                binding: 1,
+                sampler: texture.sampler.as_ref().unwrap(),
+                array_element: None,
+            },
+        ]);
+
+        // At two pixels per point, points 0.5..1.5 cover pixels 1..3 of the 4x4 target.
+        let vertex = |x: f32, y: f32| epaint::Vertex {
+            pos: egui_crate::pos2(x, y),
+            uv: egui_crate::pos2(0.0, 0.0),
+            color: egui_crate::Color32::WHITE,
+        };
+        let vertices = [
+            vertex(0.5, 0.5),
+            vertex(1.5, 0.5),
+            vertex(1.5, 1.5),
+            vertex(0.5, 1.5),
+        ];
+        let indices = [0u32, 1, 2, 0, 2, 3];
+        let host_buffer = |bytes: &[u8], usage| {
+            let buffer = device
+                .create_buffer(&BufferDesc {
+                    size: bytes.len(),
+                    usage,
+                    memory: MemoryPreset::Dynamic,
+                    host_access: HostAccess::WriteSequential,
+                    ..Default::default()
+                })
+                .unwrap();
+            buffer.write(bytes, 0);
+            buffer
+        };
+        let vertex_buffer = host_buffer(bytemuck::cast_slice(&vertices), BufferUses::VERTEX);
+        let index_buffer = host_buffer(bytemuck::cast_slice(&indices), BufferUses::INDEX);
+        let draws = [PreparedDraw {
+            set: &set,
+            clip_rect: egui_crate::Rect::from_min_max(
+                egui_crate::pos2(0.0, 0.0),
+                egui_crate::pos2(2.0, 2.0),
+            ),
+            index_range: 0..6,
+        }];
+
+        let target = vk::Extent2D {
+            width: 4,
+            height: 4,
+        };
+        let (pixels, row_pitch) =
+            test_render(&device, &queue, target, Region::from(target), |render| {
+                paint_render_pass(
+                    &pipeline,
+                    &vertex_buffer,
+                    &index_buffer,
+                    &draws,
+                    2.0,
+                    target,
+                    render,
+                );
+            });
+
+        for y in 0..4 {
+            for x in 0..4 {
+                let red = pixels[y * row_pitch as usize + x * 4];
+                let inside = (1..3).contains(&x) && (1..3).contains(&y);
+                assert_eq!(red, if inside { 255 } else { 0 }, "pixel ({x}, {y})");
+            }
+        }
+    }
 }
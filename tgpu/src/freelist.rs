@@ -4,11 +4,26 @@ pub struct FreeList<T> {
     inner: Mutex<FreeListInner<T>>,
 }
 
+/// Stable handle into a [`FreeList`].
+///
+/// The generation is bumped every time a slot is freed, so a key that outlives its value
+/// no longer matches once the slot is reused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FreeListKey {
+    pub index: usize,
+    pub generation: u32,
+}
+
 struct FreeListInner<T> {
-    data: Vec<Option<T>>,
+    data: Vec<Slot<T>>,
     free: VecDeque<usize>,
 }
 
+struct Slot<T> {
+    value: Option<T>,
+    generation: u32,
+}
+
 impl<T> FreeList<T> {
     pub fn new() -> Self {
         Self {
@@ -28,39 +43,107 @@ impl<T> FreeList<T> {
         }
     }
 
-    /// Insert a value, returning a stable index.
-    pub fn insert(&self, value: T) -> usize {
+    /// Insert a value, returning a stable key.
+    ///
+    /// Freed slots are reused in the order they were freed.
+    pub fn insert(&self, value: T) -> FreeListKey {
         let mut inner = self.inner.lock().unwrap();
 
         if let Some(idx) = inner.free.pop_front() {
-            inner.data[idx] = Some(value);
-            idx
+            let slot = &mut inner.data[idx];
+            slot.value = Some(value);
+            FreeListKey {
+                index: idx,
+                generation: slot.generation,
+            }
         } else {
             let idx = inner.data.len();
-            inner.data.push(Some(value));
-            idx
+            inner.data.push(Slot {
+                value: Some(value),
+                generation: 0,
+            });
+            FreeListKey {
+                index: idx,
+                generation: 0,
+            }
         }
     }
 
-    /// Remove value at `idx`, returning it if it was present.
-    pub fn remove(&self, idx: usize) -> Option<T> {
+    /// Remove the value behind `key`, returning it if the key is still live.
+    pub fn remove(&self, key: FreeListKey) -> Option<T> {
         let mut inner = self.inner.lock().unwrap();
 
-        if idx >= inner.data.len() {
+        let slot = inner.data.get_mut(key.index)?;
+        if slot.generation != key.generation {
             return None;
         }
 
-        if let Some(value) = inner.data[idx].take() {
-            inner.free.push_back(idx);
-            Some(value)
-        } else {
-            None
-        }
+        let value = slot.value.take()?;
+        slot.generation = slot.generation.wrapping_add(1);
+        inner.free.push_back(key.index);
+        Some(value)
+    }
+
+    /// Check if `key` still refers to a live value.
+    pub fn contains(&self, key: FreeListKey) -> bool {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .data
+            .get(key.index)
+            .is_some_and(|slot| slot.generation == key.generation && slot.value.is_some())
+    }
+
+    /// Number of occupied slots.
+    pub fn len(&self) -> usize {
+        let inner = self.inner.lock().unwrap();
+        inner.data.len() - inner.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
     }
 
-    /// Check if an index currently holds a value.
-    pub fn contains(&self, idx: usize) -> bool {
+    /// Number of slots handed out so far, occupied or free.
+    pub fn capacity(&self) -> usize {
         let inner = self.inner.lock().unwrap();
-        inner.data.get(idx).and_then(|slot| slot.as_ref()).is_some()
+        inner.data.len()
+    }
+
+    /// Iterate occupied slots as `(index, &value)`.
+    pub fn iter(&mut self) -> impl Iterator<Item = (usize, &T)> {
+        let inner = self.inner.get_mut().unwrap();
+        inner
+            .data
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, slot)| slot.value.as_ref().map(|value| (idx, value)))
+    }
+
+    /// Visit occupied slots while holding the lock, for shared access.
+    pub fn for_each(&self, mut f: impl FnMut(usize, &T)) {
+        let inner = self.inner.lock().unwrap();
+        for (idx, slot) in inner.data.iter().enumerate() {
+            if let Some(value) = &slot.value {
+                f(idx, value);
+            }
+        }
+    }
+
+    /// Remove all values, invalidating every outstanding key.
+    pub fn clear(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        let FreeListInner { data, free } = &mut *inner;
+        for (idx, slot) in data.iter_mut().enumerate() {
+            if slot.value.take().is_some() {
+                slot.generation = slot.generation.wrapping_add(1);
+                free.push_back(idx);
+            }
+        }
+    }
+}
+
+impl<T> Default for FreeList<T> {
+    fn default() -> Self {
+        Self::new()
     }
 }
@@ -1,10 +1,20 @@
-use std::{fmt, ops, sync::Arc};
+use std::{
+    collections::HashMap,
+    fmt, ops,
+    sync::{Arc, Weak},
+};
 
 use ash::vk;
 use vkm::Alloc;
 
 use crate::{
-    Allocation, Buffer, Device, GPUError, HostAccess, Label, MemoryPreset, Queue, raw::RawDevice,
+    AliasedMemory, Allocation, Buffer, BufferDesc, BufferUses, Device, ExternalHandle, GPUError,
+    HostAccess, Label, MemoryPool, MemoryPreset, Queue,
+    allocations::{
+        EXTERNAL_MEMORY_HANDLE_TYPE, ExternalMemory, ExternalResource, create_external_pool,
+        export_memory_handle, release_imported,
+    },
+    raw::RawDevice,
 };
 
 // TODO: support custom stuff
@@ -26,6 +36,7 @@ bitflags::bitflags! {
         const SPARSE_ALIASED = 1 << 12;
         const MUTABLE_FORMAT = 1 << 13;
         const CUBE = 1 << 14;
+        const EXTERNAL = 1 << 15;
         const DEVICE = 1 << 16;
         const HOST = 1 << 17;
         const LAZY = 1 << 18;
@@ -155,6 +166,9 @@ bitflags::bitflags! {
         const DEPTH_STENCIL_ATTACHMENT = 1 << 5;
         const INPUT_ATTACHMENT = 1 << 6;
         const TRANSIENT_ATTACHMENT = 1 << 7;
+        /// Memory can be shared with other APIs or processes; see `Image::export_memory_handle`
+        /// and `Device::import_external_image`.
+        const EXTERNAL = 1 << 8;
     }
 }
 
@@ -185,7 +199,16 @@ pub struct ImageDesc<'a> {
     pub memory: MemoryPreset,
     pub host_access: HostAccess,
     pub sharing: vk::SharingMode,
+    /// Queue families that use the image without ownership transfers.
+    ///
+    /// When non-empty the image is created with `CONCURRENT` sharing over these families,
+    /// overriding `sharing`. Duplicates are removed; a single remaining family stays exclusive.
+    pub queue_families: &'a [u32],
+    /// Only `Undefined` or `Custom(PREINITIALIZED)` are valid; use `image_transition` to reach
+    /// any other layout after creation.
     pub initial_layout: ImageLayout,
+    /// Allocate from this pool instead of the device allocator; `memory` must match it.
+    pub pool: Option<&'a MemoryPool>,
     pub label: Option<Label<'a>>,
 }
 
@@ -204,7 +227,9 @@ impl Default for ImageDesc<'_> {
             memory: MemoryPreset::GpuOnly,
             host_access: HostAccess::None,
             sharing: vk::SharingMode::EXCLUSIVE,
+            queue_families: &[],
             initial_layout: ImageLayout::Undefined,
+            pool: None,
             label: None,
         }
     }
@@ -214,6 +239,8 @@ impl Default for ImageDesc<'_> {
 pub struct ViewImageDesc<'a> {
     pub image: ImageDesc<'a>,
     pub sampler: Option<SamplerCreateInfo<'a>>,
+    /// Attaches an existing sampler instead of creating one; exclusive with `sampler`.
+    pub shared_sampler: Option<&'a Sampler>,
     pub view_type: Option<vk::ImageViewType>,
     pub view_format: Option<vk::Format>,
     pub aspect: Option<vk::ImageAspectFlags>,
@@ -228,6 +255,7 @@ impl Default for ViewImageDesc<'_> {
         Self {
             image: ImageDesc::default(),
             sampler: None,
+            shared_sampler: None,
             view_type: None,
             view_format: None,
             aspect: None,
@@ -246,6 +274,8 @@ pub struct Texture2DDesc<'a> {
     pub usage: TextureUses,
     pub memory: MemoryPreset,
     pub sampler: Option<SamplerCreateInfo<'a>>,
+    /// Attaches an existing sampler instead of creating one; exclusive with `sampler`.
+    pub shared_sampler: Option<&'a Sampler>,
     pub mip_levels: u32,
     pub array_layers: u32,
     pub samples: vk::SampleCountFlags,
@@ -263,6 +293,7 @@ impl Default for Texture2DDesc<'_> {
             usage: TextureUses::empty(),
             memory: MemoryPreset::GpuOnly,
             sampler: None,
+            shared_sampler: None,
             mip_levels: 1,
             array_layers: 1,
             samples: vk::SampleCountFlags::TYPE_1,
@@ -301,6 +332,9 @@ impl From<TextureUses> for ImageUsage {
         if usage.contains(TextureUses::TRANSIENT_ATTACHMENT) {
             raw |= ImageUsage::TRANSIENT;
         }
+        if usage.contains(TextureUses::EXTERNAL) {
+            raw |= ImageUsage::EXTERNAL;
+        }
         raw
     }
 }
@@ -347,8 +381,14 @@ fn validate_texture_2d_desc(desc: &Texture2DDesc<'_>) -> Result<(), GPUError> {
         ));
     }
 
+    if desc.sampler.is_some() && desc.shared_sampler.is_some() {
+        return Err(GPUError::Validation(
+            "2D texture must not set both sampler and shared_sampler",
+        ));
+    }
+
     let mut usage = desc.usage;
-    if desc.sampler.is_some() {
+    if desc.sampler.is_some() || desc.shared_sampler.is_some() {
         usage |= TextureUses::SAMPLED;
     }
     if usage.is_empty() {
@@ -400,6 +440,12 @@ fn validate_texture_2d_desc(desc: &Texture2DDesc<'_>) -> Result<(), GPUError> {
     Ok(())
 }
 
+/// Length of the full mip chain for `extent`.
+pub fn max_mip_levels(extent: vk::Extent3D) -> u32 {
+    let max = extent.width.max(extent.height).max(extent.depth).max(1);
+    u32::BITS - max.leading_zeros()
+}
+
 fn validate_image_desc(desc: &ImageDesc<'_>) -> Result<(), GPUError> {
     if desc.format == vk::Format::UNDEFINED {
         return Err(GPUError::Validation("image format must be defined"));
@@ -414,6 +460,11 @@ fn validate_image_desc(desc: &ImageDesc<'_>) -> Result<(), GPUError> {
             "image mip_levels must be greater than zero",
         ));
     }
+    if desc.mip_levels > max_mip_levels(desc.extent) {
+        return Err(GPUError::Validation(
+            "image mip_levels must not exceed floor(log2(max extent)) + 1",
+        ));
+    }
     if desc.array_layers == 0 {
         return Err(GPUError::Validation(
             "image array_layers must be greater than zero",
@@ -422,6 +473,11 @@ fn validate_image_desc(desc: &ImageDesc<'_>) -> Result<(), GPUError> {
     if desc.usage.is_empty() {
         return Err(GPUError::Validation("image usage must not be empty"));
     }
+    if desc.sharing == vk::SharingMode::CONCURRENT && desc.queue_families.is_empty() {
+        return Err(GPUError::Validation(
+            "CONCURRENT image sharing requires queue_families",
+        ));
+    }
 
     match desc.ty {
         vk::ImageType::TYPE_1D => {
@@ -493,9 +549,9 @@ fn validate_image_desc(desc: &ImageDesc<'_>) -> Result<(), GPUError> {
                 "CUBE images require square width and height",
             ));
         }
-        if desc.array_layers < 6 {
+        if desc.array_layers < 6 || !desc.array_layers.is_multiple_of(6) {
             return Err(GPUError::Validation(
-                "CUBE images require at least 6 array layers",
+                "CUBE images require a non-zero multiple of 6 array layers",
             ));
         }
     }
@@ -529,12 +585,84 @@ fn validate_image_desc(desc: &ImageDesc<'_>) -> Result<(), GPUError> {
         ));
     }
 
+    if !matches!(
+        vk::ImageLayout::from(desc.initial_layout),
+        vk::ImageLayout::UNDEFINED | vk::ImageLayout::PREINITIALIZED
+    ) {
+        return Err(GPUError::Validation(
+            "initial_layout must be Undefined or PREINITIALIZED; transition after creation instead",
+        ));
+    }
+
+    Ok(())
+}
+
+fn validate_format_features(
+    usage: ImageUses,
+    features: vk::FormatFeatureFlags,
+) -> Result<(), GPUError> {
+    let required = [
+        (
+            ImageUses::COPY_SRC,
+            vk::FormatFeatureFlags::TRANSFER_SRC,
+            "image format does not support COPY_SRC usage with the requested tiling",
+        ),
+        (
+            ImageUses::COPY_DST,
+            vk::FormatFeatureFlags::TRANSFER_DST,
+            "image format does not support COPY_DST usage with the requested tiling",
+        ),
+        (
+            ImageUses::SAMPLED,
+            vk::FormatFeatureFlags::SAMPLED_IMAGE,
+            "image format does not support SAMPLED usage with the requested tiling",
+        ),
+        (
+            ImageUses::STORAGE,
+            vk::FormatFeatureFlags::STORAGE_IMAGE,
+            "image format does not support STORAGE usage with the requested tiling",
+        ),
+        (
+            ImageUses::COLOR_ATTACHMENT,
+            vk::FormatFeatureFlags::COLOR_ATTACHMENT,
+            "image format does not support COLOR_ATTACHMENT usage with the requested tiling",
+        ),
+        (
+            ImageUses::DEPTH_STENCIL_ATTACHMENT,
+            vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT,
+            "image format does not support DEPTH_STENCIL_ATTACHMENT usage with the requested tiling",
+        ),
+    ];
+
+    for (uses, feature, message) in required {
+        if usage.contains(uses) && !features.contains(feature) {
+            return Err(GPUError::Validation(message));
+        }
+    }
+
+    if usage.contains(ImageUses::INPUT_ATTACHMENT)
+        && !features.intersects(
+            vk::FormatFeatureFlags::COLOR_ATTACHMENT
+                | vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT,
+        )
+    {
+        return Err(GPUError::Validation(
+            "image format does not support INPUT_ATTACHMENT usage with the requested tiling",
+        ));
+    }
+
     Ok(())
 }
 
 fn validate_view_image_desc(desc: &ViewImageDesc<'_>) -> Result<(), GPUError> {
     validate_image_desc(&desc.image)?;
 
+    if desc.sampler.is_some() && desc.shared_sampler.is_some() {
+        return Err(GPUError::Validation(
+            "view image must not set both sampler and shared_sampler",
+        ));
+    }
+
     if let Some(mips) = &desc.view_mips {
         if mips.start >= mips.end || mips.end > desc.image.mip_levels {
             return Err(GPUError::Validation(
@@ -559,6 +687,11 @@ fn infer_image_aspect(format: vk::Format, usage: ImageUses) -> vk::ImageAspectFl
         return depth_stencil_aspect(format);
     }
 
+    format_aspect(format)
+}
+
+/// Depth and/or stencil for depth-stencil formats, COLOR for everything else.
+pub(crate) fn format_aspect(format: vk::Format) -> vk::ImageAspectFlags {
     let depth_stencil = depth_stencil_aspect(format);
     if !depth_stencil.is_empty() {
         return depth_stencil;
@@ -600,6 +733,150 @@ fn depth_stencil_aspect(format: vk::Format) -> vk::ImageAspectFlags {
     }
 }
 
+fn validate_view_format(image: &Image, format: vk::Format) -> Result<(), GPUError> {
+    if format == image.format {
+        return Ok(());
+    }
+    if !image
+        .inner
+        .flags
+        .contains(vk::ImageCreateFlags::MUTABLE_FORMAT)
+    {
+        return Err(GPUError::Validation(
+            "viewing an image with a different format requires ImageFlags::MUTABLE_FORMAT",
+        ));
+    }
+    if !depth_stencil_aspect(format).is_empty() || !depth_stencil_aspect(image.format).is_empty() {
+        return Err(GPUError::Validation(
+            "depth/stencil formats cannot be reinterpreted",
+        ));
+    }
+    // formats missing from the table (e.g. block-compressed) are left to the driver
+    if let (Some(view), Some(image)) = (texel_size(format), texel_size(image.format))
+        && view != image
+    {
+        return Err(GPUError::Validation(
+            "reinterpreted view format must have the same texel size as the image format",
+        ));
+    }
+    Ok(())
+}
+
+pub(crate) fn validate_blit(info: &BlitImageInfo<'_>) -> Result<(), GPUError> {
+    if !info.src.inner.usage.contains(ImageUsage::COPY_SRC) {
+        return Err(GPUError::Validation(
+            "blit source image requires COPY_SRC usage",
+        ));
+    }
+    if !info.dst.inner.usage.contains(ImageUsage::COPY_DST) {
+        return Err(GPUError::Validation(
+            "blit destination image requires COPY_DST usage",
+        ));
+    }
+
+    let features = |image: &Image| {
+        let properties = image.inner.device.adapter.format_properties(image.format);
+        properties.optimal_tiling_features | properties.linear_tiling_features
+    };
+    let src_features = features(info.src);
+    if !src_features.contains(vk::FormatFeatureFlags::BLIT_SRC) {
+        return Err(GPUError::Validation(
+            "blit source format does not support BLIT_SRC",
+        ));
+    }
+    if !features(info.dst).contains(vk::FormatFeatureFlags::BLIT_DST) {
+        return Err(GPUError::Validation(
+            "blit destination format does not support BLIT_DST",
+        ));
+    }
+    if info.filter == vk::Filter::LINEAR
+        && !src_features.contains(vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR)
+    {
+        return Err(GPUError::Validation(
+            "linear blits require SAMPLED_IMAGE_FILTER_LINEAR on the source format",
+        ));
+    }
+    Ok(())
+}
+
+fn texel_size(format: vk::Format) -> Option<u32> {
+    let size = match format {
+        vk::Format::R8_UNORM
+        | vk::Format::R8_SNORM
+        | vk::Format::R8_UINT
+        | vk::Format::R8_SINT
+        | vk::Format::R8_SRGB => 1,
+        vk::Format::R8G8_UNORM
+        | vk::Format::R8G8_SNORM
+        | vk::Format::R8G8_UINT
+        | vk::Format::R8G8_SINT
+        | vk::Format::R8G8_SRGB
+        | vk::Format::R16_UNORM
+        | vk::Format::R16_SNORM
+        | vk::Format::R16_UINT
+        | vk::Format::R16_SINT
+        | vk::Format::R16_SFLOAT => 2,
+        vk::Format::R8G8B8A8_UNORM
+        | vk::Format::R8G8B8A8_SNORM
+        | vk::Format::R8G8B8A8_UINT
+        | vk::Format::R8G8B8A8_SINT
+        | vk::Format::R8G8B8A8_SRGB
+        | vk::Format::B8G8R8A8_UNORM
+        | vk::Format::B8G8R8A8_SNORM
+        | vk::Format::B8G8R8A8_UINT
+        | vk::Format::B8G8R8A8_SINT
+        | vk::Format::B8G8R8A8_SRGB
+        | vk::Format::A2B10G10R10_UNORM_PACK32
+        | vk::Format::A2B10G10R10_UINT_PACK32
+        | vk::Format::B10G11R11_UFLOAT_PACK32
+        | vk::Format::R16G16_UNORM
+        | vk::Format::R16G16_SNORM
+        | vk::Format::R16G16_UINT
+        | vk::Format::R16G16_SINT
+        | vk::Format::R16G16_SFLOAT
+        | vk::Format::R32_UINT
+        | vk::Format::R32_SINT
+        | vk::Format::R32_SFLOAT => 4,
+        vk::Format::R16G16B16A16_UNORM
+        | vk::Format::R16G16B16A16_SNORM
+        | vk::Format::R16G16B16A16_UINT
+        | vk::Format::R16G16B16A16_SINT
+        | vk::Format::R16G16B16A16_SFLOAT
+        | vk::Format::R32G32_UINT
+        | vk::Format::R32G32_SINT
+        | vk::Format::R32G32_SFLOAT => 8,
+        vk::Format::R32G32B32A32_UINT
+        | vk::Format::R32G32B32A32_SINT
+        | vk::Format::R32G32B32A32_SFLOAT => 16,
+        _ => return None,
+    };
+    Some(size)
+}
+
+/// Bytes per texel of `aspect` in the buffer layout of image-to-buffer copies.
+fn copy_texel_size(format: vk::Format, aspect: vk::ImageAspectFlags) -> Option<u32> {
+    match aspect {
+        vk::ImageAspectFlags::COLOR => texel_size(format),
+        vk::ImageAspectFlags::DEPTH => match format {
+            vk::Format::D16_UNORM | vk::Format::D16_UNORM_S8_UINT => Some(2),
+            vk::Format::X8_D24_UNORM_PACK32
+            | vk::Format::D24_UNORM_S8_UINT
+            | vk::Format::D32_SFLOAT
+            | vk::Format::D32_SFLOAT_S8_UINT => Some(4),
+            _ => None,
+        },
+        vk::ImageAspectFlags::STENCIL => matches!(
+            format,
+            vk::Format::S8_UINT
+                | vk::Format::D16_UNORM_S8_UINT
+                | vk::Format::D24_UNORM_S8_UINT
+                | vk::Format::D32_SFLOAT_S8_UINT
+        )
+        .then_some(1),
+        _ => None,
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Image {
     pub inner: Arc<ImageImpl>,
@@ -610,15 +887,21 @@ pub struct ImageImpl {
     pub handle: vk::Image,
     pub device: RawDevice,
     pub allocation: Option<Allocation>,
+    pub extent: vk::Extent3D,
+    pub mip_levels: u32,
+    pub layers: u32,
+    pub flags: vk::ImageCreateFlags,
+    pub(crate) usage: ImageUsage,
+    pub aliased: Option<Arc<AliasedMemory>>,
 }
 
 #[derive(Debug, Clone)]
 pub struct ImageView {
-    pub inner: ImageViewImpl,
+    pub inner: Arc<ImageViewImpl>,
     pub sampler: Option<Sampler>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct ImageViewImpl {
     pub handle: vk::ImageView,
     pub device: RawDevice,
@@ -641,6 +924,10 @@ pub struct ViewImage {
     pub image: Image,
     pub sampler: Option<Sampler>,
     pub view: ImageView,
+    /// Creation parameters without borrowed fields, reused by `resize`.
+    pub(crate) desc: Box<ViewImageDesc<'static>>,
+    pub(crate) queue_families: Box<[u32]>,
+    pub(crate) name: Option<String>,
 }
 
 // TODO: detach from vulkan
@@ -677,6 +964,35 @@ impl<'a> Default for SamplerCreateInfo<'a> {
     }
 }
 
+/// `SamplerCreateInfo` without the label; floats are compared by their bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SamplerCacheKey {
+    filters: [vk::Filter; 2],
+    mipmap: vk::SamplerMipmapMode,
+    address: [vk::SamplerAddressMode; 3],
+    anisotropy: Option<u32>,
+    compare: Option<vk::CompareOp>,
+    lod: [u32; 2],
+}
+
+impl SamplerCacheKey {
+    fn new(info: &SamplerCreateInfo<'_>) -> Self {
+        // Adding zero folds -0.0 into 0.0 so both map to the same sampler.
+        let bits = |value: f32| (value + 0.0).to_bits();
+        Self {
+            filters: [info.mag, info.min],
+            mipmap: info.mipmap,
+            address: [info.address_u, info.address_v, info.address_w],
+            anisotropy: info.anisotropy.map(bits),
+            compare: info.compare,
+            lod: [bits(info.min_lod), bits(info.max_lod)],
+        }
+    }
+}
+
+/// Samplers are held weakly, so a cached sampler lives only as long as some `Sampler` uses it.
+pub type SamplerCache = HashMap<SamplerCacheKey, Weak<SamplerImpl>>;
+
 #[derive(Debug, Clone, Default)]
 pub struct ImageViewOptions<'a> {
     pub sampler: Option<&'a Sampler>,
@@ -689,6 +1005,38 @@ pub struct ImageViewOptions<'a> {
     pub label: Option<Label<'a>>,
 }
 
+impl ImageViewOptions<'_> {
+    /// Replicates `channel` into RGB with alpha forced to one.
+    pub fn grayscale(mut self, channel: vk::ComponentSwizzle) -> Self {
+        self.swizzle = vk::ComponentMapping {
+            r: channel,
+            g: channel,
+            b: channel,
+            a: vk::ComponentSwizzle::ONE,
+        };
+        self
+    }
+
+    /// Views only the depth aspect of a depth/stencil image, shown as grayscale when sampled.
+    pub fn depth(mut self) -> Self {
+        self.aspect = vk::ImageAspectFlags::DEPTH;
+        self.grayscale(vk::ComponentSwizzle::R)
+    }
+
+    /// Views only the stencil aspect of a depth/stencil image.
+    pub fn stencil(mut self) -> Self {
+        self.aspect = vk::ImageAspectFlags::STENCIL;
+        self
+    }
+
+    /// Views the image as `format`, which must have the same texel size as the image format.
+    /// The image must be created with `ImageFlags::MUTABLE_FORMAT`.
+    pub fn reinterpret_format(mut self, format: vk::Format) -> Self {
+        self.format = Some(format);
+        self
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ImageViewCreateInfo<'a> {
     pub image: &'a Image,
@@ -707,7 +1055,9 @@ pub(crate) struct ImageCreateInfo<'a> {
     pub usage: ImageUsage,
     pub flags: vk::ImageCreateFlags,
     pub sharing: vk::SharingMode,
+    pub queue_families: Vec<u32>,
     pub layout: ImageLayout,
+    pub pool: Option<MemoryPool>,
     pub label: Option<Label<'a>>,
 }
 
@@ -728,6 +1078,14 @@ pub struct CopyBufferToImageInfo<'a> {
     pub regions: &'a [vk::BufferImageCopy],
 }
 
+#[derive(Debug, Copy, Clone)]
+pub struct CopyImageToBufferInfo<'a> {
+    pub src: &'a Image,
+    pub src_layout: ImageLayout,
+    pub dst: &'a Buffer,
+    pub regions: &'a [vk::BufferImageCopy],
+}
+
 #[derive(Debug, Copy, Clone)]
 pub struct BlitImageInfo<'a> {
     pub src: &'a Image,
@@ -783,6 +1141,7 @@ impl ImageViewImpl {
             );
 
         if let Some(format) = options.format {
+            validate_view_format(info.image, format)?;
             create_info.format = format;
         }
 
@@ -806,7 +1165,145 @@ impl ImageImpl {
         info: &ImageCreateInfo<'_>,
         create_info: vkm::AllocationCreateInfo,
     ) -> Result<Self, GPUError> {
-        let image_info = vk::ImageCreateInfo::default()
+        let mut external_info =
+            vk::ExternalMemoryImageCreateInfo::default().handle_types(EXTERNAL_MEMORY_HANDLE_TYPE);
+        let mut image_info: vk::ImageCreateInfo<'_> = Self::raw_info(info);
+        let mut create_info = create_info;
+        if info.usage.contains(ImageUsage::EXTERNAL) {
+            image_info = image_info.push_next(&mut external_info);
+            create_info.flags |= vkm::AllocationCreateFlags::DEDICATED_MEMORY;
+        }
+
+        // sparse images get their memory through `Queue::bind_sparse` instead
+        let sparse = image_info
+            .flags
+            .contains(vk::ImageCreateFlags::SPARSE_BINDING);
+        let (handle, allocation) = if sparse {
+            let handle = unsafe { device.handle.create_image(&image_info, None) }?;
+            (handle, None)
+        } else {
+            let (handle, allocation) = match &info.pool {
+                Some(pool) => unsafe { pool.inner.handle.create_image(&image_info, &create_info) }?,
+                None => unsafe { device.allocator.create_image(&image_info, &create_info) }?,
+            };
+            let allocation = Allocation {
+                handle: allocation,
+                allocator: Arc::clone(&device.allocator),
+                pool: info.pool.clone(),
+            };
+            (handle, Some(allocation))
+        };
+
+        if let Some(label) = &info.label {
+            unsafe { device.attach_label(handle, label) };
+        }
+
+        Ok(Self {
+            handle,
+            device,
+            allocation,
+            extent: info.volume,
+            mip_levels: info.mips,
+            layers: info.layers,
+            flags: image_info.flags,
+            usage: info.usage,
+            aliased: None,
+        })
+    }
+
+    pub(crate) unsafe fn new_aliased(
+        device: RawDevice,
+        infos: &[ImageCreateInfo<'_>],
+        create_info: vkm::AllocationCreateInfo,
+    ) -> Result<Vec<Self>, GPUError> {
+        let mut handles = Vec::with_capacity(infos.len());
+        let mut requirements = vk::MemoryRequirements {
+            memory_type_bits: u32::MAX,
+            ..Default::default()
+        };
+
+        for info in infos {
+            let handle = match unsafe { device.handle.create_image(&Self::raw_info(info), None) } {
+                Ok(handle) => handle,
+                Err(err) => {
+                    for &handle in &handles {
+                        unsafe { device.handle.destroy_image(handle, None) };
+                    }
+                    return Err(err.into());
+                }
+            };
+            let image_requirements = unsafe { device.handle.get_image_memory_requirements(handle) };
+            requirements.size = requirements.size.max(image_requirements.size);
+            requirements.alignment = requirements.alignment.max(image_requirements.alignment);
+            requirements.memory_type_bits &= image_requirements.memory_type_bits;
+            handles.push(handle);
+        }
+
+        let allocation = if requirements.memory_type_bits == 0 {
+            Err(GPUError::Validation(
+                "aliased images have no memory type in common",
+            ))
+        } else {
+            unsafe {
+                device
+                    .allocator
+                    .allocate_memory(&requirements, &create_info)
+            }
+            .map_err(GPUError::from)
+        };
+        let allocation = match allocation {
+            Ok(allocation) => allocation,
+            Err(err) => {
+                for &handle in &handles {
+                    unsafe { device.handle.destroy_image(handle, None) };
+                }
+                return Err(err);
+            }
+        };
+
+        let memory = Arc::new(AliasedMemory {
+            allocation,
+            device: device.clone(),
+        });
+
+        for &handle in &handles {
+            let bound = unsafe {
+                device
+                    .allocator
+                    .bind_image_memory(&memory.allocation, handle)
+            };
+            if let Err(err) = bound {
+                for &handle in &handles {
+                    unsafe { device.handle.destroy_image(handle, None) };
+                }
+                return Err(err.into());
+            }
+        }
+
+        Ok(handles
+            .into_iter()
+            .zip(infos)
+            .map(|(handle, info)| {
+                if let Some(label) = &info.label {
+                    unsafe { device.attach_label(handle, label) };
+                }
+                Self {
+                    handle,
+                    device: device.clone(),
+                    allocation: None,
+                    extent: info.volume,
+                    mip_levels: info.mips,
+                    layers: info.layers,
+                    flags: info.flags | vk::ImageCreateFlags::from(info.usage),
+                    usage: info.usage,
+                    aliased: Some(memory.clone()),
+                }
+            })
+            .collect())
+    }
+
+    fn raw_info<'a>(info: &'a ImageCreateInfo<'_>) -> vk::ImageCreateInfo<'a> {
+        vk::ImageCreateInfo::default()
             .image_type(info.ty)
             .format(info.format)
             .extent(info.volume)
@@ -816,29 +1313,291 @@ impl ImageImpl {
             .tiling(info.tiling)
             .usage(info.usage.into())
             .sharing_mode(info.sharing)
+            .queue_family_indices(&info.queue_families)
             .initial_layout(info.layout.into())
-            .flags(info.flags | vk::ImageCreateFlags::from(info.usage));
-
-        let (handle, allocation) =
-            unsafe { device.allocator.create_image(&image_info, &create_info) }?;
+            .flags(info.flags | vk::ImageCreateFlags::from(info.usage))
+    }
+}
 
-        let allocation = Some(Allocation {
-            handle: allocation,
-            allocator: device.allocator.clone(),
+impl Image {
+    /// Exports the image's memory as an OS handle, e.g. for CUDA or another process.
+    ///
+    /// Only images created with `ImageUses::EXTERNAL` can be exported. The importer has to
+    /// create its image with the same format, extent, usage and tiling.
+    pub fn export_memory_handle(&self) -> Result<ExternalHandle, GPUError> {
+        let allocation = self.inner.allocation.as_ref().filter(|allocation| {
+            allocation.pool.as_ref().is_some_and(|pool| {
+                matches!(
+                    pool.inner.external.as_deref(),
+                    Some(ExternalMemory::Export(_))
+                )
+            })
         });
-
-        if let Some(label) = &info.label {
-            unsafe { device.attach_label(handle, label) };
+        let Some(allocation) = allocation else {
+            return Err(GPUError::Validation(
+                "only images created with ImageUses::EXTERNAL can export their memory",
+            ));
+        };
+        let memory = allocation
+            .allocator
+            .get_allocation_info(&allocation.handle)
+            .device_memory;
+        unsafe { export_memory_handle(&self.inner.device, memory) }
+    }
+
+    /// Copies mip 0, layer 0 of `aspect` to the host and blocks until it arrives.
+    ///
+    /// `current` is the layout the image is in, which it is returned to afterwards. Returns the
+    /// tightly packed bytes and the row pitch in bytes. The bytes are exactly as stored: sRGB
+    /// values are not decoded and BGRA formats stay BGRA, so check `format()` before encoding.
+    /// Depth aspects of 24-bit formats occupy 4 bytes per texel with the top 8 bits undefined.
+    pub fn read_back(
+        &self,
+        queue: &Queue,
+        current: ImageLayoutTransition,
+        aspect: vk::ImageAspectFlags,
+    ) -> Result<(Vec<u8>, u32), GPUError> {
+        if !self.inner.usage.contains(ImageUsage::COPY_SRC) {
+            return Err(GPUError::Validation("read_back requires COPY_SRC usage"));
+        }
+        if matches!(current.layout, ImageLayout::Undefined) {
+            return Err(GPUError::Validation(
+                "read_back needs the image's current layout; UNDEFINED discards the contents",
+            ));
         }
+        let texel = copy_texel_size(self.format, aspect).ok_or(GPUError::Validation(
+            "read_back doesn't support this format and aspect",
+        ))?;
+
+        let extent = self.inner.extent;
+        let row_pitch = extent.width * texel;
+        let size = row_pitch as usize * extent.height as usize * extent.depth as usize;
+
+        let device = Device::from_raw(self.inner.device.clone());
+        let readback = device.create_buffer(&BufferDesc {
+            label: Some(Label::Name("image readback")),
+            size,
+            usage: BufferUses::COPY_DST,
+            memory: MemoryPreset::Readback,
+            host_access: HostAccess::ReadRandom,
+            ..Default::default()
+        })?;
 
-        Ok(Self {
-            handle,
-            device,
-            allocation,
+        let transfer = ImageLayoutTransition::custom(
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            vk::PipelineStageFlags2::TRANSFER,
+            vk::AccessFlags2::TRANSFER_READ,
+        );
+        let region = vk::BufferImageCopy::default()
+            .image_subresource(
+                vk::ImageSubresourceLayers::default()
+                    .aspect_mask(aspect)
+                    .layer_count(1),
+            )
+            .image_extent(extent);
+
+        device.one_time(queue, |recorder| {
+            recorder.image_transition(
+                self,
+                ImageTransition {
+                    from: current,
+                    to: transfer,
+                    aspect,
+                    ..Default::default()
+                },
+            );
+            recorder.copy_image_to_buffer(&CopyImageToBufferInfo {
+                src: self,
+                src_layout: transfer.layout,
+                dst: &readback,
+                regions: &[region],
+            });
+            recorder.image_transition(
+                self,
+                ImageTransition {
+                    from: transfer,
+                    to: current,
+                    aspect,
+                    ..Default::default()
+                },
+            );
+        })?;
+
+        let mut bytes = vec![0; size];
+        readback.read(&mut bytes, 0, size);
+        Ok((bytes, row_pitch))
+    }
+
+    /// Names the image for debuggers such as RenderDoc.
+    pub fn set_name(&self, name: &str) {
+        unsafe { self.inner.device.set_object_name(self.inner.handle, name) };
+    }
+
+    pub fn format(&self) -> vk::Format {
+        self.format
+    }
+
+    pub fn extent(&self) -> vk::Extent3D {
+        self.inner.extent
+    }
+
+    pub fn mip_levels(&self) -> u32 {
+        self.inner.mip_levels
+    }
+
+    pub fn layers(&self) -> u32 {
+        self.inner.layers
+    }
+
+    pub fn usage(&self) -> vk::ImageUsageFlags {
+        self.inner.usage.into()
+    }
+
+    /// Views only the depth aspect of all mips and layers, e.g. for sampling a packed
+    /// depth/stencil shadow map with a compare sampler.
+    pub fn depth_view(&self) -> Result<ImageView, GPUError> {
+        self.aspect_view(
+            vk::ImageAspectFlags::DEPTH,
+            "depth_view requires a format with a depth aspect",
+        )
+    }
+
+    /// Views only the stencil aspect of all mips and layers; sampled as an unsigned integer.
+    pub fn stencil_view(&self) -> Result<ImageView, GPUError> {
+        self.aspect_view(
+            vk::ImageAspectFlags::STENCIL,
+            "stencil_view requires a format with a stencil aspect",
+        )
+    }
+
+    fn aspect_view(
+        &self,
+        aspect: vk::ImageAspectFlags,
+        message: &'static str,
+    ) -> Result<ImageView, GPUError> {
+        if !format_aspect(self.format).contains(aspect) {
+            return Err(GPUError::Validation(message));
+        }
+        let ty = if self.inner.layers > 1 {
+            vk::ImageViewType::TYPE_2D_ARRAY
+        } else {
+            vk::ImageViewType::TYPE_2D
+        };
+        let info = ImageViewCreateInfo {
+            image: self,
+            options: ImageViewOptions {
+                ty,
+                aspect,
+                mips: 0..self.inner.mip_levels,
+                layers: 0..self.inner.layers,
+                ..Default::default()
+            },
+        };
+        let inner = unsafe { ImageViewImpl::new(self.inner.device.clone(), &info)? };
+        Ok(ImageView {
+            inner: Arc::new(inner),
+            sampler: None,
         })
     }
 }
 
+impl ViewImage {
+    /// Recreates the image and view at `extent` with the same format, usage and view settings,
+    /// keeping the sampler.
+    ///
+    /// Returns the previous resources, which must be kept alive until in-flight frames using them
+    /// have finished. Mip levels are clamped to what the new extent allows, and images from a
+    /// `MemoryPool` are reallocated from the device allocator.
+    pub fn resize(&mut self, device: &Device, extent: vk::Extent3D) -> Result<ViewImage, GPUError> {
+        let mut desc = (*self.desc).clone();
+        desc.image.extent = extent;
+        desc.image.queue_families = &self.queue_families;
+        desc.image.mip_levels = desc.image.mip_levels.min(max_mip_levels(extent));
+        if let Some(mips) = &mut desc.view_mips {
+            mips.end = mips.end.min(desc.image.mip_levels);
+            mips.start = mips.start.min(mips.end);
+        }
+
+        let mut resized = device.create_view_image(&ViewImageDesc {
+            shared_sampler: self.sampler.as_ref(),
+            ..desc
+        })?;
+        if let Some(name) = &self.name {
+            resized.image.set_name(name);
+        }
+        resized.name = self.name.clone();
+        Ok(std::mem::replace(self, resized))
+    }
+}
+
+impl ImageView {
+    /// Names the view for debuggers such as RenderDoc.
+    pub fn set_name(&self, name: &str) {
+        unsafe { self.inner.device.set_object_name(self.inner.handle, name) };
+    }
+}
+
+fn owned_view_image_desc(desc: &ViewImageDesc<'_>) -> ViewImageDesc<'static> {
+    let image = &desc.image;
+    ViewImageDesc {
+        image: ImageDesc {
+            format: image.format,
+            ty: image.ty,
+            extent: image.extent,
+            mip_levels: image.mip_levels,
+            array_layers: image.array_layers,
+            tiling: image.tiling,
+            samples: image.samples,
+            usage: image.usage,
+            flags: image.flags,
+            memory: image.memory,
+            host_access: image.host_access,
+            sharing: image.sharing,
+            queue_families: &[],
+            initial_layout: image.initial_layout,
+            pool: None,
+            label: None,
+        },
+        sampler: None,
+        shared_sampler: None,
+        view_type: desc.view_type,
+        view_format: desc.view_format,
+        aspect: desc.aspect,
+        swizzle: desc.swizzle,
+        view_mips: desc.view_mips.clone(),
+        view_layers: desc.view_layers.clone(),
+        view_label: None,
+    }
+}
+
+fn image_create_info<'a>(desc: &ImageDesc<'a>) -> ImageCreateInfo<'a> {
+    let mut queue_families = desc.queue_families.to_vec();
+    queue_families.sort_unstable();
+    queue_families.dedup();
+    let (sharing, queue_families) = match queue_families.len() {
+        0 => (desc.sharing, queue_families),
+        1 => (vk::SharingMode::EXCLUSIVE, Vec::new()),
+        _ => (vk::SharingMode::CONCURRENT, queue_families),
+    };
+
+    ImageCreateInfo {
+        format: desc.format,
+        ty: desc.ty,
+        volume: desc.extent,
+        mips: desc.mip_levels,
+        layers: desc.array_layers,
+        tiling: desc.tiling,
+        samples: desc.samples,
+        usage: ImageUsage::from(desc.usage),
+        flags: desc.flags.into(),
+        sharing,
+        queue_families,
+        layout: desc.initial_layout,
+        pool: desc.pool.cloned(),
+        label: desc.label.clone(),
+    }
+}
+
 fn allocation_create_info(
     memory: MemoryPreset,
     host_access: HostAccess,
@@ -847,6 +1606,7 @@ fn allocation_create_info(
         MemoryPreset::GpuOnly => vkm::MemoryUsage::AutoPreferDevice,
         MemoryPreset::Upload | MemoryPreset::Readback => vkm::MemoryUsage::AutoPreferHost,
         MemoryPreset::Dynamic => vkm::MemoryUsage::AutoPreferDevice,
+        MemoryPreset::DeviceUpload => vkm::MemoryUsage::Auto,
         MemoryPreset::TransientAttachment => vkm::MemoryUsage::GpuLazy,
     };
 
@@ -864,6 +1624,12 @@ fn allocation_create_info(
         }
     }
 
+    if memory == MemoryPreset::DeviceUpload {
+        flags |= vkm::AllocationCreateFlags::HOST_ACCESS_SEQUENTIAL_WRITE
+            | vkm::AllocationCreateFlags::HOST_ACCESS_ALLOW_TRANSFER_INSTEAD;
+        preferred_flags |= vk::MemoryPropertyFlags::DEVICE_LOCAL;
+    }
+
     vkm::AllocationCreateInfo {
         usage,
         flags,
@@ -873,11 +1639,26 @@ fn allocation_create_info(
 }
 
 impl Device {
+    /// Returns the live sampler created from an identical `info` if there is one, in which case
+    /// `info.label` is ignored.
     pub fn try_create_sampler(&self, info: &SamplerCreateInfo<'_>) -> Result<Sampler, GPUError> {
-        let inner = unsafe { SamplerImpl::new(self.inner.clone(), info)? };
-        Ok(Sampler {
-            inner: Arc::new(inner),
-        })
+        let key = SamplerCacheKey::new(info);
+        let mut cache = self.inner.sampler_cache.lock();
+        if let Some(inner) = cache.get(&key).and_then(Weak::upgrade) {
+            return Ok(Sampler { inner });
+        }
+
+        let inner = Arc::new(unsafe { SamplerImpl::new(self.inner.clone(), info)? });
+        cache.retain(|_, sampler| sampler.strong_count() > 0);
+        cache.insert(key, Arc::downgrade(&inner));
+        Ok(Sampler { inner })
+    }
+
+    /// Number of distinct live samplers, to watch against `maxSamplerAllocationCount`.
+    pub fn sampler_count(&self) -> usize {
+        let mut cache = self.inner.sampler_cache.lock();
+        cache.retain(|_, sampler| sampler.strong_count() > 0);
+        cache.len()
     }
 
     pub fn create_sampler(&self, info: &SamplerCreateInfo<'_>) -> Sampler {
@@ -890,7 +1671,10 @@ impl Device {
     ) -> Result<ImageView, GPUError> {
         let inner = unsafe { ImageViewImpl::new(self.inner.clone(), info)? };
         let sampler = info.options.sampler.cloned();
-        Ok(ImageView { inner, sampler })
+        Ok(ImageView {
+            inner: Arc::new(inner),
+            sampler,
+        })
     }
 
     pub fn create_image_view(&self, info: &ImageViewCreateInfo<'_>) -> ImageView {
@@ -898,30 +1682,55 @@ impl Device {
     }
 
     pub fn create_image(&self, desc: &ImageDesc<'_>) -> Result<Image, GPUError> {
-        validate_image_desc(desc)?;
-
-        let info = ImageCreateInfo {
-            format: desc.format,
-            ty: desc.ty,
-            volume: desc.extent,
-            mips: desc.mip_levels,
-            layers: desc.array_layers,
-            tiling: desc.tiling,
-            samples: desc.samples,
-            usage: ImageUsage::from(desc.usage),
-            flags: desc.flags.into(),
-            sharing: desc.sharing,
-            layout: desc.initial_layout,
-            label: desc.label.clone(),
+        let external = desc
+            .usage
+            .contains(ImageUses::EXTERNAL)
+            .then(ExternalMemory::export);
+        self.create_image_in(desc, external)
+    }
+
+    /// Creates an image backed by memory exported from another API or process.
+    ///
+    /// `handle` is an opaque fd on unix or an NT handle on windows, exported by the same driver
+    /// for an image with the same format, extent, usage and tiling as `desc`. On success an fd
+    /// belongs to the driver and a windows handle is closed; on failure the handle is closed.
+    pub fn import_external_image(
+        &self,
+        handle: ExternalHandle,
+        desc: &ImageDesc<'_>,
+    ) -> Result<Image, GPUError> {
+        let desc = ImageDesc {
+            usage: desc.usage | ImageUses::EXTERNAL,
+            ..desc.clone()
         };
+        let image = self.create_image_in(&desc, Some(ExternalMemory::import(&handle)))?;
+        release_imported(handle);
+        Ok(image)
+    }
 
-        let inner = unsafe {
-            ImageImpl::new_with_allocation(
-                self.inner.clone(),
-                &info,
-                allocation_create_info(desc.memory, desc.host_access),
-            )?
-        };
+    fn create_image_in(
+        &self,
+        desc: &ImageDesc<'_>,
+        external: Option<ExternalMemory>,
+    ) -> Result<Image, GPUError> {
+        self.validate_image(desc)?;
+
+        let mut info = image_create_info(desc);
+        let create_info = allocation_create_info(desc.memory, desc.host_access);
+        if let Some(external) = external {
+            let mut external_info = vk::ExternalMemoryImageCreateInfo::default()
+                .handle_types(EXTERNAL_MEMORY_HANDLE_TYPE);
+            let image_info = ImageImpl::raw_info(&info).push_next(&mut external_info);
+            info.pool = Some(create_external_pool(
+                &self.inner,
+                desc.memory,
+                &create_info,
+                ExternalResource::Image(&image_info),
+                external,
+            )?);
+        }
+        let inner =
+            unsafe { ImageImpl::new_with_allocation(self.inner.clone(), &info, create_info)? };
 
         Ok(Image {
             inner: Arc::new(inner),
@@ -929,6 +1738,102 @@ impl Device {
         })
     }
 
+    /// Creates images that share a single memory allocation.
+    ///
+    /// The allocation is sized and aligned for the largest image, and every image is bound
+    /// at offset zero. All descriptors must use the same `memory` and `host_access`.
+    ///
+    /// # Safety
+    /// The images overlap in memory. Only one of them may hold meaningful contents at a time:
+    /// the caller must finish all GPU work on one image (with a barrier) before using another,
+    /// and must treat an image's contents as undefined after any other image was written,
+    /// transitioning it from `ImageLayout::Undefined` before reuse.
+    pub unsafe fn create_aliased_images(
+        &self,
+        descs: &[ImageDesc<'_>],
+    ) -> Result<Vec<Image>, GPUError> {
+        let Some(first) = descs.first() else {
+            return Ok(Vec::new());
+        };
+        for desc in descs {
+            self.validate_image(desc)?;
+            if desc.memory != first.memory || desc.host_access != first.host_access {
+                return Err(GPUError::Validation(
+                    "aliased images must share the same memory preset and host access",
+                ));
+            }
+            if desc.pool.is_some() {
+                return Err(GPUError::Validation(
+                    "aliased images cannot be allocated from a memory pool",
+                ));
+            }
+            if desc.flags.contains(ImageFlags::SPARSE_BINDING) {
+                return Err(GPUError::Validation("aliased images cannot be sparse"));
+            }
+            if desc.usage.contains(ImageUses::EXTERNAL) {
+                return Err(GPUError::Validation("aliased images cannot be external"));
+            }
+        }
+
+        let infos = descs.iter().map(image_create_info).collect::<Vec<_>>();
+        let inners = unsafe {
+            ImageImpl::new_aliased(
+                self.inner.clone(),
+                &infos,
+                allocation_create_info(first.memory, first.host_access),
+            )?
+        };
+
+        Ok(inners
+            .into_iter()
+            .zip(&infos)
+            .map(|(inner, info)| Image {
+                inner: Arc::new(inner),
+                format: info.format,
+            })
+            .collect())
+    }
+
+    fn validate_image(&self, desc: &ImageDesc<'_>) -> Result<(), GPUError> {
+        validate_image_desc(desc)?;
+
+        if let Some(pool) = desc.pool
+            && pool.memory() != desc.memory
+        {
+            return Err(GPUError::Validation(
+                "image memory preset must match the memory pool it is allocated from",
+            ));
+        }
+
+        if desc.usage.contains(ImageUses::EXTERNAL)
+            && (desc.pool.is_some() || desc.flags.contains(ImageFlags::SPARSE_BINDING))
+        {
+            return Err(GPUError::Validation(
+                "ImageUses::EXTERNAL images allocate their own memory and cannot be pooled or sparse",
+            ));
+        }
+
+        if desc.flags.contains(ImageFlags::SPARSE_BINDING) && !self.inner.features.sparse_binding {
+            return Err(GPUError::Validation(
+                "ImageFlags::SPARSE_BINDING requires sparse_binding to be enabled on the device",
+            ));
+        }
+        if desc.flags.contains(ImageFlags::SPARSE_RESIDENCY)
+            && !self.inner.features.sparse_residency
+        {
+            return Err(GPUError::Validation(
+                "ImageFlags::SPARSE_RESIDENCY requires sparse_residency to be enabled on the device",
+            ));
+        }
+
+        let properties = self.inner.adapter.format_properties(desc.format);
+        let features = match desc.tiling {
+            vk::ImageTiling::LINEAR => properties.linear_tiling_features,
+            _ => properties.optimal_tiling_features,
+        };
+        validate_format_features(desc.usage, features)
+    }
+
     pub fn create_image_with(&self, desc: &ImageDesc<'_>) -> Result<Image, GPUError> {
         self.create_image(desc)
     }
@@ -941,7 +1846,7 @@ impl Device {
         let sampler = if let Some(sampler_desc) = desc.sampler.as_ref() {
             Some(self.try_create_sampler(sampler_desc)?)
         } else {
-            None
+            desc.shared_sampler.cloned()
         };
 
         let view = self.try_create_image_view(&ImageViewCreateInfo {
@@ -969,6 +1874,12 @@ impl Device {
             image,
             sampler,
             view,
+            desc: Box::new(owned_view_image_desc(desc)),
+            queue_families: desc.image.queue_families.into(),
+            name: match &desc.image.label {
+                Some(Label::Name(name) | Label::Both((name, _))) => Some(name.to_string()),
+                _ => None,
+            },
         })
     }
 
@@ -981,7 +1892,10 @@ impl Device {
 
         if matches!(
             desc.memory,
-            MemoryPreset::Upload | MemoryPreset::Readback | MemoryPreset::Dynamic
+            MemoryPreset::Upload
+                | MemoryPreset::Readback
+                | MemoryPreset::Dynamic
+                | MemoryPreset::DeviceUpload
         ) {
             return Err(GPUError::Validation(
                 "Texture2DDesc only supports GpuOnly or TransientAttachment memory; use ImageDesc for explicit image allocation",
@@ -989,7 +1903,7 @@ impl Device {
         }
 
         let mut usage = desc.usage;
-        if desc.sampler.is_some() {
+        if desc.sampler.is_some() || desc.shared_sampler.is_some() {
             usage |= TextureUses::SAMPLED;
         }
 
@@ -1012,6 +1926,7 @@ impl Device {
                 ..Default::default()
             },
             sampler: desc.sampler.clone(),
+            shared_sampler: desc.shared_sampler,
             view_type: None,
             view_format: desc.view_format,
             aspect: Some(infer_image_aspect(desc.format, usage)),
@@ -1033,6 +1948,12 @@ pub enum ImageLayout {
     Color,
     TransferDst,
     Present,
+    /// Depth/stencil attachment that is tested and written.
+    DepthStencil,
+    /// Depth/stencil tested without writes, and readable from shaders, e.g. a shadow map.
+    DepthStencilReadOnly,
+    /// Read-only depth with a writable stencil attachment.
+    DepthReadOnly,
     Custom(vk::ImageLayout),
 }
 
@@ -1053,6 +1974,7 @@ impl Default for ImageLayout {
 pub struct ImageTransition<'a> {
     pub from: ImageLayoutTransition,
     pub to: ImageLayoutTransition,
+    /// Left empty, the aspect is inferred from the image's format.
     pub aspect: vk::ImageAspectFlags,
     pub mips: ops::Range<u32>,
     pub layers: ops::Range<u32>,
@@ -1081,6 +2003,9 @@ impl ImageLayoutTransition {
     pub const COMPUTE: Self = Self::new(ImageLayout::Compute);
     pub const PRESENT: Self = Self::new(ImageLayout::Present);
     pub const COLOR: Self = Self::new(ImageLayout::Color);
+    pub const DEPTH_STENCIL: Self = Self::new(ImageLayout::DepthStencil);
+    pub const DEPTH_STENCIL_READ_ONLY: Self = Self::new(ImageLayout::DepthStencilReadOnly);
+    pub const DEPTH_READ_ONLY: Self = Self::new(ImageLayout::DepthReadOnly);
 
     pub const fn new(layout: ImageLayout) -> Self {
         let (stage, access) = layout.infer_stage_flags();
@@ -1143,6 +2068,39 @@ impl ImageLayout {
                 vk::PipelineStageFlags2::BOTTOM_OF_PIPE,
                 vk::AccessFlags2::empty(),
             ),
+            ImageLayout::DepthStencil => (
+                vk::PipelineStageFlags2::from_raw(
+                    vk::PipelineStageFlags2::EARLY_FRAGMENT_TESTS.as_raw()
+                        | vk::PipelineStageFlags2::LATE_FRAGMENT_TESTS.as_raw(),
+                ),
+                vk::AccessFlags2::from_raw(
+                    vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_READ.as_raw()
+                        | vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE.as_raw(),
+                ),
+            ),
+            ImageLayout::DepthStencilReadOnly => (
+                vk::PipelineStageFlags2::from_raw(
+                    vk::PipelineStageFlags2::EARLY_FRAGMENT_TESTS.as_raw()
+                        | vk::PipelineStageFlags2::LATE_FRAGMENT_TESTS.as_raw()
+                        | vk::PipelineStageFlags2::FRAGMENT_SHADER.as_raw(),
+                ),
+                vk::AccessFlags2::from_raw(
+                    vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_READ.as_raw()
+                        | vk::AccessFlags2::SHADER_READ.as_raw(),
+                ),
+            ),
+            ImageLayout::DepthReadOnly => (
+                vk::PipelineStageFlags2::from_raw(
+                    vk::PipelineStageFlags2::EARLY_FRAGMENT_TESTS.as_raw()
+                        | vk::PipelineStageFlags2::LATE_FRAGMENT_TESTS.as_raw()
+                        | vk::PipelineStageFlags2::FRAGMENT_SHADER.as_raw(),
+                ),
+                vk::AccessFlags2::from_raw(
+                    vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_READ.as_raw()
+                        | vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE.as_raw()
+                        | vk::AccessFlags2::SHADER_READ.as_raw(),
+                ),
+            ),
             Self::Custom(_) => panic!("Custom cannot infer"),
         }
     }
@@ -1159,6 +2117,11 @@ impl From<ImageLayout> for vk::ImageLayout {
             ImageLayout::Color => vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
             ImageLayout::TransferDst => vk::ImageLayout::TRANSFER_DST_OPTIMAL,
             ImageLayout::Present => vk::ImageLayout::PRESENT_SRC_KHR,
+            ImageLayout::DepthStencil => vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+            ImageLayout::DepthStencilReadOnly => vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+            ImageLayout::DepthReadOnly => {
+                vk::ImageLayout::DEPTH_READ_ONLY_STENCIL_ATTACHMENT_OPTIMAL
+            }
             ImageLayout::Custom(layout) => layout,
         }
     }
@@ -1185,6 +2148,10 @@ impl Drop for ImageImpl {
                 allocation
                     .allocator
                     .destroy_image(self.handle, &mut allocation.handle);
+            } else if self.aliased.is_some()
+                || self.flags.contains(vk::ImageCreateFlags::SPARSE_BINDING)
+            {
+                self.device.handle.destroy_image(self.handle, None);
             }
         }
     }
@@ -1197,3 +2164,307 @@ impl Drop for ImageViewImpl {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn desc(extent: [u32; 3], mip_levels: u32) -> ImageDesc<'static> {
+        ImageDesc {
+            format: vk::Format::R8G8B8A8_UNORM,
+            ty: if extent[2] > 1 {
+                vk::ImageType::TYPE_3D
+            } else {
+                vk::ImageType::TYPE_2D
+            },
+            extent: vk::Extent3D {
+                width: extent[0],
+                height: extent[1],
+                depth: extent[2],
+            },
+            mip_levels,
+            usage: ImageUses::SAMPLED,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn mip_levels_are_bounded_by_extent() {
+        assert_eq!(max_mip_levels(desc([1, 1, 1], 1).extent), 1);
+        assert_eq!(max_mip_levels(desc([256, 64, 1], 1).extent), 9);
+        assert_eq!(max_mip_levels(desc([5, 3, 17], 1).extent), 5);
+
+        assert!(validate_image_desc(&desc([256, 64, 1], 9)).is_ok());
+        assert!(validate_image_desc(&desc([256, 64, 1], 10)).is_err());
+        assert!(validate_image_desc(&desc([256, 64, 1], 0)).is_err());
+        assert!(validate_image_desc(&desc([0, 64, 1], 1)).is_err());
+    }
+
+    #[test]
+    fn cube_layers_must_be_multiple_of_six() {
+        let cube = |array_layers| ImageDesc {
+            array_layers,
+            flags: ImageFlags::CUBE,
+            ..desc([64, 64, 1], 1)
+        };
+
+        assert!(validate_image_desc(&cube(6)).is_ok());
+        assert!(validate_image_desc(&cube(12)).is_ok());
+        assert!(validate_image_desc(&cube(8)).is_err());
+        assert!(validate_image_desc(&cube(0)).is_err());
+    }
+
+    #[test]
+    fn initial_layout_must_be_undefined_or_preinitialized() {
+        let with_layout = |initial_layout| ImageDesc {
+            initial_layout,
+            ..desc([64, 64, 1], 1)
+        };
+
+        assert!(validate_image_desc(&with_layout(ImageLayout::Undefined)).is_ok());
+        assert!(
+            validate_image_desc(&with_layout(ImageLayout::Custom(
+                vk::ImageLayout::PREINITIALIZED
+            )))
+            .is_ok()
+        );
+        assert!(validate_image_desc(&with_layout(ImageLayout::Color)).is_err());
+    }
+
+    #[test]
+    fn queue_families_select_concurrent_sharing() {
+        let shared = |queue_families| ImageDesc {
+            queue_families,
+            ..desc([64, 64, 1], 1)
+        };
+
+        let info = image_create_info(&shared(&[2, 0, 2]));
+        assert_eq!(info.sharing, vk::SharingMode::CONCURRENT);
+        assert_eq!(info.queue_families, [0, 2]);
+
+        let info = image_create_info(&shared(&[1, 1]));
+        assert_eq!(info.sharing, vk::SharingMode::EXCLUSIVE);
+        assert!(info.queue_families.is_empty());
+
+        let concurrent = ImageDesc {
+            sharing: vk::SharingMode::CONCURRENT,
+            ..desc([64, 64, 1], 1)
+        };
+        assert!(validate_image_desc(&concurrent).is_err());
+    }
+
+    #[test]
+    fn aspect_follows_format() {
+        assert_eq!(
+            format_aspect(vk::Format::B8G8R8A8_SRGB),
+            vk::ImageAspectFlags::COLOR
+        );
+        assert_eq!(
+            format_aspect(vk::Format::D32_SFLOAT),
+            vk::ImageAspectFlags::DEPTH
+        );
+        assert_eq!(
+            format_aspect(vk::Format::D24_UNORM_S8_UINT),
+            vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL
+        );
+    }
+
+    #[test]
+    fn readback_texel_size_follows_aspect() {
+        let size = copy_texel_size;
+        assert_eq!(
+            size(vk::Format::B8G8R8A8_SRGB, vk::ImageAspectFlags::COLOR),
+            Some(4)
+        );
+        assert_eq!(
+            size(vk::Format::D24_UNORM_S8_UINT, vk::ImageAspectFlags::DEPTH),
+            Some(4)
+        );
+        assert_eq!(
+            size(vk::Format::D24_UNORM_S8_UINT, vk::ImageAspectFlags::STENCIL),
+            Some(1)
+        );
+        assert_eq!(
+            size(vk::Format::D32_SFLOAT, vk::ImageAspectFlags::STENCIL),
+            None
+        );
+    }
+
+    #[test]
+    fn sampler_keys_ignore_label_and_zero_sign() {
+        let base = SamplerCreateInfo::default();
+        let labeled = SamplerCreateInfo {
+            label: Some(Label::Name("labeled")),
+            min_lod: -0.0,
+            ..Default::default()
+        };
+        assert_eq!(SamplerCacheKey::new(&base), SamplerCacheKey::new(&labeled));
+
+        let anisotropic = SamplerCreateInfo {
+            anisotropy: Some(16.0),
+            ..Default::default()
+        };
+        assert_ne!(
+            SamplerCacheKey::new(&base),
+            SamplerCacheKey::new(&anisotropic)
+        );
+    }
+
+    #[test]
+    fn depth_layouts_use_fragment_test_stages() {
+        let attachment = ImageLayoutTransition::DEPTH_STENCIL;
+        assert_eq!(
+            vk::ImageLayout::from(attachment.layout),
+            vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL
+        );
+        assert!(
+            attachment
+                .stage
+                .contains(vk::PipelineStageFlags2::LATE_FRAGMENT_TESTS)
+        );
+        assert!(
+            attachment
+                .access
+                .contains(vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE)
+        );
+
+        let read_only = ImageLayoutTransition::DEPTH_STENCIL_READ_ONLY;
+        assert!(
+            !read_only
+                .access
+                .contains(vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE)
+        );
+    }
+
+    #[test]
+    #[ignore = "requires a Vulkan device"]
+    fn copy_image_region_round_trips() {
+        use crate::{BufferDesc, BufferUses, HostAccess, QueueFlags, device::test_device};
+
+        let (device, queue) = test_device(QueueFlags::TRANSFER);
+
+        let image_desc = ImageDesc {
+            usage: ImageUses::COPY_SRC | ImageUses::COPY_DST,
+            ..desc([4, 4, 1], 1)
+        };
+        let src = device.create_image(&image_desc).unwrap();
+        let dst = device.create_image(&image_desc).unwrap();
+
+        let pixels = (0..64).collect::<Vec<u8>>();
+        let upload = device
+            .create_buffer_init(
+                &queue,
+                &BufferDesc {
+                    size: pixels.len(),
+                    usage: BufferUses::COPY_SRC,
+                    ..Default::default()
+                },
+                &pixels,
+            )
+            .unwrap();
+        let readback = device
+            .create_buffer(&BufferDesc {
+                size: pixels.len(),
+                usage: BufferUses::COPY_DST,
+                memory: MemoryPreset::Readback,
+                host_access: HostAccess::ReadRandom,
+                ..Default::default()
+            })
+            .unwrap();
+
+        let subresource = vk::ImageSubresourceLayers::default()
+            .aspect_mask(vk::ImageAspectFlags::COLOR)
+            .layer_count(1);
+        let whole = vk::BufferImageCopy::default()
+            .image_subresource(subresource)
+            .image_extent(image_desc.extent);
+        let general = ImageTransition {
+            from: ImageLayoutTransition::UNDEFINED,
+            to: ImageLayoutTransition::custom(
+                vk::ImageLayout::GENERAL,
+                vk::PipelineStageFlags2::TRANSFER,
+                vk::AccessFlags2::TRANSFER_WRITE,
+            ),
+            ..Default::default()
+        };
+        let transfer = ImageTransition {
+            from: ImageLayoutTransition::custom(
+                vk::ImageLayout::GENERAL,
+                vk::PipelineStageFlags2::TRANSFER,
+                vk::AccessFlags2::TRANSFER_WRITE,
+            ),
+            to: ImageLayoutTransition::custom(
+                vk::ImageLayout::GENERAL,
+                vk::PipelineStageFlags2::TRANSFER,
+                vk::AccessFlags2::TRANSFER_READ,
+            ),
+            ..Default::default()
+        };
+
+        device
+            .one_time(&queue, |recorder| {
+                recorder.image_transition(&src, general.clone());
+                recorder.image_transition(&dst, general.clone());
+                recorder.copy_buffer_to_image(&CopyBufferToImageInfo {
+                    src: &upload,
+                    dst: &src,
+                    dst_layout: ImageLayout::General,
+                    regions: &[whole],
+                });
+                recorder.image_transition(&src, transfer.clone());
+                recorder.copy_image(&CopyImageInfo {
+                    src: &src,
+                    src_layout: ImageLayout::General,
+                    dst: &dst,
+                    dst_layout: ImageLayout::General,
+                    regions: &[vk::ImageCopy::default()
+                        .src_subresource(subresource)
+                        .dst_subresource(subresource)
+                        .extent(image_desc.extent)],
+                });
+                recorder.image_transition(&dst, transfer.clone());
+                recorder.copy_image_to_buffer(&CopyImageToBufferInfo {
+                    src: &dst,
+                    src_layout: ImageLayout::General,
+                    dst: &readback,
+                    regions: &[whole],
+                });
+            })
+            .unwrap();
+
+        let mut read = vec![0u8; pixels.len()];
+        readback.read(&mut read, 0, pixels.len());
+        assert_eq!(read, pixels);
+    }
+
+    #[test]
+    #[ignore = "requires a Vulkan device"]
+    fn external_images_round_trip() {
+        use crate::{QueueFlags, device::test_device_with};
+
+        let (device, _queue) = test_device_with(QueueFlags::COMPUTE, |adapter| {
+            let mut info = crate::DeviceCreateInfo::default();
+            info.features = info
+                .features
+                .external_memory(adapter.features().external_memory);
+            info
+        });
+        if !device.inner.features.external_memory {
+            return;
+        }
+
+        let image_desc = desc([64, 64, 1], 1);
+        let plain = device.create_image(&image_desc).unwrap();
+        assert!(plain.export_memory_handle().is_err());
+
+        let exported = device
+            .create_image(&ImageDesc {
+                usage: ImageUses::SAMPLED | ImageUses::EXTERNAL,
+                ..image_desc.clone()
+            })
+            .unwrap();
+        let handle = exported.export_memory_handle().unwrap();
+        let imported = device.import_external_image(handle, &image_desc).unwrap();
+        assert_eq!(imported.extent(), exported.extent());
+    }
+}
@@ -0,0 +1,663 @@
+//! Renderer for Dear ImGui draw data.
+//!
+//! This module does not depend on an ImGui binding. Convert the binding's draw data into
+//! `DrawData`; `DrawVert` matches `ImDrawVert`, so vertex slices can be cast with `bytemuck`.
+
+use std::collections::HashMap;
+
+use ash::vk;
+
+use crate::{
+    Buffer, BufferDesc, BufferUses, CopyBufferToImageInfo, DescriptorBinding, DescriptorPool,
+    DescriptorPoolInfo, DescriptorSet, DescriptorSetLayout, DescriptorSetLayoutInfo,
+    DescriptorType, DescriptorWrite, Device, GPUError, HostAccess, ImageDesc, ImageLayout,
+    ImageLayoutTransition, ImageTransition, ImageUses, Label, MemoryPreset, Queue, RenderPipeline,
+    RenderPipelineInfo, RenderRecorder, SamplerCreateInfo, ShaderSource, ShaderStageFlags,
+    Swapchain, ViewImage, ViewImageDesc, ui,
+};
+
+/// Layout of `ImDrawVert`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DrawVert {
+    pub pos: [f32; 2],
+    pub uv: [f32; 2],
+    pub col: [u8; 4],
+}
+
+/// Value for `ImTextureID`, returned by `Renderer::upload_font_atlas` and `register_texture`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TextureId(pub usize);
+
+/// One `ImDrawCmd`; user callbacks are not supported.
+#[derive(Debug, Clone, Copy)]
+pub struct DrawCmd {
+    /// `[min_x, min_y, max_x, max_y]` in display coordinates.
+    pub clip_rect: [f32; 4],
+    pub texture_id: TextureId,
+    pub vtx_offset: u32,
+    pub idx_offset: u32,
+    pub elem_count: u32,
+}
+
+/// One `ImDrawList`.
+#[derive(Debug, Clone, Copy)]
+pub struct DrawList<'a> {
+    pub vertices: &'a [DrawVert],
+    pub indices: &'a [u16],
+    pub commands: &'a [DrawCmd],
+}
+
+/// `ImDrawData` for one frame.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DrawData<'a> {
+    pub display_pos: [f32; 2],
+    pub display_size: [f32; 2],
+    pub framebuffer_scale: [f32; 2],
+    pub lists: &'a [DrawList<'a>],
+}
+
+#[derive(Debug, Clone)]
+pub struct RendererCreateInfo<'a> {
+    pub label: Option<Label<'a>>,
+    pub color_format: vk::Format,
+    pub max_textures: u32,
+    /// Number of vertex/index buffer pairs `render` cycles through with its `flight_index`.
+    pub max_frames_in_flight: usize,
+}
+
+impl Default for RendererCreateInfo<'_> {
+    fn default() -> Self {
+        Self {
+            label: Some(Label::Name("imgui")),
+            color_format: vk::Format::B8G8R8A8_SRGB,
+            max_textures: 256,
+            max_frames_in_flight: 3,
+        }
+    }
+}
+
+struct TextureBinding {
+    image: ViewImage,
+    descriptor_set: DescriptorSet,
+}
+
+#[derive(Default)]
+struct FrameBuffers {
+    vertices: Option<Buffer>,
+    indices: Option<Buffer>,
+}
+
+pub struct Renderer {
+    device: Device,
+    pipeline: RenderPipeline,
+    texture_layout: DescriptorSetLayout,
+    texture_pool: DescriptorPool,
+    textures: HashMap<TextureId, TextureBinding>,
+    /// Sets of unregistered textures, rewritten by the next `register_texture`.
+    free_sets: Vec<DescriptorSet>,
+    max_textures: u32,
+    frames: Vec<FrameBuffers>,
+    font_texture: Option<TextureId>,
+    next_texture_id: usize,
+}
+
+impl Renderer {
+    pub fn new(device: &Device, info: &RendererCreateInfo<'_>) -> Result<Self, GPUError> {
+        if info.max_textures == 0 {
+            return Err(GPUError::Validation(
+                "max_textures must be greater than zero",
+            ));
+        }
+        if info.max_frames_in_flight == 0 {
+            return Err(GPUError::Validation(
+                "max_frames_in_flight must be greater than zero",
+            ));
+        }
+
+        let texture_binding = [
+            DescriptorBinding::unique(0, DescriptorType::SampledImage, ShaderStageFlags::FRAGMENT),
+            DescriptorBinding::unique(1, DescriptorType::Sampler, ShaderStageFlags::FRAGMENT),
+        ];
+
+        let texture_layout = device.create_descriptor_set_layout(&DescriptorSetLayoutInfo {
+            bindings: &texture_binding,
+            label: info.label.clone(),
+            ..Default::default()
+        });
+
+        let texture_pool = device.create_descriptor_pool(&DescriptorPoolInfo {
+            max_sets: info.max_textures,
+            layouts: &[&texture_layout],
+            label: info.label.clone(),
+            ..Default::default()
+        });
+
+        let shader =
+            device.create_shader(info.label.clone(), ShaderSource::Wgsl(ui::SHADER_WGSL))?;
+
+        const _: () = assert!(size_of::<DrawVert>() == ui::VERTEX_STRIDE as usize);
+        let vertex_binding = [vk::VertexInputBindingDescription::default()
+            .binding(0)
+            .stride(ui::VERTEX_STRIDE)
+            .input_rate(vk::VertexInputRate::VERTEX)];
+        let vertex_input = vk::PipelineVertexInputStateCreateInfo::default()
+            .vertex_binding_descriptions(&vertex_binding)
+            .vertex_attribute_descriptions(&ui::VERTEX_ATTRIBUTES);
+
+        let blend_state = [vk::PipelineColorBlendAttachmentState::default()
+            .blend_enable(true)
+            .src_color_blend_factor(vk::BlendFactor::SRC_ALPHA)
+            .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+            .color_blend_op(vk::BlendOp::ADD)
+            .src_alpha_blend_factor(vk::BlendFactor::ONE)
+            .dst_alpha_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+            .alpha_blend_op(vk::BlendOp::ADD)
+            .color_write_mask(vk::ColorComponentFlags::RGBA)];
+
+        let pipeline = device.try_create_render_pipeline(&RenderPipelineInfo {
+            label: info.label.clone(),
+            vertex_shader: shader.entry("vs_main"),
+            fragment_shader: shader.entry(ui::fragment_entry(info.color_format)),
+            color_formats: &[info.color_format],
+            descriptor_layouts: &[&texture_layout],
+            push_constant_size: Some(size_of::<ui::PushConstants>() as u32),
+            blend_states: Some(&blend_state),
+            vertex_input_state: Some(vertex_input),
+            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+            polygon: vk::PolygonMode::FILL,
+            cull: vk::CullModeFlags::NONE,
+            front_face: vk::FrontFace::COUNTER_CLOCKWISE,
+            ..Default::default()
+        })?;
+
+        Ok(Self {
+            device: device.clone(),
+            pipeline,
+            texture_layout,
+            texture_pool,
+            textures: HashMap::new(),
+            free_sets: Vec::new(),
+            max_textures: info.max_textures,
+            frames: (0..info.max_frames_in_flight)
+                .map(|_| FrameBuffers::default())
+                .collect(),
+            font_texture: None,
+            next_texture_id: 1,
+        })
+    }
+
+    pub fn new_for_swapchain(device: &Device, swapchain: &Swapchain) -> Result<Self, GPUError> {
+        Self::new(
+            device,
+            &RendererCreateInfo {
+                color_format: swapchain.format(),
+                max_frames_in_flight: swapchain.max_frames_in_flight(),
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Uploads the font atlas as RGBA8 pixels, replacing the previous one.
+    ///
+    /// Set the returned id as the atlas' `TexID`.
+    pub fn upload_font_atlas(
+        &mut self,
+        queue: &Queue,
+        width: u32,
+        height: u32,
+        rgba: &[u8],
+    ) -> Result<TextureId, GPUError> {
+        if rgba.len() != width as usize * height as usize * 4 {
+            return Err(GPUError::Validation(
+                "font atlas pixels must be width * height RGBA8 texels",
+            ));
+        }
+
+        let extent = vk::Extent3D {
+            width,
+            height,
+            depth: 1,
+        };
+        let image = self.device.create_view_image(&ViewImageDesc {
+            image: ImageDesc {
+                label: Some(Label::Name("imgui font atlas")),
+                format: vk::Format::R8G8B8A8_UNORM,
+                extent,
+                usage: ImageUses::COPY_DST | ImageUses::SAMPLED,
+                ..Default::default()
+            },
+            sampler: Some(SamplerCreateInfo {
+                label: Some(Label::Name("imgui sampler")),
+                ..Default::default()
+            }),
+            aspect: Some(vk::ImageAspectFlags::COLOR),
+            ..Default::default()
+        })?;
+
+        let staging = self.device.create_buffer(&BufferDesc {
+            label: Some(Label::Name("imgui font upload")),
+            size: rgba.len(),
+            usage: BufferUses::COPY_SRC,
+            memory: MemoryPreset::Dynamic,
+            host_access: HostAccess::WriteSequential,
+            ..Default::default()
+        })?;
+        staging.write(rgba, 0);
+
+        let region = vk::BufferImageCopy::default()
+            .image_subresource(
+                vk::ImageSubresourceLayers::default()
+                    .aspect_mask(vk::ImageAspectFlags::COLOR)
+                    .layer_count(1),
+            )
+            .image_extent(extent);
+
+        self.device.one_time(queue, |cmd| {
+            cmd.image_transition(
+                &image.image,
+                ImageTransition {
+                    from: ImageLayoutTransition::UNDEFINED,
+                    to: ImageLayoutTransition::new(ImageLayout::TransferDst),
+                    aspect: vk::ImageAspectFlags::COLOR,
+                    ..Default::default()
+                },
+            );
+            cmd.copy_buffer_to_image(&CopyBufferToImageInfo {
+                src: &staging,
+                dst: &image.image,
+                dst_layout: ImageLayout::TransferDst,
+                regions: &[region],
+            });
+            cmd.image_transition(
+                &image.image,
+                ImageTransition {
+                    from: ImageLayoutTransition::new(ImageLayout::TransferDst),
+                    to: ImageLayoutTransition::FRAGMENT,
+                    aspect: vk::ImageAspectFlags::COLOR,
+                    ..Default::default()
+                },
+            );
+        })?;
+
+        if let Some(old) = self.font_texture.take() {
+            self.unregister_texture(old);
+        }
+        let id = self.register_texture(&image)?;
+        self.font_texture = Some(id);
+        Ok(id)
+    }
+
+    /// Makes `image` drawable through `ImGui::Image`; it must have a sampler and be in
+    /// `SHADER_READ_ONLY_OPTIMAL` when drawn.
+    ///
+    /// At most `max_textures` textures can be registered at once, the font atlas included.
+    pub fn register_texture(&mut self, image: &ViewImage) -> Result<TextureId, GPUError> {
+        let Some(sampler) = image.sampler.as_ref() else {
+            return Err(GPUError::Validation(
+                "imgui textures require a sampler-backed ViewImage",
+            ));
+        };
+
+        let descriptor_set = match self.free_sets.pop() {
+            Some(set) => set,
+            None if self.textures.len() >= self.max_textures as usize => {
+                return Err(GPUError::Validation(
+                    "imgui texture limit reached; unregister textures or raise max_textures",
+                ));
+            }
+            None => self.texture_pool.try_allocate_set(&self.texture_layout)?,
+        };
+        descriptor_set.write(&[
+            DescriptorWrite::SampledImage {
+                binding: 0,
+                image_view: &image.view,
+                image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                array_element: None,
+            },
+            DescriptorWrite::Sampler {
+                binding: 1,
+                sampler,
+                array_element: None,
+            },
+        ]);
+
+        let id = TextureId(self.next_texture_id);
+        self.next_texture_id += 1;
+        self.textures.insert(
+            id,
+            TextureBinding {
+                image: image.clone(),
+                descriptor_set,
+            },
+        );
+        Ok(id)
+    }
+
+    /// Waits for the device to go idle, since the texture's descriptor set is rewritten by the
+    /// next `register_texture`.
+    pub fn unregister_texture(&mut self, id: TextureId) {
+        if let Some(binding) = self.textures.remove(&id) {
+            self.device.wait_idle();
+            self.free_sets.push(binding.descriptor_set);
+        }
+    }
+
+    pub fn texture(&self, id: TextureId) -> Option<&ViewImage> {
+        self.textures.get(&id).map(|binding| &binding.image)
+    }
+
+    /// Records `draw_data` into an active `begin_render` scope covering `extent`.
+    ///
+    /// Vertices and indices are written into the buffers owned by `flight_index`, which grow
+    /// as needed, so the frame that last used `flight_index` must have finished on the GPU.
+    pub fn render(
+        &mut self,
+        render: &mut RenderRecorder<'_>,
+        draw_data: &DrawData<'_>,
+        extent: vk::Extent2D,
+        flight_index: usize,
+    ) -> Result<(), GPUError> {
+        if flight_index >= self.frames.len() {
+            return Err(GPUError::Validation(
+                "flight_index must be less than max_frames_in_flight",
+            ));
+        }
+
+        let vertex_count = draw_data
+            .lists
+            .iter()
+            .map(|list| list.vertices.len())
+            .sum::<usize>();
+        let index_count = draw_data
+            .lists
+            .iter()
+            .map(|list| list.indices.len())
+            .sum::<usize>();
+        if vertex_count == 0
+            || index_count == 0
+            || draw_data.display_size[0] <= 0.0
+            || draw_data.display_size[1] <= 0.0
+        {
+            return Ok(());
+        }
+
+        let frame = &mut self.frames[flight_index];
+        let vertex_buffer = reserve_buffer(
+            &self.device,
+            &mut frame.vertices,
+            "imgui vertices",
+            vertex_count * size_of::<DrawVert>(),
+            BufferUses::VERTEX,
+        )?;
+        let index_buffer = reserve_buffer(
+            &self.device,
+            &mut frame.indices,
+            "imgui indices",
+            index_count * size_of::<u16>(),
+            BufferUses::INDEX,
+        )?;
+
+        let mut vertex_offset = 0;
+        let mut index_offset = 0;
+        for list in draw_data.lists {
+            vertex_buffer.write(bytemuck::cast_slice(list.vertices), vertex_offset);
+            index_buffer.write(bytemuck::cast_slice(list.indices), index_offset);
+            vertex_offset += size_of_val(list.vertices);
+            index_offset += size_of_val(list.indices);
+        }
+
+        let scale = [
+            2.0 / draw_data.display_size[0],
+            2.0 / draw_data.display_size[1],
+        ];
+        let push_constants = ui::PushConstants {
+            scale,
+            translate: [
+                -1.0 - draw_data.display_pos[0] * scale[0],
+                -1.0 - draw_data.display_pos[1] * scale[1],
+            ],
+        };
+
+        render.bind_render_pipeline(&self.pipeline);
+        render.bind_vertex_buffer(0, vertex_buffer, 0);
+        render.bind_index_buffer(index_buffer, 0, vk::IndexType::UINT16);
+        render.viewport(vk::Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: extent.width as f32,
+            height: extent.height as f32,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        });
+        render.push_render_constants(&self.pipeline, push_constants);
+
+        let mut bound = None;
+        let mut list_vertex_offset = 0;
+        let mut list_index_offset = 0;
+        for list in draw_data.lists {
+            for command in list.commands {
+                if command.elem_count == 0 {
+                    continue;
+                }
+                let Some(scissor) = clip_rect_to_scissor(command.clip_rect, draw_data, extent)
+                else {
+                    continue;
+                };
+                let Some(texture) = self.textures.get(&command.texture_id) else {
+                    log::warn!(
+                        "Skipping imgui draw for unregistered texture {:?}",
+                        command.texture_id
+                    );
+                    continue;
+                };
+
+                if bound != Some(command.texture_id) {
+                    render.bind_render_descriptor_set(
+                        &texture.descriptor_set,
+                        &self.pipeline,
+                        0,
+                        &[],
+                    );
+                    bound = Some(command.texture_id);
+                }
+                render.scissor(scissor);
+
+                let first = list_index_offset + command.idx_offset;
+                render.draw_indexed(
+                    first..first + command.elem_count,
+                    (list_vertex_offset + command.vtx_offset) as i32,
+                    0..1,
+                );
+            }
+            list_vertex_offset += list.vertices.len() as u32;
+            list_index_offset += list.indices.len() as u32;
+        }
+
+        render.scissor(vk::Rect2D {
+            offset: vk::Offset2D { x: 0, y: 0 },
+            extent,
+        });
+        Ok(())
+    }
+}
+
+/// Reuses `slot` if it holds at least `size` bytes, otherwise replaces it with a buffer
+/// rounded up to the next power of two.
+fn reserve_buffer<'a>(
+    device: &Device,
+    slot: &'a mut Option<Buffer>,
+    label: &'static str,
+    size: usize,
+    usage: BufferUses,
+) -> Result<&'a Buffer, GPUError> {
+    if slot.as_ref().is_some_and(|buffer| buffer.size >= size) {
+        return Ok(slot.as_ref().unwrap());
+    }
+    let buffer = device.create_buffer(&BufferDesc {
+        label: Some(Label::Name(label)),
+        size: size.next_power_of_two(),
+        usage,
+        memory: MemoryPreset::Dynamic,
+        host_access: HostAccess::WriteSequential,
+        ..Default::default()
+    })?;
+    Ok(slot.insert(buffer))
+}
+
+/// Converts `clip_rect` from display coordinates to framebuffer pixels.
+fn clip_rect_to_scissor(
+    clip_rect: [f32; 4],
+    draw_data: &DrawData<'_>,
+    extent: vk::Extent2D,
+) -> Option<vk::Rect2D> {
+    let [scale_x, scale_y] = draw_data.framebuffer_scale;
+    let [pos_x, pos_y] = draw_data.display_pos;
+    let rect = [
+        (clip_rect[0] - pos_x) * scale_x,
+        (clip_rect[1] - pos_y) * scale_y,
+        (clip_rect[2] - pos_x) * scale_x,
+        (clip_rect[3] - pos_y) * scale_y,
+    ];
+    ui::clip_rect_to_scissor(rect, extent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clip_rects_scale_to_framebuffer_pixels() {
+        let draw_data = DrawData {
+            display_pos: [10.0, 20.0],
+            display_size: [640.0, 360.0],
+            framebuffer_scale: [2.0, 2.0],
+            lists: &[],
+        };
+        let extent = vk::Extent2D {
+            width: 1280,
+            height: 720,
+        };
+
+        let scissor = clip_rect_to_scissor([10.0, 20.0, 110.0, 70.0], &draw_data, extent).unwrap();
+        assert_eq!((scissor.offset.x, scissor.offset.y), (0, 0));
+        assert_eq!((scissor.extent.width, scissor.extent.height), (200, 100));
+
+        let outside = clip_rect_to_scissor([700.0, 20.0, 800.0, 70.0], &draw_data, extent);
+        assert!(outside.is_none());
+    }
+
+    #[test]
+    #[ignore = "requires a Vulkan device"]
+    fn unregistered_texture_sets_are_reused() {
+        use crate::{QueueFlags, device::test_device};
+
+        let (device, _queue) = test_device(QueueFlags::GRAPHICS);
+        let mut renderer = Renderer::new(
+            &device,
+            &RendererCreateInfo {
+                color_format: vk::Format::R8G8B8A8_UNORM,
+                max_textures: 2,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let image = device
+            .create_view_image(&ViewImageDesc {
+                image: ImageDesc {
+                    format: vk::Format::R8G8B8A8_UNORM,
+                    extent: vk::Extent3D {
+                        width: 1,
+                        height: 1,
+                        depth: 1,
+                    },
+                    usage: ImageUses::SAMPLED,
+                    ..Default::default()
+                },
+                sampler: Some(SamplerCreateInfo::default()),
+                aspect: Some(vk::ImageAspectFlags::COLOR),
+                ..Default::default()
+            })
+            .unwrap();
+
+        for _ in 0..8 {
+            let id = renderer.register_texture(&image).unwrap();
+            renderer.unregister_texture(id);
+        }
+
+        let first = renderer.register_texture(&image).unwrap();
+        renderer.register_texture(&image).unwrap();
+        assert!(matches!(
+            renderer.register_texture(&image),
+            Err(GPUError::Validation(_))
+        ));
+
+        renderer.unregister_texture(first);
+        renderer.register_texture(&image).unwrap();
+    }
+
+    #[test]
+    #[ignore = "requires a Vulkan device"]
+    fn renders_a_textured_quad() {
+        use crate::{QueueFlags, Region, command::test_render, device::test_device};
+
+        let (device, queue) = test_device(QueueFlags::GRAPHICS);
+        let mut renderer = Renderer::new(
+            &device,
+            &RendererCreateInfo {
+                color_format: vk::Format::R8G8B8A8_UNORM,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let atlas = renderer.upload_font_atlas(&queue, 1, 1, &[255; 4]).unwrap();
+
+        // The display starts at (10, 20), so the quad covers pixels 1..3 of the 4x4 target.
+        let vertex = |x: f32, y: f32| DrawVert {
+            pos: [x, y],
+            uv: [0.0, 0.0],
+            col: [255; 4],
+        };
+        let vertices = [
+            vertex(11.0, 21.0),
+            vertex(13.0, 21.0),
+            vertex(13.0, 23.0),
+            vertex(11.0, 23.0),
+        ];
+        let commands = [DrawCmd {
+            clip_rect: [10.0, 20.0, 14.0, 24.0],
+            texture_id: atlas,
+            vtx_offset: 0,
+            idx_offset: 0,
+            elem_count: 6,
+        }];
+        let lists = [DrawList {
+            vertices: &vertices,
+            indices: &[0, 1, 2, 0, 2, 3],
+            commands: &commands,
+        }];
+        let draw_data = DrawData {
+            display_pos: [10.0, 20.0],
+            display_size: [4.0, 4.0],
+            framebuffer_scale: [1.0, 1.0],
+            lists: &lists,
+        };
+
+        let extent = vk::Extent2D {
+            width: 4,
+            height: 4,
+        };
+        let (pixels, row_pitch) =
+            test_render(&device, &queue, extent, Region::from(extent), |render| {
+                renderer.render(render, &draw_data, extent, 0).unwrap();
+            });
+
+        for y in 0..4 {
+            for x in 0..4 {
+                let red = pixels[y * row_pitch as usize + x * 4];
+                let inside = (1..3).contains(&x) && (1..3).contains(&y);
+                assert_eq!(red, if inside { 255 } else { 0 }, "pixel ({x}, {y})");
+            }
+        }
+    }
+}
@@ -4,6 +4,8 @@ use std::borrow::Cow;
 use std::ffi;
 use std::sync::Arc;
 
+use crate::allocations::EXTERNAL_MEMORY_EXTENSION;
+use crate::sync::EXTERNAL_SEMAPHORE_EXTENSION;
 use crate::{Adapter, AdapterDescriptorIndexingFeatures, AdapterFeatures, GPUError, RankedAdapter};
 
 pub struct Instance {
@@ -15,12 +17,23 @@ pub type RawInstance = Arc<InstanceImpl>;
 pub struct InstanceImpl {
     pub entry: ash::Entry,
     pub handle: ash::Instance,
+    pub debug_utils: ash::ext::debug_utils::Instance,
+    pub messenger: vk::DebugUtilsMessengerEXT,
 }
 
+/// Log target that shader `printf` output is logged under.
+pub const SHADER_PRINTF_TARGET: &str = "tgpu::shader_printf";
+
 #[derive(Default)]
 pub struct InstanceCreateInfo<'a> {
     pub app_name: &'a str,
     pub engine_name: &'a str,
+    /// Enables the validation layer's debug printf; shader output is logged under
+    /// `SHADER_PRINTF_TARGET` at info level.
+    ///
+    /// Devices also need `DeviceFeatures::debug_printf`. In Slang, call `printf("x = %d\n", x)`;
+    /// in GLSL, enable `GL_EXT_debug_printf` and call `debugPrintfEXT`. WGSL has no printf.
+    pub debug_printf: bool,
 }
 
 impl Instance {
@@ -29,14 +42,25 @@ impl Instance {
         let engine_name = ffi::CString::new(info.engine_name).expect("Convert to cstring");
 
         let (extensions, flags) = Self::get_required_extensions_and_flags(None)?;
+        let optional_extensions = Self::optional_surface_extensions();
 
         let validation_layer = ffi::CString::new("VK_LAYER_KHRONOS_validation")
             .expect("Create Validation Layer String");
 
         let layers = vec![validation_layer.as_ptr()];
-
-        let instance =
-            unsafe { InstanceImpl::new(&app_name, &engine_name, &extensions, &layers, flags)? };
+        let validation_features = validation_features(info);
+
+        let instance = unsafe {
+            InstanceImpl::new_with_optional_extensions(
+                &app_name,
+                &engine_name,
+                &extensions,
+                optional_extensions,
+                &layers,
+                validation_features,
+                flags,
+            )?
+        };
 
         let instance = Arc::new(instance);
 
@@ -51,14 +75,25 @@ impl Instance {
         let engine_name = ffi::CString::new(info.engine_name).expect("Convert to cstring");
 
         let (extensions, flags) = Self::get_required_extensions_and_flags(Some(display))?;
+        let optional_extensions = Self::optional_surface_extensions();
 
         let validation_layer = ffi::CString::new("VK_LAYER_KHRONOS_validation")
             .expect("Create Validation Layer String");
 
         let layers = vec![validation_layer.as_ptr()];
-
-        let instance =
-            unsafe { InstanceImpl::new(&app_name, &engine_name, &extensions, &layers, flags)? };
+        let validation_features = validation_features(info);
+
+        let instance = unsafe {
+            InstanceImpl::new_with_optional_extensions(
+                &app_name,
+                &engine_name,
+                &extensions,
+                optional_extensions,
+                &layers,
+                validation_features,
+                flags,
+            )?
+        };
 
         let instance = Arc::new(instance);
 
@@ -116,20 +151,8 @@ impl Instance {
         // TODO: make this toggable
         push_unique(&mut extensions, ash::ext::debug_utils::NAME.as_ptr());
 
-        #[cfg(target_os = "windows")]
-        {
-            push_unique(&mut extensions, ash::khr::win32_surface::NAME.as_ptr());
-        }
-
-        #[cfg(all(unix, not(target_os = "android"), not(target_os = "macos")))]
-        {
-            push_unique(&mut extensions, ash::khr::xlib_surface::NAME.as_ptr());
-            push_unique(&mut extensions, ash::khr::wayland_surface::NAME.as_ptr());
-        }
-
         #[cfg(target_os = "macos")]
         {
-            push_unique(&mut extensions, ash::ext::metal_surface::NAME.as_ptr());
             push_unique(
                 &mut extensions,
                 ash::khr::portability_enumeration::NAME.as_ptr(),
@@ -137,22 +160,51 @@ impl Instance {
             flags |= vk::InstanceCreateFlags::ENUMERATE_PORTABILITY_KHR;
         }
 
+        Ok((extensions, flags))
+    }
+
+    /// Platform surface extensions enabled when the loader reports them, so one binary can
+    /// create surfaces for whichever windowing system it ends up running on.
+    fn optional_surface_extensions() -> &'static [&'static ffi::CStr] {
+        #[cfg(target_os = "windows")]
+        return &[ash::khr::win32_surface::NAME];
+
+        #[cfg(all(unix, not(target_os = "android"), not(target_os = "macos")))]
+        return &[
+            ash::khr::xlib_surface::NAME,
+            ash::khr::xcb_surface::NAME,
+            ash::khr::wayland_surface::NAME,
+        ];
+
+        #[cfg(target_os = "macos")]
+        return &[ash::ext::metal_surface::NAME];
+
         #[cfg(target_os = "android")]
-        {
-            push_unique(&mut extensions, ash::khr::android_surface::NAME.as_ptr());
-        }
+        return &[ash::khr::android_surface::NAME];
 
         #[cfg(target_os = "ios")]
-        {
-            push_unique(&mut extensions, ash::mvk::ios_surface::NAME.as_ptr());
-        }
+        return &[ash::mvk::ios_surface::NAME];
 
-        Ok((extensions, flags))
+        #[allow(unreachable_code)]
+        &[]
     }
 
     pub fn raw(&self) -> RawInstance {
         self.inner.clone()
     }
+
+    /// Raw `VkInstance`, for interop with other Vulkan libraries; it stays owned by `self`.
+    pub fn raw_handle(&self) -> vk::Instance {
+        self.inner.handle.handle()
+    }
+}
+
+fn validation_features(info: &InstanceCreateInfo<'_>) -> &'static [vk::ValidationFeatureEnableEXT] {
+    if info.debug_printf {
+        &[vk::ValidationFeatureEnableEXT::DEBUG_PRINTF]
+    } else {
+        &[]
+    }
 }
 
 fn push_unique(extensions: &mut Vec<*const i8>, extension: *const i8) {
@@ -168,6 +220,33 @@ impl InstanceImpl {
         extensions: &[*const i8],
         layers: &[*const i8],
         flags: vk::InstanceCreateFlags,
+    ) -> Result<Self, GPUError> {
+        unsafe {
+            Self::new_with_optional_extensions(
+                app_name,
+                engine_name,
+                extensions,
+                &[],
+                layers,
+                &[],
+                flags,
+            )
+        }
+    }
+
+    /// Like `new`, but also enables each of `optional_extensions` the loader supports, and
+    /// `validation_features` of the validation layer.
+    ///
+    /// # Safety
+    /// Every pointer in `extensions` and `layers` must point to a valid nul-terminated string.
+    pub unsafe fn new_with_optional_extensions(
+        app_name: &ffi::CStr,
+        engine_name: &ffi::CStr,
+        extensions: &[*const i8],
+        optional_extensions: &[&ffi::CStr],
+        layers: &[*const i8],
+        validation_features: &[vk::ValidationFeatureEnableEXT],
+        flags: vk::InstanceCreateFlags,
     ) -> Result<Self, GPUError> {
         let entry = match unsafe { Self::load_entry() } {
             Ok(entry) => entry,
@@ -176,6 +255,29 @@ impl InstanceImpl {
             }
         };
 
+        let mut extensions = extensions.to_vec();
+        if !validation_features.is_empty() {
+            push_unique(
+                &mut extensions,
+                ash::ext::validation_features::NAME.as_ptr(),
+            );
+        }
+        if !optional_extensions.is_empty() {
+            let available =
+                unsafe { entry.enumerate_instance_extension_properties(None) }.unwrap_or_default();
+            for &name in optional_extensions {
+                let requested = extensions
+                    .iter()
+                    .any(|&ext| unsafe { ffi::CStr::from_ptr(ext) } == name);
+                let supported = available
+                    .iter()
+                    .any(|ext| ext.extension_name_as_c_str() == Ok(name));
+                if !requested && supported {
+                    extensions.push(name.as_ptr());
+                }
+            }
+        }
+
         let version = unsafe { Self::get_vulkan_instance_version(&entry).map_err(GPUError::from)? };
 
         log::debug!(
@@ -205,19 +307,40 @@ impl InstanceImpl {
             )
             .pfn_user_callback(Some(vulkan_debug_callback));
 
-        let ifo = vk::InstanceCreateInfo::default()
+        let mut validation_features_info =
+            vk::ValidationFeaturesEXT::default().enabled_validation_features(validation_features);
+
+        let mut ifo = vk::InstanceCreateInfo::default()
             .application_info(&afo)
-            .enabled_extension_names(extensions)
+            .enabled_extension_names(&extensions)
             .enabled_layer_names(layers)
             .flags(flags)
             .push_next(&mut dfo);
+        if !validation_features.is_empty() {
+            ifo = ifo.push_next(&mut validation_features_info);
+        }
 
         let handle = match unsafe { entry.create_instance(&ifo, None) } {
             Ok(handle) => handle,
             Err(e) => return Err(GPUError::Vulkan(e)),
         };
 
-        Ok(Self { entry, handle })
+        // The messenger chained into `ifo` only covers instance creation and destruction.
+        let debug_utils = ash::ext::debug_utils::Instance::new(&entry, &handle);
+        let messenger = match unsafe { debug_utils.create_debug_utils_messenger(&dfo, None) } {
+            Ok(messenger) => messenger,
+            Err(e) => {
+                unsafe { handle.destroy_instance(None) };
+                return Err(GPUError::Vulkan(e));
+            }
+        };
+
+        Ok(Self {
+            entry,
+            handle,
+            debug_utils,
+            messenger,
+        })
     }
 
     pub unsafe fn load_entry() -> Result<ash::Entry, ash::LoadingError> {
@@ -253,14 +376,27 @@ impl InstanceImpl {
     }
 
     pub unsafe fn features(&self, pdev: vk::PhysicalDevice) -> AdapterFeatures {
-        let (fill_mode_non_solid, descriptor_indexing, buffer_device_address, shader_int64) = {
+        let (
+            fill_mode_non_solid,
+            descriptor_indexing,
+            buffer_device_address,
+            shader_int64,
+            fragment_stores_and_atomics,
+            multiview,
+            sparse_binding,
+            sparse_residency_image_2d,
+            multi_viewport,
+            robust_buffer_access,
+        ) = {
             let mut descriptor_indexing_features =
                 vk::PhysicalDeviceDescriptorIndexingFeatures::default();
             let mut buffer_device_address_features =
                 vk::PhysicalDeviceBufferDeviceAddressFeatures::default();
+            let mut multiview_features = vk::PhysicalDeviceMultiviewFeatures::default();
             let mut features2 = vk::PhysicalDeviceFeatures2::default()
                 .push_next(&mut descriptor_indexing_features)
-                .push_next(&mut buffer_device_address_features);
+                .push_next(&mut buffer_device_address_features)
+                .push_next(&mut multiview_features);
 
             unsafe { self.handle.get_physical_device_features2(pdev, &mut features2) };
 
@@ -275,6 +411,11 @@ impl InstanceImpl {
             let storage_buffer_dynamic_indexing =
                 base_features.shader_storage_buffer_array_dynamic_indexing == vk::TRUE;
             let shader_int64 = base_features.shader_int64 == vk::TRUE;
+            let fragment_stores_and_atomics = base_features.fragment_stores_and_atomics == vk::TRUE;
+            let sparse_binding = base_features.sparse_binding == vk::TRUE;
+            let sparse_residency_image_2d = base_features.sparse_residency_image2_d == vk::TRUE;
+            let multi_viewport = base_features.multi_viewport == vk::TRUE;
+            let robust_buffer_access = base_features.robust_buffer_access == vk::TRUE;
             let _ = features2;
 
             let descriptor_indexing = AdapterDescriptorIndexingFeatures {
@@ -322,17 +463,104 @@ impl InstanceImpl {
                 descriptor_indexing,
                 buffer_device_address,
                 shader_int64,
+                fragment_stores_and_atomics,
+                multiview_features.multiview == vk::TRUE,
+                sparse_binding,
+                sparse_residency_image_2d,
+                multi_viewport,
+                robust_buffer_access,
             )
         };
 
+        let draw_indirect_count =
+            unsafe { self.supports_device_extension(pdev, ash::khr::draw_indirect_count::NAME) };
+
+        let dynamic_rendering_local_read = unsafe {
+            self.supports_device_extension(pdev, ash::khr::dynamic_rendering_local_read::NAME)
+        } && {
+            let mut local_read_features =
+                vk::PhysicalDeviceDynamicRenderingLocalReadFeaturesKHR::default();
+            let mut features2 =
+                vk::PhysicalDeviceFeatures2::default().push_next(&mut local_read_features);
+            unsafe {
+                self.handle
+                    .get_physical_device_features2(pdev, &mut features2)
+            };
+            local_read_features.dynamic_rendering_local_read == vk::TRUE
+        };
+
+        let device_fault =
+            unsafe { self.supports_device_extension(pdev, ash::ext::device_fault::NAME) } && {
+                let mut fault_features = vk::PhysicalDeviceFaultFeaturesEXT::default();
+                let mut features2 =
+                    vk::PhysicalDeviceFeatures2::default().push_next(&mut fault_features);
+                unsafe {
+                    self.handle
+                        .get_physical_device_features2(pdev, &mut features2)
+                };
+                fault_features.device_fault == vk::TRUE
+            };
+
+        let robust_buffer_access2 =
+            unsafe { self.supports_device_extension(pdev, ash::ext::robustness2::NAME) } && {
+                let mut robustness2_features = vk::PhysicalDeviceRobustness2FeaturesEXT::default();
+                let mut features2 =
+                    vk::PhysicalDeviceFeatures2::default().push_next(&mut robustness2_features);
+                unsafe {
+                    self.handle
+                        .get_physical_device_features2(pdev, &mut features2)
+                };
+                robustness2_features.robust_buffer_access2 == vk::TRUE
+            };
+
+        let shader_non_semantic_info = unsafe {
+            self.supports_device_extension(pdev, ash::khr::shader_non_semantic_info::NAME)
+        };
+
+        let external_semaphore =
+            unsafe { self.supports_device_extension(pdev, EXTERNAL_SEMAPHORE_EXTENSION) };
+        let external_memory =
+            unsafe { self.supports_device_extension(pdev, EXTERNAL_MEMORY_EXTENSION) };
+
         AdapterFeatures {
             fill_mode_non_solid,
             descriptor_indexing,
             buffer_device_address,
             shader_int64,
+            fragment_stores_and_atomics,
+            draw_indirect_count,
+            multiview,
+            sparse_binding,
+            sparse_residency_image_2d,
+            dynamic_rendering_local_read,
+            device_fault,
+            multi_viewport,
+            external_semaphore,
+            external_memory,
+            robust_buffer_access,
+            robust_buffer_access2,
+            shader_non_semantic_info,
         }
     }
 
+    /// # Safety
+    /// `pdev` must be a physical device enumerated from this instance.
+    pub unsafe fn supports_device_extension(
+        &self,
+        pdev: vk::PhysicalDevice,
+        name: &ffi::CStr,
+    ) -> bool {
+        let extensions = unsafe {
+            self.handle
+                .enumerate_device_extension_properties(pdev)
+                .unwrap_or_default()
+        };
+
+        extensions
+            .iter()
+            .any(|extension| extension.extension_name_as_c_str() == Ok(name))
+    }
+
     pub unsafe fn queue_properties(
         &self,
         pdev: vk::PhysicalDevice,
@@ -384,6 +612,8 @@ impl InstanceImpl {
 impl Drop for InstanceImpl {
     fn drop(&mut self) {
         unsafe {
+            self.debug_utils
+                .destroy_debug_utils_messenger(self.messenger, None);
             self.handle.destroy_instance(None);
         }
     }
@@ -408,6 +638,11 @@ pub unsafe extern "system" fn vulkan_debug_callback(
         unsafe { ffi::CStr::from_ptr(callback_data.p_message).to_string_lossy() }
     };
 
+    if message_id_name.contains("DEBUG-PRINTF") {
+        log::info!(target: SHADER_PRINTF_TARGET, "{}", message);
+        return vk::FALSE;
+    }
+
     match message_severity {
         vk::DebugUtilsMessageSeverityFlagsEXT::ERROR => {
             log::error!(
@@ -4,44 +4,63 @@ use std::fmt;
 
 use ash::vk;
 
+/// Enters an info span until the end of the enclosing block when the `tracing` feature is enabled.
+macro_rules! profile_span {
+    ($name:literal $(, $($fields:tt)*)?) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!($name $(, $($fields)*)?).entered();
+    };
+}
+
 mod adapter;
 mod allocations;
+#[cfg(feature = "winit")]
+pub mod app;
 mod bindless;
 mod buffer;
 mod command;
+mod compute_graph;
 mod debug;
 mod descriptor;
 mod device;
 #[cfg(feature = "egui")]
 pub mod egui;
 mod image;
+#[cfg(feature = "imgui")]
+pub mod imgui;
 mod instance;
 mod pipeline;
 mod queue;
+mod reflect;
 mod render_graph;
 mod resource;
 mod shader;
 mod swapchain;
 mod sync;
+#[cfg(any(feature = "egui", feature = "imgui"))]
+mod ui;
+mod vertex;
 
 pub mod raw {
     pub use crate::adapter::{AdapterImpl, RawAdapter};
+    pub use crate::allocations::MemoryPoolImpl;
     pub use crate::buffer::BufferImpl;
-    pub use crate::command::{CommandBufferImpl, CommandRecorderImpl};
+    pub use crate::command::{CommandBufferImpl, CommandRecorderImpl, RawSubmit};
+    pub use crate::descriptor::DescriptorPoolImpl;
     pub use crate::device::{DeviceImpl, RawDevice};
     pub use crate::image::{ImageImpl, ImageViewImpl, SamplerImpl};
     pub use crate::instance::{InstanceImpl, RawInstance};
     pub use crate::pipeline::{ComputePipelineImpl, RenderPipelineImpl};
     pub use crate::queue::{QueueImpl, RawQueue};
     pub use crate::swapchain::{SwapchainImpl, SwapchainImplResources};
-    pub use crate::sync::SemaphoreImpl;
+    pub use crate::sync::{EventImpl, FenceImpl, SemaphoreImpl};
 }
 
 pub use adapter::{
     Adapter, AdapterDescriptorIndexingFeatures, AdapterDeviceType, AdapterFeatures, AdapterInfo,
-    AdapterLimits, RankedAdapter,
+    AdapterLimits, MemoryHeap, MemoryType, RankedAdapter,
 };
-pub use allocations::Allocation;
+pub use allocations::{AliasedMemory, Allocation, MemoryPool, MemoryPoolInfo};
 pub use ash;
 pub use ash::vk::{
     ColorSpaceKHR, CullModeFlags, Format, FrontFace, PolygonMode, PresentModeKHR,
@@ -55,24 +74,30 @@ pub use bindless::{
 };
 pub use buffer::{
     Buffer, BufferAccessTransition, BufferDesc, BufferTransition, BufferUses, CopyBufferInfo,
+    MappedSlice, MappedSliceMut,
 };
 pub use command::{
-    CommandBuffer, CommandPools, CommandRecorder, RenderInfo, RenderRecorder, SubmitInfo,
-    ThreadCommandPool,
+    ColorAttachment, CommandBuffer, CommandPoolConfig, CommandPoolStats, CommandPools,
+    CommandRecorder, DepthAttachment, LocalReadInfo, Region, RenderInfo, RenderRecorder,
+    SubmitInfo, ThreadCommandPool, TransferRecorder,
 };
+pub use compute_graph::{ComputeGraph, ComputeNodeBuilder};
 pub use debug::Label;
 pub use descriptor::{
     DescriptorArena, DescriptorBinding, DescriptorPool, DescriptorPoolInfo, DescriptorSet,
     DescriptorSetLayout, DescriptorSetLayoutInfo, DescriptorType, DescriptorWrite,
 };
-pub use device::{Device, DeviceCreateInfo, DeviceFeatures};
+pub use device::{
+    Device, DeviceCreateInfo, DeviceFaultAddress, DeviceFaultInfo, DeviceFaultVendorInfo,
+    DeviceFeatures,
+};
 pub use image::{
-    BlitImageInfo, CopyBufferToImageInfo, CopyImageInfo, Image, ImageDesc, ImageFlags, ImageLayout,
-    ImageLayoutTransition, ImageTransition, ImageUses, ImageView, ImageViewCreateInfo,
-    ImageViewOptions, Sampler, SamplerCreateInfo, Texture2DDesc, TextureUses, ViewImage,
-    ViewImageDesc,
+    BlitImageInfo, CopyBufferToImageInfo, CopyImageInfo, CopyImageToBufferInfo, Image, ImageDesc,
+    ImageFlags, ImageLayout, ImageLayoutTransition, ImageTransition, ImageUses, ImageView,
+    ImageViewCreateInfo, ImageViewOptions, Sampler, SamplerCreateInfo, Texture2DDesc, TextureUses,
+    ViewImage, ViewImageDesc, max_mip_levels,
 };
-pub use instance::{Instance, InstanceCreateInfo};
+pub use instance::{Instance, InstanceCreateInfo, SHADER_PRINTF_TARGET};
 pub use pipeline::{ComputePipeline, ComputePipelineInfo, RenderPipeline, RenderPipelineInfo};
 pub use queue::{Queue, QueueFamilyInfo, QueueRequest};
 pub use render_graph::{
@@ -83,12 +108,19 @@ pub use render_graph::{
 };
 pub use resource::{HostAccess, MemoryPreset};
 pub use shader::{Shader, ShaderEntry, ShaderSource};
-pub use swapchain::{Frame, Swapchain, SwapchainCreateInfo};
-pub use sync::Semaphore;
+pub use swapchain::{FormatSelector, Frame, Swapchain, SwapchainCreateInfo, SwapchainSurfaceInfo};
+pub use sync::{Event, ExternalHandle, Fence, PerFrame, Semaphore, SemaphoreKind, SubmitToken};
+pub use vertex::{Vertex, VertexAttribute, VertexLayout};
 
 pub enum GPUError {
     Vulkan(vk::Result),
     Validation(&'static str),
+    /// The device was lost (GPU hang or driver reset); it has to be recreated.
+    DeviceLost,
+    /// Shader source failed to compile, or SPIR-V failed validation.
+    ShaderCompile(String),
+    /// Pipeline descriptor layouts don't match the bindings a shader declares.
+    ShaderLayout(String),
 }
 
 impl fmt::Debug for GPUError {
@@ -96,6 +128,9 @@ impl fmt::Debug for GPUError {
         match self {
             Self::Vulkan(result) => write!(f, "Vulkan error: {:?}", result),
             Self::Validation(message) => write!(f, "Validation error: {message}"),
+            Self::DeviceLost => write!(f, "Device lost"),
+            Self::ShaderCompile(message) => write!(f, "Shader compile error: {message}"),
+            Self::ShaderLayout(message) => write!(f, "Shader layout mismatch: {message}"),
         }
     }
 }
@@ -105,6 +140,9 @@ impl fmt::Display for GPUError {
         match self {
             Self::Vulkan(result) => write!(f, "Vulkan error: {:?}", result),
             Self::Validation(message) => write!(f, "Validation error: {message}"),
+            Self::DeviceLost => write!(f, "Device lost"),
+            Self::ShaderCompile(message) => write!(f, "Shader compile error: {message}"),
+            Self::ShaderLayout(message) => write!(f, "Shader layout mismatch: {message}"),
         }
     }
 }
@@ -114,12 +152,18 @@ impl std::error::Error for GPUError {
         match self {
             Self::Vulkan(_) => None,
             Self::Validation(_) => None,
+            Self::DeviceLost => None,
+            Self::ShaderCompile(_) => None,
+            Self::ShaderLayout(_) => None,
         }
     }
 }
 
 impl From<vk::Result> for GPUError {
     fn from(value: vk::Result) -> Self {
-        Self::Vulkan(value)
+        match value {
+            vk::Result::ERROR_DEVICE_LOST => Self::DeviceLost,
+            _ => Self::Vulkan(value),
+        }
     }
 }
@@ -1,13 +1,20 @@
 use ash::vk;
 use std::sync::Arc;
 
-use crate::{DescriptorSetLayout, Device, Label, ShaderEntry, raw::DeviceImpl};
+use crate::{
+    DescriptorSetLayout, Device, DeviceFeatures, Label, LocalReadInfo, ShaderEntry, raw::DeviceImpl,
+};
 
 pub struct ComputePipelineInfo<'a> {
     pub shader: ShaderEntry<'a>,
     pub descriptor_layouts: &'a [&'a DescriptorSetLayout],
     pub push_constant_size: Option<u32>,
     pub cache: Option<vk::PipelineCache>,
+    /// Creates the pipeline with `DISPATCH_BASE` so it can be used with `dispatch_base`.
+    pub dispatch_base: bool,
+    /// Checks the descriptors the shader uses against `descriptor_layouts` at creation and
+    /// returns `GPUError::ShaderLayout` on a mismatch.
+    pub validate_layouts: bool,
     pub label: Option<Label<'a>>,
 }
 
@@ -18,6 +25,8 @@ impl Default for ComputePipelineInfo<'_> {
             descriptor_layouts: &[],
             push_constant_size: None,
             cache: None,
+            dispatch_base: false,
+            validate_layouts: false,
             label: None,
         }
     }
@@ -31,14 +40,43 @@ pub struct RenderPipelineInfo<'a> {
     pub depth_test: bool,
     pub depth_write: bool,
     pub depth_compare: vk::CompareOp,
+    /// Set together with `depth_format` when using a combined format like `D24_UNORM_S8_UINT`.
+    pub stencil_format: Option<vk::Format>,
+    /// Enables the stencil test; reference, compare and write masks become dynamic state.
+    pub stencil_test: bool,
+    pub stencil_front: vk::StencilOpState,
+    pub stencil_back: vk::StencilOpState,
     pub descriptor_layouts: &'a [&'a DescriptorSetLayout],
     pub push_constant_size: Option<u32>,
     pub blend_states: Option<&'a [vk::PipelineColorBlendAttachmentState]>,
+    /// Makes the blend constants dynamic state, set with `RenderRecorder::blend_constants`.
+    pub dynamic_blend_constants: bool,
+    /// One write mask per entry in `color_formats`; overrides the masks in `blend_states`.
+    pub color_write_masks: Option<&'a [vk::ColorComponentFlags]>,
+    /// See `VertexLayout::state` for building this from a `Vertex` type.
     pub vertex_input_state: Option<vk::PipelineVertexInputStateCreateInfo<'a>>,
     pub topology: vk::PrimitiveTopology,
+    /// Treat the max index value (e.g. `0xFFFFFFFF`) as a strip restart; strip/fan topologies only.
+    pub primitive_restart: bool,
     pub polygon: vk::PolygonMode,
     pub cull: vk::CullModeFlags,
     pub front_face: vk::FrontFace,
+    /// Must match the `view_mask` of the `RenderInfo` this pipeline is used with.
+    pub view_mask: u32,
+    /// Must match the `local_read` of the `RenderInfo` this pipeline is used with.
+    pub local_read: Option<LocalReadInfo<'a>>,
+    /// Number of dynamic viewports and scissors; more than one requires the `multi_viewport` feature.
+    pub viewport_count: u32,
+    /// Lets later pipelines name this one as their `base`. Can slow the pipeline down, so only
+    /// set it on pipelines that variants derive from.
+    pub allow_derivatives: bool,
+    /// Creates this pipeline as a derivative of `base`, letting the driver share compilation work
+    /// between variants that differ only in a few states such as blending. `base` must have
+    /// been created with `allow_derivatives`.
+    pub base: Option<&'a RenderPipeline>,
+    /// Checks the descriptors both shaders use against `descriptor_layouts` at creation and
+    /// returns `GPUError::ShaderLayout` on a mismatch.
+    pub validate_layouts: bool,
     pub label: Option<Label<'a>>,
 }
 
@@ -52,72 +90,275 @@ impl Default for RenderPipelineInfo<'_> {
             depth_test: false,
             depth_write: false,
             depth_compare: vk::CompareOp::ALWAYS,
+            stencil_format: None,
+            stencil_test: false,
+            stencil_front: vk::StencilOpState::default(),
+            stencil_back: vk::StencilOpState::default(),
             descriptor_layouts: &[],
             push_constant_size: None,
             blend_states: None,
+            dynamic_blend_constants: false,
+            color_write_masks: None,
             vertex_input_state: None,
             topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+            primitive_restart: false,
             polygon: vk::PolygonMode::FILL,
             cull: vk::CullModeFlags::NONE,
             front_face: vk::FrontFace::COUNTER_CLOCKWISE,
+            view_mask: 0,
+            local_read: None,
+            viewport_count: 1,
+            allow_derivatives: false,
+            base: None,
+            validate_layouts: false,
             label: None,
         }
     }
 }
 
 pub struct ComputePipeline {
-    pub inner: ComputePipelineImpl,
+    pub inner: Arc<ComputePipelineImpl>,
 }
 
 pub struct ComputePipelineImpl {
     pub handle: vk::Pipeline,
     pub layout: vk::PipelineLayout,
+    pub dispatch_base: bool,
+    pub push_constant_size: u32,
+    pub descriptor_layout_count: u32,
     pub device: Arc<DeviceImpl>,
 }
 
+impl ComputePipeline {
+    /// Size in bytes of the push-constant range, 0 if the layout has none.
+    pub fn push_constant_size(&self) -> u32 {
+        self.inner.push_constant_size
+    }
+
+    /// Number of descriptor set layouts in the pipeline layout.
+    pub fn descriptor_layout_count(&self) -> u32 {
+        self.inner.descriptor_layout_count
+    }
+
+    pub fn bind_point(&self) -> vk::PipelineBindPoint {
+        vk::PipelineBindPoint::COMPUTE
+    }
+}
+
 pub struct RenderPipeline {
-    pub inner: RenderPipelineImpl,
+    pub inner: Arc<RenderPipelineImpl>,
 }
 
 pub struct RenderPipelineImpl {
     pub handle: vk::Pipeline,
     pub layout: vk::PipelineLayout,
+    pub push_constant_size: u32,
+    pub descriptor_layout_count: u32,
+    pub allow_derivatives: bool,
+    pub viewport_count: u32,
     pub device: Arc<DeviceImpl>,
 }
 
+impl RenderPipeline {
+    /// Size in bytes of the push-constant range, 0 if the layout has none.
+    pub fn push_constant_size(&self) -> u32 {
+        self.inner.push_constant_size
+    }
+
+    /// Number of descriptor set layouts in the pipeline layout.
+    pub fn descriptor_layout_count(&self) -> u32 {
+        self.inner.descriptor_layout_count
+    }
+
+    pub fn bind_point(&self) -> vk::PipelineBindPoint {
+        vk::PipelineBindPoint::GRAPHICS
+    }
+}
+
 impl RenderPipelineImpl {
     pub fn try_new(
         device: Arc<DeviceImpl>,
         info: &RenderPipelineInfo,
     ) -> Result<RenderPipelineImpl, crate::GPUError> {
-        let mut push_constant_ranges = Vec::new();
-        if let Some(size) = info.push_constant_size {
-            push_constant_ranges.push(
-                vk::PushConstantRange::default()
-                    .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
-                    .size(size),
-            );
+        let mut pipelines = Self::try_new_batch(device, std::slice::from_ref(info))?;
+        Ok(pipelines.remove(0))
+    }
+
+    pub fn new(device: Arc<DeviceImpl>, info: &RenderPipelineInfo) -> RenderPipelineImpl {
+        Self::try_new(device, info).expect("Create render pipeline")
+    }
+
+    /// Creates all pipelines with a single `vkCreateGraphicsPipelines` call.
+    pub fn try_new_batch(
+        device: Arc<DeviceImpl>,
+        infos: &[RenderPipelineInfo],
+    ) -> Result<Vec<RenderPipelineImpl>, crate::GPUError> {
+        if device.compute_only {
+            return Err(crate::GPUError::Validation(
+                "render pipelines can't be created on a compute_only device",
+            ));
+        }
+        if infos.is_empty() {
+            return Ok(Vec::new());
+        }
+        for info in infos {
+            validate_render_pipeline(
+                info,
+                &device.features,
+                device.adapter.info.limits.max_viewports,
+            )?;
+            if info.validate_layouts {
+                validate_shader_layouts(&info.vertex_shader, info.descriptor_layouts)?;
+                validate_shader_layouts(&info.fragment_shader, info.descriptor_layouts)?;
+            }
         }
 
-        let layouts = info
-            .descriptor_layouts
-            .iter()
-            .map(|l| l.handle)
-            .collect::<Vec<_>>();
+        let layouts = create_layouts(
+            &device,
+            infos.iter().map(|info| {
+                (
+                    info.descriptor_layouts,
+                    info.push_constant_size,
+                    vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+                )
+            }),
+        )?;
+
+        let mut storages = Vec::with_capacity(infos.len());
+        for info in infos {
+            match RenderPipelineStorage::new(info) {
+                Ok(storage) => storages.push(storage),
+                Err(err) => {
+                    destroy_layouts(&device, &layouts);
+                    return Err(err);
+                }
+            }
+        }
 
-        let layout_info = vk::PipelineLayoutCreateInfo::default()
-            .set_layouts(&layouts)
-            .push_constant_ranges(&push_constant_ranges);
+        let create_infos = storages
+            .iter_mut()
+            .zip(infos)
+            .zip(&layouts)
+            .map(|((storage, info), &layout)| storage.create_info(info, layout))
+            .collect::<Vec<_>>();
 
-        let layout = unsafe {
+        let handles = unsafe {
             device
                 .handle
-                .create_pipeline_layout(&layout_info, None)
-                .map_err(crate::GPUError::from)?
+                .create_graphics_pipelines(vk::PipelineCache::null(), &create_infos, None)
         };
+        let handles = match handles {
+            Ok(handles) => handles,
+            Err((handles, err)) => {
+                destroy_pipelines(&device, &handles);
+                destroy_layouts(&device, &layouts);
+                return Err(crate::GPUError::from(err));
+            }
+        };
+
+        Ok(handles
+            .into_iter()
+            .zip(layouts)
+            .zip(infos)
+            .map(|((handle, layout), info)| {
+                if let Some(label) = &info.label {
+                    unsafe {
+                        device.attach_label(handle, label);
+                        device.attach_label(layout, label);
+                    }
+                }
+                RenderPipelineImpl {
+                    handle,
+                    layout,
+                    push_constant_size: info.push_constant_size.unwrap_or(0),
+                    descriptor_layout_count: info.descriptor_layouts.len() as u32,
+                    allow_derivatives: info.allow_derivatives,
+                    viewport_count: info.viewport_count,
+                    device: device.clone(),
+                }
+            })
+            .collect())
+    }
+}
+
+/// State referenced by a `vk::GraphicsPipelineCreateInfo`, kept alive until the create call.
+struct RenderPipelineStorage<'a> {
+    stage_names: [std::ffi::CString; 2],
+    stages: [vk::PipelineShaderStageCreateInfo<'a>; 2],
+    vertex_input: vk::PipelineVertexInputStateCreateInfo<'a>,
+    input_assembly: vk::PipelineInputAssemblyStateCreateInfo<'a>,
+    viewport: vk::PipelineViewportStateCreateInfo<'a>,
+    rasterization: vk::PipelineRasterizationStateCreateInfo<'a>,
+    multisample: vk::PipelineMultisampleStateCreateInfo<'a>,
+    depth_stencil: vk::PipelineDepthStencilStateCreateInfo<'a>,
+    color_blend_attachments: Vec<vk::PipelineColorBlendAttachmentState>,
+    color_blend: vk::PipelineColorBlendStateCreateInfo<'a>,
+    dynamic_states: Vec<vk::DynamicState>,
+    dynamic_state: vk::PipelineDynamicStateCreateInfo<'a>,
+    rendering: vk::PipelineRenderingCreateInfo<'a>,
+    locations: vk::RenderingAttachmentLocationInfoKHR<'a>,
+    input_indices: vk::RenderingInputAttachmentIndexInfoKHR<'a>,
+}
+
+impl<'a> RenderPipelineStorage<'a> {
+    fn new(info: &RenderPipelineInfo<'_>) -> Result<Self, crate::GPUError> {
+        let mut dynamic_states = vec![vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+        if info.stencil_test {
+            dynamic_states.extend([
+                vk::DynamicState::STENCIL_REFERENCE,
+                vk::DynamicState::STENCIL_COMPARE_MASK,
+                vk::DynamicState::STENCIL_WRITE_MASK,
+            ]);
+        }
+        if info.dynamic_blend_constants {
+            dynamic_states.push(vk::DynamicState::BLEND_CONSTANTS);
+        }
+
+        Ok(Self {
+            stage_names: [
+                std::ffi::CString::new(info.vertex_shader.name).unwrap(),
+                std::ffi::CString::new(info.fragment_shader.name).unwrap(),
+            ],
+            stages: Default::default(),
+            vertex_input: Default::default(),
+            input_assembly: Default::default(),
+            viewport: Default::default(),
+            rasterization: Default::default(),
+            multisample: Default::default(),
+            depth_stencil: Default::default(),
+            color_blend_attachments: color_blend_attachments(info)?,
+            color_blend: Default::default(),
+            dynamic_states,
+            dynamic_state: Default::default(),
+            rendering: Default::default(),
+            locations: Default::default(),
+            input_indices: Default::default(),
+        })
+    }
+
+    fn create_info(
+        &'a mut self,
+        info: &'a RenderPipelineInfo<'a>,
+        layout: vk::PipelineLayout,
+    ) -> vk::GraphicsPipelineCreateInfo<'a> {
+        let Self {
+            stage_names,
+            stages,
+            vertex_input,
+            input_assembly,
+            viewport,
+            rasterization,
+            multisample,
+            depth_stencil,
+            color_blend_attachments,
+            color_blend,
+            dynamic_states,
+            dynamic_state,
+            rendering,
+            locations,
+            input_indices,
+        } = self;
 
-        let vertex_stage_name = std::ffi::CString::new(info.vertex_shader.name).unwrap();
-        let fragment_stage_name = std::ffi::CString::new(info.fragment_shader.name).unwrap();
         let vertex_shader = info
             .vertex_shader
             .shader
@@ -127,28 +368,28 @@ impl RenderPipelineImpl {
             .shader
             .expect("RenderPipelineInfo::fragment_shader must be set");
 
-        let stages = [
+        *stages = [
             vk::PipelineShaderStageCreateInfo::default()
                 .stage(vk::ShaderStageFlags::VERTEX)
                 .module(vertex_shader.module.handle)
-                .name(&vertex_stage_name),
+                .name(&stage_names[0]),
             vk::PipelineShaderStageCreateInfo::default()
                 .stage(vk::ShaderStageFlags::FRAGMENT)
                 .module(fragment_shader.module.handle)
-                .name(&fragment_stage_name),
+                .name(&stage_names[1]),
         ];
 
-        let vertex_input = info.vertex_input_state.unwrap_or_default();
+        *vertex_input = info.vertex_input_state.unwrap_or_default();
 
-        let input_assembly = vk::PipelineInputAssemblyStateCreateInfo::default()
+        *input_assembly = vk::PipelineInputAssemblyStateCreateInfo::default()
             .topology(info.topology)
-            .primitive_restart_enable(false);
+            .primitive_restart_enable(info.primitive_restart);
 
-        let viewport_state = vk::PipelineViewportStateCreateInfo::default()
-            .viewport_count(1)
-            .scissor_count(1);
+        *viewport = vk::PipelineViewportStateCreateInfo::default()
+            .viewport_count(info.viewport_count)
+            .scissor_count(info.viewport_count);
 
-        let rasterization = vk::PipelineRasterizationStateCreateInfo::default()
+        *rasterization = vk::PipelineRasterizationStateCreateInfo::default()
             .depth_clamp_enable(false)
             .rasterizer_discard_enable(false)
             .depth_bias_enable(false)
@@ -157,145 +398,286 @@ impl RenderPipelineImpl {
             .cull_mode(info.cull)
             .front_face(info.front_face);
 
-        let multisample = vk::PipelineMultisampleStateCreateInfo::default()
+        *multisample = vk::PipelineMultisampleStateCreateInfo::default()
             .sample_shading_enable(false)
             .rasterization_samples(vk::SampleCountFlags::TYPE_1);
 
-        let depth_stencil = vk::PipelineDepthStencilStateCreateInfo::default()
+        *depth_stencil = vk::PipelineDepthStencilStateCreateInfo::default()
             .depth_test_enable(info.depth_test)
             .depth_write_enable(info.depth_write)
             .depth_compare_op(info.depth_compare)
             .depth_bounds_test_enable(false)
-            .stencil_test_enable(false);
-
-        let color_blend_attachment = info.blend_states.as_ref().map_or_else(
-            || {
-                vec![
-                    vk::PipelineColorBlendAttachmentState::default()
-                        .color_write_mask(vk::ColorComponentFlags::RGBA)
-                        .blend_enable(false),
-                ]
-            },
-            |&states| states.to_vec(),
-        );
+            .stencil_test_enable(info.stencil_test)
+            .front(info.stencil_front)
+            .back(info.stencil_back);
 
-        let color_blend = vk::PipelineColorBlendStateCreateInfo::default()
+        *color_blend = vk::PipelineColorBlendStateCreateInfo::default()
             .logic_op_enable(false)
             .logic_op(vk::LogicOp::COPY)
             .blend_constants([0.0, 0.0, 0.0, 0.0])
-            .attachments(&color_blend_attachment);
-
-        let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+            .attachments(color_blend_attachments);
 
-        let dynamic_state =
-            vk::PipelineDynamicStateCreateInfo::default().dynamic_states(&dynamic_states);
+        *dynamic_state =
+            vk::PipelineDynamicStateCreateInfo::default().dynamic_states(dynamic_states);
 
-        let mut rendering_info =
-            vk::PipelineRenderingCreateInfo::default().color_attachment_formats(info.color_formats);
+        *rendering = vk::PipelineRenderingCreateInfo::default()
+            .color_attachment_formats(info.color_formats)
+            .view_mask(info.view_mask);
 
         if let Some(format) = info.depth_format {
-            rendering_info = rendering_info.depth_attachment_format(format);
+            *rendering = rendering.depth_attachment_format(format);
+        }
+        if let Some(format) = info.stencil_format {
+            *rendering = rendering.stencil_attachment_format(format);
         }
 
         let mut create_info = vk::GraphicsPipelineCreateInfo::default()
-            .stages(&stages)
-            .vertex_input_state(&vertex_input)
-            .input_assembly_state(&input_assembly)
-            .viewport_state(&viewport_state)
-            .rasterization_state(&rasterization)
-            .multisample_state(&multisample)
-            .color_blend_state(&color_blend)
-            .dynamic_state(&dynamic_state)
+            .stages(stages)
+            .vertex_input_state(vertex_input)
+            .input_assembly_state(input_assembly)
+            .viewport_state(viewport)
+            .rasterization_state(rasterization)
+            .multisample_state(multisample)
+            .color_blend_state(color_blend)
+            .dynamic_state(dynamic_state)
             .layout(layout)
-            .base_pipeline_handle(vk::Pipeline::null())
-            .push_next(&mut rendering_info);
+            .push_next(rendering);
 
-        if info.depth_format.is_some() || info.depth_test || info.depth_write {
-            create_info = create_info.depth_stencil_state(&depth_stencil);
+        if info.allow_derivatives {
+            create_info.flags = vk::PipelineCreateFlags::ALLOW_DERIVATIVES;
+        }
+        create_info.base_pipeline_index = -1;
+        if let Some(base) = info.base {
+            create_info.flags |= vk::PipelineCreateFlags::DERIVATIVE;
+            create_info.base_pipeline_handle = base.inner.handle;
         }
 
-        let handle = unsafe {
-            device
-                .handle
-                .create_graphics_pipelines(vk::PipelineCache::null(), &[create_info], None)
-                .map_err(|(_, err)| crate::GPUError::from(err))?[0]
-        };
-
-        if let Some(label) = &info.label {
-            unsafe { device.attach_label(handle, label) };
+        if let Some(local_read) = &info.local_read {
+            *locations = local_read.locations(info.color_formats.len());
+            *input_indices = local_read.input_indices(info.color_formats.len());
+            create_info = create_info.push_next(locations).push_next(input_indices);
         }
 
-        Ok(RenderPipelineImpl {
-            handle,
-            layout,
-            device,
-        })
-    }
+        if info.depth_format.is_some()
+            || info.depth_test
+            || info.depth_write
+            || info.stencil_format.is_some()
+            || info.stencil_test
+        {
+            create_info = create_info.depth_stencil_state(depth_stencil);
+        }
 
-    pub fn new(device: Arc<DeviceImpl>, info: &RenderPipelineInfo) -> RenderPipelineImpl {
-        Self::try_new(device, info).expect("Create render pipeline")
+        create_info
     }
 }
 
-impl ComputePipelineImpl {
-    pub fn new(device: Arc<DeviceImpl>, info: &ComputePipelineInfo<'_>) -> ComputePipelineImpl {
+fn create_layouts<'a>(
+    device: &DeviceImpl,
+    layouts: impl Iterator<
+        Item = (
+            &'a [&'a DescriptorSetLayout],
+            Option<u32>,
+            vk::ShaderStageFlags,
+        ),
+    >,
+) -> Result<Vec<vk::PipelineLayout>, crate::GPUError> {
+    let mut created = Vec::new();
+    for (descriptor_layouts, push_constant_size, stages) in layouts {
         let mut push_constant_ranges = Vec::new();
-        if let Some(size) = info.push_constant_size {
+        if let Some(size) = push_constant_size {
             push_constant_ranges.push(
                 vk::PushConstantRange::default()
-                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .stage_flags(stages)
                     .size(size),
             );
         }
 
-        let layouts = info
-            .descriptor_layouts
+        let set_layouts = descriptor_layouts
             .iter()
             .map(|l| l.handle)
             .collect::<Vec<_>>();
 
         let layout_info = vk::PipelineLayoutCreateInfo::default()
-            .set_layouts(&layouts)
+            .set_layouts(&set_layouts)
             .push_constant_ranges(&push_constant_ranges);
 
-        let layout = unsafe {
-            device
-                .handle
-                .create_pipeline_layout(&layout_info, None)
-                .unwrap()
-        };
+        match unsafe { device.handle.create_pipeline_layout(&layout_info, None) } {
+            Ok(layout) => created.push(layout),
+            Err(err) => {
+                destroy_layouts(device, &created);
+                return Err(crate::GPUError::from(err));
+            }
+        }
+    }
+    Ok(created)
+}
 
-        let stage_name = std::ffi::CString::new(info.shader.name).unwrap();
-        let shader = info
-            .shader
-            .shader
-            .expect("ComputePipelineInfo::shader must be set");
-        let stage = vk::PipelineShaderStageCreateInfo::default()
-            .stage(vk::ShaderStageFlags::COMPUTE)
-            .module(shader.module.handle)
-            .name(&stage_name);
-
-        let create_info = vk::ComputePipelineCreateInfo::default()
-            .stage(stage)
-            .layout(layout);
-
-        let handle = unsafe {
-            let cache = info.cache.unwrap_or(vk::PipelineCache::null());
-            device
-                .handle
-                .create_compute_pipelines(cache, &[create_info], None)
-                .unwrap()[0]
-        };
+fn destroy_layouts(device: &DeviceImpl, layouts: &[vk::PipelineLayout]) {
+    for &layout in layouts {
+        unsafe { device.handle.destroy_pipeline_layout(layout, None) };
+    }
+}
 
-        if let Some(label) = &info.label {
-            unsafe { device.attach_label(handle, label) };
+fn destroy_pipelines(device: &DeviceImpl, pipelines: &[vk::Pipeline]) {
+    for &pipeline in pipelines {
+        if pipeline != vk::Pipeline::null() {
+            unsafe { device.handle.destroy_pipeline(pipeline, None) };
         }
+    }
+}
+
+fn is_strip_topology(topology: vk::PrimitiveTopology) -> bool {
+    matches!(
+        topology,
+        vk::PrimitiveTopology::LINE_STRIP
+            | vk::PrimitiveTopology::TRIANGLE_STRIP
+            | vk::PrimitiveTopology::TRIANGLE_FAN
+            | vk::PrimitiveTopology::LINE_STRIP_WITH_ADJACENCY
+            | vk::PrimitiveTopology::TRIANGLE_STRIP_WITH_ADJACENCY
+    )
+}
 
-        ComputePipelineImpl {
-            handle,
-            layout,
-            device,
+fn color_blend_attachments(
+    info: &RenderPipelineInfo<'_>,
+) -> Result<Vec<vk::PipelineColorBlendAttachmentState>, crate::GPUError> {
+    let mut states = info.blend_states.as_ref().map_or_else(
+        || {
+            vec![
+                vk::PipelineColorBlendAttachmentState::default()
+                    .color_write_mask(vk::ColorComponentFlags::RGBA)
+                    .blend_enable(false),
+            ]
+        },
+        |&states| states.to_vec(),
+    );
+
+    if let Some(masks) = info.color_write_masks {
+        if masks.len() != info.color_formats.len() {
+            return Err(crate::GPUError::Validation(
+                "color_write_masks must have one entry per color format",
+            ));
         }
+        if info.blend_states.is_none() {
+            states.resize(masks.len(), states[0]);
+        } else if states.len() != masks.len() {
+            return Err(crate::GPUError::Validation(
+                "blend_states and color_write_masks must have the same length",
+            ));
+        }
+        for (state, &mask) in states.iter_mut().zip(masks) {
+            state.color_write_mask = mask;
+        }
+    }
+
+    Ok(states)
+}
+
+impl ComputePipelineImpl {
+    pub fn new(device: Arc<DeviceImpl>, info: &ComputePipelineInfo<'_>) -> ComputePipelineImpl {
+        Self::try_new_batch(device, std::slice::from_ref(info))
+            .expect("Create compute pipeline")
+            .remove(0)
+    }
+
+    /// Creates all pipelines with a single `vkCreateComputePipelines` call.
+    ///
+    /// All infos must use the same `cache`.
+    pub fn try_new_batch(
+        device: Arc<DeviceImpl>,
+        infos: &[ComputePipelineInfo<'_>],
+    ) -> Result<Vec<ComputePipelineImpl>, crate::GPUError> {
+        if infos.is_empty() {
+            return Ok(Vec::new());
+        }
+        let cache = infos.first().and_then(|info| info.cache);
+        if infos.iter().any(|info| info.cache != cache) {
+            return Err(crate::GPUError::Validation(
+                "batched compute pipelines must share the same pipeline cache",
+            ));
+        }
+        for info in infos.iter().filter(|info| info.validate_layouts) {
+            validate_shader_layouts(&info.shader, info.descriptor_layouts)?;
+        }
+
+        let stage_names = infos
+            .iter()
+            .map(|info| std::ffi::CString::new(info.shader.name).unwrap())
+            .collect::<Vec<_>>();
+
+        let layouts = create_layouts(
+            &device,
+            infos.iter().map(|info| {
+                (
+                    info.descriptor_layouts,
+                    info.push_constant_size,
+                    vk::ShaderStageFlags::COMPUTE,
+                )
+            }),
+        )?;
+
+        let create_infos = infos
+            .iter()
+            .zip(&stage_names)
+            .zip(&layouts)
+            .map(|((info, stage_name), &layout)| {
+                let shader = info
+                    .shader
+                    .shader
+                    .expect("ComputePipelineInfo::shader must be set");
+                let stage = vk::PipelineShaderStageCreateInfo::default()
+                    .stage(vk::ShaderStageFlags::COMPUTE)
+                    .module(shader.module.handle)
+                    .name(stage_name);
+
+                let flags = if info.dispatch_base {
+                    vk::PipelineCreateFlags::DISPATCH_BASE
+                } else {
+                    vk::PipelineCreateFlags::empty()
+                };
+                vk::ComputePipelineCreateInfo::default()
+                    .flags(flags)
+                    .stage(stage)
+                    .layout(layout)
+            })
+            .collect::<Vec<_>>();
+
+        let handles = unsafe {
+            device.handle.create_compute_pipelines(
+                cache.unwrap_or(vk::PipelineCache::null()),
+                &create_infos,
+                None,
+            )
+        };
+        let handles = match handles {
+            Ok(handles) => handles,
+            Err((handles, err)) => {
+                destroy_pipelines(&device, &handles);
+                destroy_layouts(&device, &layouts);
+                return Err(crate::GPUError::from(err));
+            }
+        };
+
+        Ok(handles
+            .into_iter()
+            .zip(layouts)
+            .zip(infos)
+            .map(|((handle, layout), info)| {
+                if let Some(label) = &info.label {
+                    unsafe {
+                        device.attach_label(handle, label);
+                        device.attach_label(layout, label);
+                    }
+                }
+                ComputePipelineImpl {
+                    handle,
+                    layout,
+                    dispatch_base: info.dispatch_base,
+                    push_constant_size: info.push_constant_size.unwrap_or(0),
+                    descriptor_layout_count: info.descriptor_layouts.len() as u32,
+                    device: device.clone(),
+                }
+            })
+            .collect())
     }
 }
 
@@ -305,7 +687,9 @@ impl Device {
         info: &RenderPipelineInfo<'_>,
     ) -> Result<RenderPipeline, crate::GPUError> {
         let inner = RenderPipelineImpl::try_new(self.inner.clone(), info)?;
-        Ok(RenderPipeline { inner })
+        Ok(RenderPipeline {
+            inner: Arc::new(inner),
+        })
     }
 
     pub fn create_render_pipeline(&self, info: &RenderPipelineInfo<'_>) -> RenderPipeline {
@@ -313,9 +697,52 @@ impl Device {
             .expect("Create render pipeline")
     }
 
+    /// Creates all render pipelines with a single driver call.
+    pub fn try_create_render_pipelines(
+        &self,
+        infos: &[RenderPipelineInfo<'_>],
+    ) -> Result<Vec<RenderPipeline>, crate::GPUError> {
+        let pipelines = RenderPipelineImpl::try_new_batch(self.inner.clone(), infos)?;
+        Ok(pipelines
+            .into_iter()
+            .map(|inner| RenderPipeline {
+                inner: Arc::new(inner),
+            })
+            .collect())
+    }
+
+    pub fn create_render_pipelines(&self, infos: &[RenderPipelineInfo<'_>]) -> Vec<RenderPipeline> {
+        self.try_create_render_pipelines(infos)
+            .expect("Create render pipelines")
+    }
+
     pub fn create_compute_pipeline(&self, info: &ComputePipelineInfo<'_>) -> ComputePipeline {
         let inner = ComputePipelineImpl::new(self.inner.clone(), info);
-        ComputePipeline { inner }
+        ComputePipeline {
+            inner: Arc::new(inner),
+        }
+    }
+
+    /// Creates all compute pipelines with a single driver call; all infos must share one `cache`.
+    pub fn try_create_compute_pipelines(
+        &self,
+        infos: &[ComputePipelineInfo<'_>],
+    ) -> Result<Vec<ComputePipeline>, crate::GPUError> {
+        let pipelines = ComputePipelineImpl::try_new_batch(self.inner.clone(), infos)?;
+        Ok(pipelines
+            .into_iter()
+            .map(|inner| ComputePipeline {
+                inner: Arc::new(inner),
+            })
+            .collect())
+    }
+
+    pub fn create_compute_pipelines(
+        &self,
+        infos: &[ComputePipelineInfo<'_>],
+    ) -> Vec<ComputePipeline> {
+        self.try_create_compute_pipelines(infos)
+            .expect("Create compute pipelines")
     }
 }
 
@@ -340,3 +767,226 @@ impl Drop for RenderPipelineImpl {
         }
     }
 }
+
+/// Checks the descriptor bindings reflected from `entry`'s SPIR-V against `layouts`.
+fn validate_shader_layouts(
+    entry: &ShaderEntry<'_>,
+    layouts: &[&DescriptorSetLayout],
+) -> Result<(), crate::GPUError> {
+    match entry
+        .shader
+        .and_then(|shader| shader.module.reflection.as_ref())
+    {
+        Some(reflection) => reflection.validate_layouts(entry.name, layouts),
+        None => Ok(()),
+    }
+}
+
+/// Checks `info` against the enabled device features and adapter limits.
+fn validate_render_pipeline(
+    info: &RenderPipelineInfo<'_>,
+    features: &DeviceFeatures,
+    max_viewports: u32,
+) -> Result<(), crate::GPUError> {
+    if info.primitive_restart && !is_strip_topology(info.topology) {
+        return Err(crate::GPUError::Validation(
+            "primitive_restart requires a strip or fan topology",
+        ));
+    }
+    if info.polygon != vk::PolygonMode::FILL && !features.fill_mode_non_solid {
+        return Err(crate::GPUError::Validation(
+            "LINE and POINT polygon modes require the fill_mode_non_solid device feature",
+        ));
+    }
+    if info.local_read.is_some() && !features.dynamic_rendering_local_read {
+        return Err(crate::GPUError::Validation(
+            "local_read requires the dynamic_rendering_local_read device feature",
+        ));
+    }
+    if info.viewport_count == 0 {
+        return Err(crate::GPUError::Validation(
+            "viewport_count must be at least 1",
+        ));
+    }
+    if info.viewport_count > 1 && !features.multi_viewport {
+        return Err(crate::GPUError::Validation(
+            "viewport_count > 1 requires the multi_viewport device feature",
+        ));
+    }
+    if info.viewport_count > max_viewports {
+        return Err(crate::GPUError::Validation(
+            "viewport_count exceeds the adapter's max_viewports",
+        ));
+    }
+    if info.base.is_some_and(|base| !base.inner.allow_derivatives) {
+        return Err(crate::GPUError::Validation(
+            "base pipeline must be created with allow_derivatives",
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn color_write_masks_apply_per_target() {
+        let formats = [vk::Format::R8G8B8A8_UNORM, vk::Format::R16G16B16A16_SFLOAT];
+        let rgb =
+            vk::ColorComponentFlags::R | vk::ColorComponentFlags::G | vk::ColorComponentFlags::B;
+        let masks = [rgb, vk::ColorComponentFlags::A];
+        let states = color_blend_attachments(&RenderPipelineInfo {
+            color_formats: &formats,
+            color_write_masks: Some(&masks),
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert_eq!(states.len(), 2);
+        assert_eq!(states[0].color_write_mask, rgb);
+        assert_eq!(states[1].color_write_mask, vk::ColorComponentFlags::A);
+        assert_eq!(states[1].blend_enable, vk::FALSE);
+    }
+
+    #[test]
+    fn color_write_masks_must_match_color_formats() {
+        let formats = [vk::Format::R8G8B8A8_UNORM];
+        let masks = [vk::ColorComponentFlags::R, vk::ColorComponentFlags::A];
+        let result = color_blend_attachments(&RenderPipelineInfo {
+            color_formats: &formats,
+            color_write_masks: Some(&masks),
+            ..Default::default()
+        });
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn non_fill_polygon_modes_need_fill_mode_non_solid() {
+        let info = RenderPipelineInfo {
+            polygon: vk::PolygonMode::LINE,
+            ..Default::default()
+        };
+        let features = DeviceFeatures::minimal();
+
+        assert!(validate_render_pipeline(&info, &features, 16).is_err());
+        assert!(validate_render_pipeline(&info, &features.fill_mode_non_solid(true), 16).is_ok());
+        assert!(validate_render_pipeline(&RenderPipelineInfo::default(), &features, 16).is_ok());
+    }
+
+    #[test]
+    #[ignore = "requires a Vulkan device"]
+    fn blend_variants_derive_from_a_base() {
+        use crate::{QueueFlags, ShaderSource, device::test_device};
+        use std::time::{Duration, Instant};
+
+        const SHADER: &str = r#"
+@vertex
+fn vs(@builtin(vertex_index) index: u32) -> @builtin(position) vec4f {
+    return vec4f(f32(index), 0.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs() -> @location(0) vec4f {
+    return vec4f(1.0);
+}
+"#;
+
+        let (device, _queue) = test_device(QueueFlags::GRAPHICS);
+        let shader = device
+            .create_shader(None, ShaderSource::Wgsl(SHADER))
+            .unwrap();
+
+        let factors = [
+            vk::BlendFactor::ZERO,
+            vk::BlendFactor::ONE,
+            vk::BlendFactor::SRC_COLOR,
+            vk::BlendFactor::ONE_MINUS_SRC_COLOR,
+            vk::BlendFactor::DST_COLOR,
+            vk::BlendFactor::ONE_MINUS_DST_COLOR,
+            vk::BlendFactor::SRC_ALPHA,
+            vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+            vk::BlendFactor::DST_ALPHA,
+            vk::BlendFactor::ONE_MINUS_DST_ALPHA,
+        ];
+        let ops = [
+            vk::BlendOp::ADD,
+            vk::BlendOp::SUBTRACT,
+            vk::BlendOp::REVERSE_SUBTRACT,
+            vk::BlendOp::MIN,
+            vk::BlendOp::MAX,
+        ];
+        let variants = factors
+            .iter()
+            .flat_map(|&factor| {
+                ops.iter().map(move |&op| {
+                    [vk::PipelineColorBlendAttachmentState::default()
+                        .blend_enable(true)
+                        .src_color_blend_factor(factor)
+                        .dst_color_blend_factor(vk::BlendFactor::ONE)
+                        .color_blend_op(op)
+                        .color_write_mask(vk::ColorComponentFlags::RGBA)]
+                })
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(variants.len(), 50);
+
+        let formats = [vk::Format::R8G8B8A8_UNORM];
+        let info = |blend, base, allow_derivatives| RenderPipelineInfo {
+            vertex_shader: shader.entry("vs"),
+            fragment_shader: shader.entry("fs"),
+            color_formats: &formats,
+            blend_states: Some(blend),
+            allow_derivatives,
+            base,
+            ..Default::default()
+        };
+
+        let plain = device.create_render_pipeline(&info(&variants[0], None, false));
+        assert!(
+            device
+                .try_create_render_pipeline(&info(&variants[1], Some(&plain), false))
+                .is_err()
+        );
+
+        let base = device.create_render_pipeline(&info(&variants[0], None, true));
+        let derived = device
+            .try_create_render_pipelines(
+                &variants
+                    .iter()
+                    .map(|blend| info(blend, Some(&base), false))
+                    .collect::<Vec<_>>(),
+            )
+            .unwrap();
+        assert_eq!(derived.len(), variants.len());
+
+        // Create every variant once so both arms see warm driver caches, then alternate which
+        // arm goes first for each variant.
+        for blend in &variants {
+            device.create_render_pipeline(&info(blend, None, false));
+        }
+        let time = |blend, base| {
+            let start = Instant::now();
+            let pipeline = device.create_render_pipeline(&info(blend, base, false));
+            let elapsed = start.elapsed();
+            drop(pipeline);
+            elapsed
+        };
+        let mut plain_time = Duration::ZERO;
+        let mut derived_time = Duration::ZERO;
+        for (i, blend) in variants.iter().enumerate() {
+            if i % 2 == 0 {
+                plain_time += time(blend, None);
+                derived_time += time(blend, Some(&base));
+            } else {
+                derived_time += time(blend, Some(&base));
+                plain_time += time(blend, None);
+            }
+        }
+        eprintln!(
+            "{} blend variants: plain {plain_time:?}, derived {derived_time:?}",
+            variants.len()
+        );
+    }
+}
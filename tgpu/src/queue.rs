@@ -1,10 +1,13 @@
-use std::sync::{Arc, atomic::AtomicU64};
+use std::sync::{
+    Arc,
+    atomic::{AtomicU64, Ordering},
+};
 
 use ash::vk;
 use parking_lot::Mutex;
 
 use crate::{
-    CommandPools, GPUError, Semaphore,
+    Buffer, BufferUses, CommandPools, GPUError, Image, Semaphore, SubmitToken,
     raw::{AdapterImpl, InstanceImpl, RawDevice},
 };
 
@@ -45,6 +48,156 @@ impl Queue {
     pub fn lock(&self) -> parking_lot::lock_api::MutexGuard<'_, parking_lot::RawMutex, ()> {
         self.state.lock()
     }
+
+    pub fn flags(&self) -> vk::QueueFlags {
+        self.inner.info.flags
+    }
+
+    /// Raw `VkQueue`, for interop with other Vulkan libraries.
+    ///
+    /// Submitting through it bypasses the queue lock and the submission timeline; hold `lock`
+    /// while doing so.
+    pub fn raw_handle(&self) -> vk::Queue {
+        self.inner.handle
+    }
+
+    pub fn family_index(&self) -> u32 {
+        self.inner.info.family_index
+    }
+
+    /// Index of this queue within its family.
+    pub fn queue_index(&self) -> u32 {
+        self.inner.info.queue_index
+    }
+
+    /// Whether the queue family supports every capability in `flags`.
+    pub fn supports(&self, flags: vk::QueueFlags) -> bool {
+        self.inner.info.flags.contains(flags)
+    }
+
+    /// Timeline semaphore signaled with each submission index returned by `submit`.
+    pub fn timeline(&self) -> &Semaphore {
+        &self.timeline
+    }
+
+    /// Wraps a submission index returned by `submit` or a sparse bind in a waitable token.
+    pub fn token(&self, submission: u64) -> SubmitToken {
+        SubmitToken {
+            timeline: self.timeline.clone(),
+            value: submission,
+        }
+    }
+
+    /// Blocks until the submission with index `submission` has finished on the GPU.
+    pub fn wait_for(&self, submission: u64) -> Result<(), GPUError> {
+        self.timeline.wait(submission, None).map(|_| ())
+    }
+
+    /// Returns whether the submission with index `submission` has finished on the GPU.
+    pub fn is_complete(&self, submission: u64) -> bool {
+        self.timeline.get() >= submission
+    }
+
+    /// Binds memory to regions of a sparse image, returning a submission index like `submit`.
+    ///
+    /// `binds` update individual mip tiles of a `SPARSE_RESIDENCY` image; `opaque` binds cover
+    /// the mip tail and whole `SPARSE_BINDING` images.
+    pub fn bind_sparse(
+        &self,
+        image: &Image,
+        binds: &[vk::SparseImageMemoryBind],
+        opaque: &[vk::SparseMemoryBind],
+    ) -> Result<u64, GPUError> {
+        if !self.supports(vk::QueueFlags::SPARSE_BINDING) {
+            return Err(GPUError::Validation(
+                "bind_sparse requires a queue with SPARSE_BINDING support",
+            ));
+        }
+        let flags = image.inner.flags;
+        if !flags.contains(vk::ImageCreateFlags::SPARSE_BINDING) {
+            return Err(GPUError::Validation(
+                "bind_sparse requires an image created with ImageFlags::SPARSE_BINDING",
+            ));
+        }
+        if !binds.is_empty() && !flags.contains(vk::ImageCreateFlags::SPARSE_RESIDENCY) {
+            return Err(GPUError::Validation(
+                "sparse image binds require an image created with ImageFlags::SPARSE_RESIDENCY",
+            ));
+        }
+
+        let image_binds = [vk::SparseImageMemoryBindInfo::default()
+            .image(image.inner.handle)
+            .binds(binds)];
+        let opaque_binds = [vk::SparseImageOpaqueMemoryBindInfo::default()
+            .image(image.inner.handle)
+            .binds(opaque)];
+        let mut info = vk::BindSparseInfo::default();
+        if !binds.is_empty() {
+            info = info.image_binds(&image_binds);
+        }
+        if !opaque.is_empty() {
+            info = info.image_opaque_binds(&opaque_binds);
+        }
+        self.submit_bind_sparse(info)
+    }
+
+    /// Binds memory to ranges of a `BufferUses::SPARSE_BINDING` buffer, returning a submission
+    /// index like `submit`.
+    ///
+    /// Offsets and sizes must be multiples of the buffer's memory alignment, and together the
+    /// binds may cover at most `Buffer::allocation_size` bytes.
+    pub fn bind_sparse_buffer(
+        &self,
+        buffer: &Buffer,
+        binds: &[vk::SparseMemoryBind],
+    ) -> Result<u64, GPUError> {
+        if !self.supports(vk::QueueFlags::SPARSE_BINDING) {
+            return Err(GPUError::Validation(
+                "bind_sparse_buffer requires a queue with SPARSE_BINDING support",
+            ));
+        }
+        if !buffer.uses.contains(BufferUses::SPARSE_BINDING) {
+            return Err(GPUError::Validation(
+                "bind_sparse_buffer requires a buffer created with BufferUses::SPARSE_BINDING",
+            ));
+        }
+
+        let buffer_binds = [vk::SparseBufferMemoryBindInfo::default()
+            .buffer(buffer.inner.handle)
+            .binds(binds)];
+        let mut info = vk::BindSparseInfo::default();
+        if !binds.is_empty() {
+            info = info.buffer_binds(&buffer_binds);
+        }
+        self.submit_bind_sparse(info)
+    }
+
+    /// Submits `info` signaling the queue timeline; the submission index is only consumed
+    /// once the driver accepts the bind.
+    fn submit_bind_sparse(&self, info: vk::BindSparseInfo<'_>) -> Result<u64, GPUError> {
+        let _lock = self.lock();
+        let submission = self.submission_counter.load(Ordering::SeqCst);
+
+        let signal_semaphores = [self.timeline.inner.handle];
+        let signal_values = [submission];
+        let mut timeline_info =
+            vk::TimelineSemaphoreSubmitInfo::default().signal_semaphore_values(&signal_values);
+        let info = info
+            .signal_semaphores(&signal_semaphores)
+            .push_next(&mut timeline_info);
+
+        unsafe {
+            self.inner
+                .device
+                .handle
+                .queue_bind_sparse(self.inner.handle, &[info], vk::Fence::null())
+                .map_err(|e| self.inner.device.vk_error(e))?;
+        }
+
+        self.submission_counter
+            .store(submission + 1, Ordering::SeqCst);
+        Ok(submission)
+    }
 }
 
 impl QueueImpl {
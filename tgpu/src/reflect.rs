@@ -0,0 +1,421 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+use ash::vk;
+
+use crate::{DescriptorBinding, DescriptorSetLayout, DescriptorType, GPUError};
+
+const OP_ENTRY_POINT: u32 = 15;
+const OP_FUNCTION: u32 = 54;
+const OP_FUNCTION_END: u32 = 56;
+const OP_FUNCTION_CALL: u32 = 57;
+const OP_TYPE_IMAGE: u32 = 25;
+const OP_TYPE_SAMPLER: u32 = 26;
+const OP_TYPE_SAMPLED_IMAGE: u32 = 27;
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_RUNTIME_ARRAY: u32 = 29;
+const OP_TYPE_STRUCT: u32 = 30;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_VARIABLE: u32 = 59;
+const OP_DECORATE: u32 = 71;
+
+const DECORATION_BLOCK: u32 = 2;
+const DECORATION_BUFFER_BLOCK: u32 = 3;
+const DECORATION_BINDING: u32 = 33;
+const DECORATION_DESCRIPTOR_SET: u32 = 34;
+
+const STORAGE_UNIFORM_CONSTANT: u32 = 0;
+const STORAGE_UNIFORM: u32 = 2;
+const STORAGE_STORAGE_BUFFER: u32 = 12;
+
+const DIM_BUFFER: u32 = 5;
+
+/// Descriptor bindings declared by a SPIR-V module, used to check pipeline layouts.
+#[derive(Debug, Default)]
+pub(crate) struct ShaderReflection {
+    /// Each entry point with the global variables its static call tree references.
+    entry_points: Vec<(String, HashSet<u32>)>,
+    bindings: Vec<ReflectedBinding>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ReflectedBinding {
+    variable: u32,
+    set: u32,
+    binding: u32,
+    /// `None` for descriptors `DescriptorType` can't express, e.g. texel buffers.
+    ty: Option<DescriptorType>,
+}
+
+/// Global variables a function references directly and the functions it calls.
+#[derive(Debug, Default)]
+struct FunctionUses {
+    globals: HashSet<u32>,
+    callees: Vec<u32>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum TypeInfo {
+    Image { dim: u32, sampled: u32 },
+    Sampler,
+    SampledImage,
+    Array(u32),
+    Struct,
+    Pointer(u32),
+}
+
+impl ShaderReflection {
+    /// Returns `None` if `spirv` is malformed; the header must already be validated.
+    pub(crate) fn parse(spirv: &[u32]) -> Option<Self> {
+        let mut types = HashMap::new();
+        let mut decorations = HashMap::<u32, (Option<u32>, Option<u32>)>::new();
+        let mut blocks = HashMap::new();
+        let mut variables = Vec::new();
+        let mut globals = HashSet::new();
+        let mut entry_points = Vec::new();
+        let mut functions = HashMap::<u32, FunctionUses>::new();
+        let mut current = None;
+
+        let mut words = spirv.get(5..)?;
+        while let Some(&first) = words.first() {
+            let count = (first >> 16) as usize;
+            if count == 0 || count > words.len() {
+                return None;
+            }
+            let (inst, rest) = words.split_at(count);
+            words = rest;
+            let operands = &inst[1..];
+
+            match first & 0xffff {
+                OP_ENTRY_POINT if operands.len() >= 3 => {
+                    let (name, _) = literal_string(&operands[2..])?;
+                    entry_points.push((name, operands[1]));
+                }
+                OP_FUNCTION if operands.len() >= 2 => {
+                    current = Some(operands[1]);
+                    functions.entry(operands[1]).or_default();
+                }
+                OP_FUNCTION_END => current = None,
+                // Global variables are declared before any function body, so every id a
+                // function can reference is already in `globals`.
+                opcode if current.is_some() => {
+                    let uses = functions.entry(current?).or_default();
+                    if opcode == OP_FUNCTION_CALL && operands.len() >= 3 {
+                        uses.callees.push(operands[2]);
+                    }
+                    uses.globals
+                        .extend(operands.iter().filter(|id| globals.contains(*id)));
+                }
+                OP_DECORATE if operands.len() >= 2 => {
+                    let target = operands[0];
+                    match (operands[1], operands.get(2)) {
+                        (DECORATION_DESCRIPTOR_SET, Some(&set)) => {
+                            decorations.entry(target).or_default().0 = Some(set)
+                        }
+                        (DECORATION_BINDING, Some(&binding)) => {
+                            decorations.entry(target).or_default().1 = Some(binding)
+                        }
+                        (DECORATION_BLOCK, _) => {
+                            blocks.insert(target, false);
+                        }
+                        (DECORATION_BUFFER_BLOCK, _) => {
+                            blocks.insert(target, true);
+                        }
+                        _ => {}
+                    }
+                }
+                OP_TYPE_IMAGE if operands.len() >= 7 => {
+                    let info = TypeInfo::Image {
+                        dim: operands[2],
+                        sampled: operands[6],
+                    };
+                    types.insert(operands[0], info);
+                }
+                OP_TYPE_SAMPLER if !operands.is_empty() => {
+                    types.insert(operands[0], TypeInfo::Sampler);
+                }
+                OP_TYPE_SAMPLED_IMAGE if !operands.is_empty() => {
+                    types.insert(operands[0], TypeInfo::SampledImage);
+                }
+                OP_TYPE_ARRAY | OP_TYPE_RUNTIME_ARRAY if operands.len() >= 2 => {
+                    types.insert(operands[0], TypeInfo::Array(operands[1]));
+                }
+                OP_TYPE_STRUCT if !operands.is_empty() => {
+                    types.insert(operands[0], TypeInfo::Struct);
+                }
+                OP_TYPE_POINTER if operands.len() >= 3 => {
+                    types.insert(operands[0], TypeInfo::Pointer(operands[2]));
+                }
+                OP_VARIABLE if operands.len() >= 3 => {
+                    globals.insert(operands[1]);
+                    variables.push((operands[0], operands[1], operands[2]));
+                }
+                _ => {}
+            }
+        }
+
+        let bindings = variables
+            .into_iter()
+            .filter_map(|(pointer, variable, storage)| {
+                let (Some(set), Some(binding)) = decorations.get(&variable).copied()? else {
+                    return None;
+                };
+                let ty = match types.get(&pointer) {
+                    Some(TypeInfo::Pointer(pointee)) => {
+                        descriptor_type(&types, &blocks, storage, *pointee)
+                    }
+                    _ => None,
+                };
+                Some(ReflectedBinding {
+                    variable,
+                    set,
+                    binding,
+                    ty,
+                })
+            })
+            .collect();
+
+        let entry_points = entry_points
+            .into_iter()
+            .map(|(name, function)| (name, reachable_globals(&functions, function)))
+            .collect();
+
+        Some(Self {
+            entry_points,
+            bindings,
+        })
+    }
+
+    /// Checks that every descriptor `entry` uses exists in `layouts` with a matching type.
+    pub(crate) fn validate_layouts(
+        &self,
+        entry: &str,
+        layouts: &[&DescriptorSetLayout],
+    ) -> Result<(), GPUError> {
+        let layouts = layouts
+            .iter()
+            .map(|layout| layout.bindings.as_slice())
+            .collect::<Vec<_>>();
+        self.check_layouts(entry, &layouts)
+            .map_err(GPUError::ShaderLayout)
+    }
+
+    fn check_layouts(&self, entry: &str, layouts: &[&[DescriptorBinding]]) -> Result<(), String> {
+        let used = self
+            .entry_points
+            .iter()
+            .find(|(name, _)| name == entry)
+            .map(|(_, used)| used);
+
+        let mut errors = String::new();
+        for reflected in &self.bindings {
+            if used.is_some_and(|used| !used.contains(&reflected.variable)) {
+                continue;
+            }
+            let Some(ty) = reflected.ty else {
+                continue;
+            };
+            let (set, binding) = (reflected.set, reflected.binding);
+
+            let Some(layout) = layouts.get(set as usize) else {
+                let _ = writeln!(
+                    errors,
+                    "`{entry}` uses set {set} binding {binding} ({ty:?}), but the pipeline has \
+                     only {} descriptor layouts",
+                    layouts.len()
+                );
+                continue;
+            };
+            match layout.iter().find(|b| b.binding == binding) {
+                None => {
+                    let _ = writeln!(
+                        errors,
+                        "`{entry}` uses set {set} binding {binding} ({ty:?}), which is missing \
+                         from descriptor layout {set}"
+                    );
+                }
+                Some(declared) if !same_descriptor_type(ty, declared.ty) => {
+                    let _ = writeln!(
+                        errors,
+                        "`{entry}` set {set} binding {binding}: shader expects {ty:?}, layout \
+                         has {:?}",
+                        declared.ty
+                    );
+                }
+                Some(_) => {}
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.trim_end().to_string())
+        }
+    }
+}
+
+fn same_descriptor_type(a: DescriptorType, b: DescriptorType) -> bool {
+    vk::DescriptorType::from(a) == vk::DescriptorType::from(b)
+}
+
+/// Global variables referenced by `function` or anything it calls.
+///
+/// Any operand naming a global counts as a use, so this over-approximates only if a literal
+/// operand happens to equal a global's id.
+fn reachable_globals(functions: &HashMap<u32, FunctionUses>, function: u32) -> HashSet<u32> {
+    let mut used = HashSet::new();
+    let mut visited = HashSet::new();
+    let mut stack = vec![function];
+    while let Some(function) = stack.pop() {
+        if !visited.insert(function) {
+            continue;
+        }
+        if let Some(uses) = functions.get(&function) {
+            used.extend(&uses.globals);
+            stack.extend(&uses.callees);
+        }
+    }
+    used
+}
+
+fn descriptor_type(
+    types: &HashMap<u32, TypeInfo>,
+    blocks: &HashMap<u32, bool>,
+    storage: u32,
+    mut ty: u32,
+) -> Option<DescriptorType> {
+    while let Some(TypeInfo::Array(element)) = types.get(&ty) {
+        ty = *element;
+    }
+    match (storage, types.get(&ty)?) {
+        (STORAGE_STORAGE_BUFFER, TypeInfo::Struct) => Some(DescriptorType::StorageBuffer),
+        (STORAGE_UNIFORM, TypeInfo::Struct) => match blocks.get(&ty)? {
+            true => Some(DescriptorType::StorageBuffer),
+            false => Some(DescriptorType::UniformBuffer),
+        },
+        (STORAGE_UNIFORM_CONSTANT, TypeInfo::Image { dim, .. }) if *dim == DIM_BUFFER => None,
+        (STORAGE_UNIFORM_CONSTANT, TypeInfo::Image { sampled: 2, .. }) => {
+            Some(DescriptorType::StorageImage)
+        }
+        (STORAGE_UNIFORM_CONSTANT, TypeInfo::Image { .. }) => Some(DescriptorType::SampledImage),
+        (STORAGE_UNIFORM_CONSTANT, TypeInfo::Sampler) => Some(DescriptorType::Sampler),
+        (STORAGE_UNIFORM_CONSTANT, TypeInfo::SampledImage) => {
+            Some(DescriptorType::CombinedImageSampler)
+        }
+        _ => None,
+    }
+}
+
+/// Splits a nul-terminated SPIR-V literal string from the words that follow it.
+fn literal_string(words: &[u32]) -> Option<(String, &[u32])> {
+    let mut bytes = Vec::new();
+    for (index, word) in words.iter().enumerate() {
+        for byte in word.to_le_bytes() {
+            if byte == 0 {
+                let name = String::from_utf8(bytes).ok()?;
+                return Some((name, &words[index + 1..]));
+            }
+            bytes.push(byte);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shader::WgslShader;
+
+    const SHADER: &str = r#"
+struct Params { scale: f32 }
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read_write> data: array<f32>;
+@group(1) @binding(0) var color: texture_2d<f32>;
+@group(1) @binding(1) var color_sampler: sampler;
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let tint = textureSampleLevel(color, color_sampler, vec2<f32>(0.0), 0.0).x;
+    data[id.x] = data[id.x] * params.scale * tint;
+}
+"#;
+
+    fn reflect() -> ShaderReflection {
+        let spirv = WgslShader::new(SHADER).unwrap().compile().unwrap();
+        ShaderReflection::parse(&spirv).unwrap()
+    }
+
+    fn binding(binding: u32, ty: DescriptorType) -> DescriptorBinding {
+        DescriptorBinding::unique(binding, ty, vk::ShaderStageFlags::COMPUTE)
+    }
+
+    #[test]
+    fn matching_layouts_pass() {
+        let set0 = [
+            binding(0, DescriptorType::UniformBuffer),
+            binding(1, DescriptorType::StorageBuffer),
+        ];
+        let set1 = [
+            binding(0, DescriptorType::SampledImage),
+            binding(1, DescriptorType::Sampler),
+        ];
+
+        assert_eq!(reflect().check_layouts("main", &[&set0, &set1]), Ok(()));
+    }
+
+    #[test]
+    fn mismatches_name_the_binding() {
+        let set0 = [
+            binding(0, DescriptorType::UniformBuffer),
+            binding(1, DescriptorType::UniformBuffer),
+        ];
+        let set1 = [binding(0, DescriptorType::SampledImage)];
+
+        let err = reflect()
+            .check_layouts("main", &[&set0, &set1])
+            .unwrap_err();
+        assert!(
+            err.contains("set 0 binding 1: shader expects StorageBuffer, layout has UniformBuffer")
+        );
+        assert!(err.contains("set 1 binding 1 (Sampler), which is missing"));
+
+        let err = reflect().check_layouts("main", &[&set0]).unwrap_err();
+        assert!(err.contains("only 1 descriptor layouts"));
+    }
+
+    #[test]
+    fn entry_points_only_check_what_they_reach() {
+        let shader = r#"
+@group(0) @binding(0) var<storage, read_write> first: array<f32>;
+@group(1) @binding(0) var<storage, read_write> second: array<f32>;
+
+fn touch_second(index: u32) {
+    second[index] = 2.0;
+}
+
+@compute @workgroup_size(64)
+fn write_first(@builtin(global_invocation_id) id: vec3<u32>) {
+    first[id.x] = 1.0;
+}
+
+@compute @workgroup_size(64)
+fn write_second(@builtin(global_invocation_id) id: vec3<u32>) {
+    touch_second(id.x);
+}
+"#;
+        let spirv = WgslShader::new(shader).unwrap().compile().unwrap();
+        let reflection = ShaderReflection::parse(&spirv).unwrap();
+        let storage = [binding(0, DescriptorType::StorageBuffer)];
+
+        assert_eq!(reflection.check_layouts("write_first", &[&storage]), Ok(()));
+        assert_eq!(
+            reflection.check_layouts("write_second", &[&[], &storage]),
+            Ok(())
+        );
+        let err = reflection
+            .check_layouts("write_second", &[&storage])
+            .unwrap_err();
+        assert!(err.contains("set 1 binding 0"));
+    }
+}
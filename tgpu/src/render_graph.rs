@@ -797,7 +797,7 @@ impl<'a> RenderGraph<'a> {
                 signal_binary: &signal_binary_refs,
                 fence,
                 ..Default::default()
-            });
+            })?;
         }
 
         if let Some((mut swapchain, frame)) = swapchain_to_present {
@@ -979,11 +979,22 @@ impl<'pass, 'graph> RenderPassContext<'pass, 'graph> {
                 })
         });
 
+        let images = self.pass.images;
+        let attachment_views = self
+            .render
+            .colors
+            .iter()
+            .map(|target| target.image)
+            .chain(self.render.depth.as_ref().map(|target| target.image))
+            .map(|image| images[image.0 as usize].view())
+            .collect::<Vec<_>>();
+
         self.pass.recorder.begin_render(
             &RenderInfo {
                 area: self.render.area,
                 colors: &colors,
                 depth,
+                attachment_views: &attachment_views,
                 ..Default::default()
             },
             render,
@@ -1123,7 +1134,7 @@ impl ImageUse {
         match self {
             Self::General { access, .. } => access.transition(),
             Self::ColorAttachment { .. } => ImageLayoutTransition::COLOR,
-            Self::DepthAttachment { .. } => depth_attachment_transition(),
+            Self::DepthAttachment { .. } => ImageLayoutTransition::DEPTH_STENCIL,
         }
     }
 
@@ -1698,6 +1709,9 @@ fn push_image_layout_signature(signature: &mut String, layout: ImageLayout) {
         ImageLayout::Color => signature.push_str("color"),
         ImageLayout::TransferDst => signature.push_str("transfer-dst"),
         ImageLayout::Present => signature.push_str("present"),
+        ImageLayout::DepthStencil => signature.push_str("depth-stencil"),
+        ImageLayout::DepthStencilReadOnly => signature.push_str("depth-stencil-read-only"),
+        ImageLayout::DepthReadOnly => signature.push_str("depth-read-only"),
         ImageLayout::Custom(layout) => {
             let _ = write!(signature, "custom:{}", layout.as_raw());
         }
@@ -2399,16 +2413,6 @@ fn same_image_layout(lhs: ImageLayout, rhs: ImageLayout) -> bool {
     }
 }
 
-fn depth_attachment_transition() -> ImageLayoutTransition {
-    ImageLayoutTransition::custom(
-        vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
-        vk::PipelineStageFlags2::EARLY_FRAGMENT_TESTS
-            | vk::PipelineStageFlags2::LATE_FRAGMENT_TESTS,
-        vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_READ
-            | vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE,
-    )
-}
-
 fn infer_image_aspect(format: vk::Format) -> vk::ImageAspectFlags {
     match format {
         vk::Format::D16_UNORM | vk::Format::D32_SFLOAT | vk::Format::X8_D24_UNORM_PACK32 => {
@@ -2791,7 +2795,10 @@ mod tests {
         }
 
         let render = cached_plan.passes[0].render.as_ref().unwrap();
-        assert_eq!(render.colors[0].desc.clear.float32, [0.0, 0.0, 1.0, 1.0]);
+        assert_eq!(
+            unsafe { render.colors[0].desc.clear.float32 },
+            [0.0, 0.0, 1.0, 1.0]
+        );
     }
 
     #[test]
@@ -5,6 +5,10 @@ pub enum MemoryPreset {
     Upload,
     Readback,
     Dynamic,
+    /// Device-local memory the host writes directly when the adapter exposes it (resizable BAR).
+    /// Falls back to memory that is not host-visible; check `Buffer::is_host_visible` and upload
+    /// through a staging copy in that case.
+    DeviceUpload,
     TransientAttachment,
 }
 
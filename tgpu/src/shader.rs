@@ -1,18 +1,33 @@
-use std::{fs, path::Path, process::Command, sync::Arc};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fs,
+    path::Path,
+    process::Command,
+    sync::{Arc, Weak},
+};
 
 use ash::vk;
 
-use crate::{Device, Label, raw::RawDevice};
+use crate::{Device, GPUError, Label, raw::RawDevice, reflect::ShaderReflection};
+
+const SPIRV_MAGIC: u32 = 0x0723_0203;
+const SPIRV_HEADER_WORDS: usize = 5;
 
 pub enum ShaderSource<'a> {
     Slang(&'a [u8]),
     Glsl(&'a [u8]),
     Wgsl(&'a str),
+    /// WGSL as bytes, e.g. from `include_bytes!`; must be valid UTF-8.
+    WgslBytes(&'a [u8]),
     SpirV(&'a [u32]),
+    /// Raw `.spv` file contents; the length must be a multiple of 4.
+    SpirVBytes(&'a [u8]),
 }
 
 pub struct Shader {
-    pub module: ShaderModule,
+    /// Shared with every other `Shader` created from the same source.
+    pub module: Arc<ShaderModule>,
 }
 
 pub struct ShaderEntry<'a> {
@@ -23,6 +38,8 @@ pub struct ShaderEntry<'a> {
 pub struct ShaderModule {
     pub device: RawDevice,
     pub handle: vk::ShaderModule,
+    /// Descriptor bindings parsed from the SPIR-V, used to check pipeline layouts.
+    pub(crate) reflection: Option<ShaderReflection>,
 }
 
 impl<'a> ShaderEntry<'a> {
@@ -43,38 +60,213 @@ impl Shader {
     }
 }
 
+/// Source kind and bytes; the full source is kept so identical keys mean identical modules.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ShaderCacheKey {
+    kind: u8,
+    source: Box<[u8]>,
+}
+
+impl ShaderCacheKey {
+    fn new(source: &ShaderSource<'_>) -> Option<Self> {
+        let (kind, bytes): (u8, &[u8]) = match source {
+            ShaderSource::Slang(code) => (0, code),
+            ShaderSource::Glsl(_) => return None,
+            ShaderSource::Wgsl(code) => (1, code.as_bytes()),
+            ShaderSource::WgslBytes(code) => (1, code),
+            ShaderSource::SpirV(spirv) => (2, bytemuck::cast_slice(spirv)),
+            ShaderSource::SpirVBytes(bytes) => (2, bytes),
+        };
+        Some(Self {
+            kind,
+            source: Box::from(bytes),
+        })
+    }
+}
+
+/// Modules are held weakly, so a cached module lives only as long as some `Shader` uses it.
+pub type ShaderCache = HashMap<ShaderCacheKey, Weak<ShaderModule>>;
+
 impl Device {
+    /// Compiles `source` unless a live module was already created from the same source, in which
+    /// case that module is shared and `label` is ignored.
     pub fn create_shader<'a>(
         &self,
         label: Option<Label<'a>>,
         source: ShaderSource<'a>,
-    ) -> Result<Shader, String> {
+    ) -> Result<Shader, GPUError> {
+        profile_span!("create_shader", label = ?label);
+        let key = ShaderCacheKey::new(&source);
+        if let Some(key) = &key
+            && let Some(module) = self
+                .inner
+                .shader_cache
+                .lock()
+                .get(key)
+                .and_then(Weak::upgrade)
+        {
+            return Ok(Shader { module });
+        }
+
+        let shader = self.compile_shader(label, source)?;
+        if let Some(key) = key {
+            let mut cache = self.inner.shader_cache.lock();
+            cache.retain(|_, module| module.strong_count() > 0);
+            cache.insert(key, Arc::downgrade(&shader.module));
+        }
+        Ok(shader)
+    }
+
+    /// Forgets all cached modules so the next `create_shader` recompiles, e.g. for hot reload.
+    pub fn clear_shader_cache(&self) {
+        self.inner.shader_cache.lock().clear();
+    }
+
+    fn compile_shader<'a>(
+        &self,
+        label: Option<Label<'a>>,
+        source: ShaderSource<'a>,
+    ) -> Result<Shader, GPUError> {
         match source {
             ShaderSource::Slang(code) => {
-                let spirv = compile_slang_from_bytes(code)?;
-                Ok(self.create_shader_from_spirv(label, &spirv))
+                let spirv = compile_slang_from_bytes(code).map_err(GPUError::ShaderCompile)?;
+                self.create_shader_from_spirv(label, &spirv)
+            }
+            ShaderSource::Glsl(_code) => Err(GPUError::ShaderCompile(
+                "GLSL sources are not supported yet; compile them to SPIR-V first".to_string(),
+            )),
+            ShaderSource::WgslBytes(bytes) => {
+                let code = std::str::from_utf8(bytes).map_err(|e| {
+                    GPUError::ShaderCompile(format!("WGSL source is not valid UTF-8: {e}"))
+                })?;
+                self.compile_shader(label, ShaderSource::Wgsl(code))
             }
-            ShaderSource::Glsl(_code) => unimplemented!(),
             ShaderSource::Wgsl(code) => {
-                let wgsl_shader = WgslShader::new(code)?;
-                let spirv = wgsl_shader.compile().map_err(|e| e.to_string())?;
-                Ok(self.create_shader_from_spirv(label, &spirv))
+                let wgsl_shader = WgslShader::new(code).map_err(GPUError::ShaderCompile)?;
+                let spirv = wgsl_shader
+                    .compile()
+                    .map_err(|e| GPUError::ShaderCompile(e.to_string()))?;
+                self.create_shader_from_spirv(label, &spirv)
+            }
+            ShaderSource::SpirV(spirv) => self.create_shader_from_spirv(label, spirv),
+            ShaderSource::SpirVBytes(bytes) => {
+                let spirv = spirv_words(bytes)?;
+                self.create_shader_from_spirv(label, &spirv)
             }
-            ShaderSource::SpirV(spirv) => Ok(self.create_shader_from_spirv(label, spirv)),
         }
     }
 
+    /// Reads and compiles a shader file, picking the source kind from its extension:
+    /// `.spv`, `.wgsl`, `.slang`, or `.glsl`/`.vert`/`.frag`/`.comp`.
+    ///
+    /// The file name is used as the label.
+    pub fn create_shader_from_file(&self, path: impl AsRef<Path>) -> Result<Shader, GPUError> {
+        let path = path.as_ref();
+        let kind = ShaderFileKind::from_path(path).ok_or_else(|| {
+            GPUError::ShaderCompile(format!("unknown shader file extension: {}", path.display()))
+        })?;
+        if !path.is_file() {
+            return Err(GPUError::ShaderCompile(format!(
+                "shader file does not exist: {}",
+                path.display()
+            )));
+        }
+        let bytes = fs::read(path).map_err(|e| {
+            GPUError::ShaderCompile(format!("failed to read {}: {e}", path.display()))
+        })?;
+
+        let name = path.file_name().map(|name| name.to_string_lossy());
+        let label = name.as_deref().map(Label::Name);
+        match kind {
+            ShaderFileKind::SpirV => self.create_shader(label, ShaderSource::SpirVBytes(&bytes)),
+            ShaderFileKind::Slang => self.create_shader(label, ShaderSource::Slang(&bytes)),
+            ShaderFileKind::Glsl => self.create_shader(label, ShaderSource::Glsl(&bytes)),
+            ShaderFileKind::Wgsl => {
+                let code = std::str::from_utf8(&bytes).map_err(|e| {
+                    GPUError::ShaderCompile(format!(
+                        "{}: WGSL source is not valid UTF-8: {e}",
+                        path.display()
+                    ))
+                })?;
+                self.create_shader(label, ShaderSource::Wgsl(code))
+            }
+        }
+    }
+
+    /// Validates the SPIR-V header, byte-swapping big-endian modules, and creates the module.
     pub fn create_shader_from_spirv<'a>(
         &self,
         label: Option<Label<'a>>,
         spirv: &'a [u32],
-    ) -> Shader {
-        let handle = unsafe { self.inner.create_shader_module_from_spirv(label, spirv) };
+    ) -> Result<Shader, GPUError> {
+        let spirv = validate_spirv(spirv)?;
+        let handle = unsafe { self.inner.create_shader_module_from_spirv(label, &spirv) }?;
         let module = ShaderModule {
             device: self.inner.clone(),
             handle,
+            reflection: ShaderReflection::parse(&spirv),
         };
-        Shader { module }
+        Ok(Shader {
+            module: Arc::new(module),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ShaderFileKind {
+    SpirV,
+    Wgsl,
+    Slang,
+    Glsl,
+}
+
+impl ShaderFileKind {
+    fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "spv" => Some(Self::SpirV),
+            "wgsl" => Some(Self::Wgsl),
+            "slang" => Some(Self::Slang),
+            "glsl" | "vert" | "frag" | "comp" => Some(Self::Glsl),
+            _ => None,
+        }
+    }
+}
+
+fn spirv_words(bytes: &[u8]) -> Result<Cow<'_, [u32]>, GPUError> {
+    if !bytes.len().is_multiple_of(4) {
+        return Err(GPUError::ShaderCompile(format!(
+            "SPIR-V length ({}) is not a multiple of 4 bytes",
+            bytes.len()
+        )));
+    }
+
+    Ok(match bytemuck::try_cast_slice(bytes) {
+        Ok(words) => Cow::Borrowed(words),
+        Err(_) => Cow::Owned(
+            bytes
+                .chunks_exact(4)
+                .map(bytemuck::pod_read_unaligned)
+                .collect(),
+        ),
+    })
+}
+
+fn validate_spirv(spirv: &[u32]) -> Result<Cow<'_, [u32]>, GPUError> {
+    if spirv.len() < SPIRV_HEADER_WORDS {
+        return Err(GPUError::ShaderCompile(format!(
+            "SPIR-V module has {} words, shorter than the {SPIRV_HEADER_WORDS}-word header",
+            spirv.len()
+        )));
+    }
+
+    match spirv[0] {
+        SPIRV_MAGIC => Ok(Cow::Borrowed(spirv)),
+        magic if magic.swap_bytes() == SPIRV_MAGIC => Ok(Cow::Owned(
+            spirv.iter().map(|word| word.swap_bytes()).collect(),
+        )),
+        magic => Err(GPUError::ShaderCompile(format!(
+            "invalid SPIR-V magic number {magic:#010x}"
+        ))),
     }
 }
 
@@ -232,6 +424,75 @@ impl Drop for ShaderModule {
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
+    #[test]
+    fn spirv_header_is_validated() {
+        let header = [SPIRV_MAGIC, 0x0001_0300, 0, 1, 0];
+        assert!(matches!(validate_spirv(&header), Ok(Cow::Borrowed(_))));
+        assert!(validate_spirv(&header[..3]).is_err());
+        assert!(validate_spirv(&[0xdead_beef, 0, 0, 0, 0]).is_err());
+
+        let swapped = header.map(u32::swap_bytes);
+        assert_eq!(validate_spirv(&swapped).unwrap().as_ref(), &header);
+    }
+
+    #[test]
+    fn shader_cache_key_separates_source_kinds() {
+        let code = "main";
+        let slang = ShaderCacheKey::new(&ShaderSource::Slang(code.as_bytes()));
+        let wgsl = ShaderCacheKey::new(&ShaderSource::Wgsl(code));
+        assert_ne!(slang, wgsl);
+        assert_eq!(wgsl, ShaderCacheKey::new(&ShaderSource::Wgsl(code)));
+        assert_eq!(
+            wgsl,
+            ShaderCacheKey::new(&ShaderSource::WgslBytes(code.as_bytes()))
+        );
+
+        let words = [SPIRV_MAGIC];
+        assert_eq!(
+            ShaderCacheKey::new(&ShaderSource::SpirV(&words)),
+            ShaderCacheKey::new(&ShaderSource::SpirVBytes(&SPIRV_MAGIC.to_ne_bytes())),
+        );
+    }
+
+    #[test]
+    fn shader_file_kind_follows_extension() {
+        let kind = |path: &str| ShaderFileKind::from_path(Path::new(path));
+        assert_eq!(kind("shaders/particles.spv"), Some(ShaderFileKind::SpirV));
+        assert_eq!(kind("triangle.wgsl"), Some(ShaderFileKind::Wgsl));
+        assert_eq!(kind("matmul.slang"), Some(ShaderFileKind::Slang));
+        assert_eq!(kind("blur.comp"), Some(ShaderFileKind::Glsl));
+        assert_eq!(kind("README.md"), None);
+        assert_eq!(kind("shader"), None);
+    }
+
+    #[test]
+    fn spirv_bytes_require_whole_words() {
+        let bytes = SPIRV_MAGIC.to_ne_bytes();
+        assert_eq!(spirv_words(&bytes).unwrap().as_ref(), &[SPIRV_MAGIC]);
+        assert!(spirv_words(&bytes[..3]).is_err());
+    }
+
+    #[test]
+    #[ignore = "requires a Vulkan device"]
+    fn invalid_utf8_wgsl_is_a_compile_error() {
+        use crate::{QueueFlags, device::test_device};
+
+        let (device, _queue) = test_device(QueueFlags::COMPUTE);
+        let bytes = [b'f', b'n', 0xff, 0xfe];
+        let result = device.create_shader(None, ShaderSource::WgslBytes(&bytes));
+        assert!(matches!(result, Err(GPUError::ShaderCompile(msg)) if msg.contains("UTF-8")));
+
+        let path = std::env::temp_dir().join(format!("tgpu-invalid-{}.wgsl", std::process::id()));
+        std::fs::write(&path, bytes).unwrap();
+        let result = device.create_shader_from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            result,
+            Err(GPUError::ShaderCompile(msg)) if msg.starts_with(&path.display().to_string())
+        ));
+    }
 
     //     #[test]
     // fn create_wgsl_shader() {
@@ -277,6 +538,7 @@ fn fmain(input: VertexOutput) -> @location(0) vec4f {
         let instance = crate::Instance::new(&crate::InstanceCreateInfo {
             app_name: "Triangle",
             engine_name: "Example Engine",
+            debug_printf: false,
         })
         .unwrap();
 
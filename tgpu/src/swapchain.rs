@@ -4,6 +4,7 @@ use std::sync::Arc;
 
 use crate::{
     Device, GPUError, Image, ImageView, Queue, Semaphore,
+    image::ImageUsage,
     raw::{DeviceImpl, ImageImpl, ImageViewImpl, QueueImpl, RawAdapter, RawDevice, SemaphoreImpl},
 };
 
@@ -17,13 +18,39 @@ pub struct Swapchain {
     pub inner: SwapchainImpl,
 }
 
+/// Picks the surface format from the formats the surface supports.
+pub type FormatSelector = Box<dyn Fn(&[vk::SurfaceFormatKHR]) -> vk::SurfaceFormatKHR>;
+
 pub struct SwapchainCreateInfo {
     pub display: RawDisplayHandle,
     pub window: RawWindowHandle,
     pub preferred_extent: vk::Extent2D,
     pub preferred_image_count: usize,
     pub preferred_present_mode: vk::PresentModeKHR,
-    pub format_selector: Box<dyn Fn(&[vk::SurfaceFormatKHR]) -> vk::SurfaceFormatKHR>,
+    /// Fail with a validation error instead of falling back when the preferred mode is missing.
+    pub require_present_mode: bool,
+    /// Also create the images with `STORAGE` usage so compute shaders can write them directly.
+    ///
+    /// Needs a format with storage image support, which usually rules out the sRGB formats.
+    pub storage: bool,
+    pub format_selector: FormatSelector,
+}
+
+/// Creates a swapchain for a surface supplied by an external windowing layer.
+///
+/// The surface is not destroyed with the swapchain; the caller keeps ownership.
+pub struct SwapchainSurfaceInfo {
+    pub surface: vk::SurfaceKHR,
+    pub preferred_extent: vk::Extent2D,
+    pub preferred_image_count: usize,
+    pub preferred_present_mode: vk::PresentModeKHR,
+    /// Fail with a validation error instead of falling back when the preferred mode is missing.
+    pub require_present_mode: bool,
+    /// Also create the images with `STORAGE` usage so compute shaders can write them directly.
+    ///
+    /// Needs a format with storage image support, which usually rules out the sRGB formats.
+    pub storage: bool,
+    pub format_selector: FormatSelector,
 }
 
 #[derive(Debug)]
@@ -40,9 +67,9 @@ pub struct SwapchainImplResources {
 pub struct SwapchainImpl {
     pub device: RawDevice,
     pub adapter: RawAdapter,
-    pub loader: ash::khr::swapchain::Device,
     pub surface: vk::SurfaceKHR,
-    pub surface_loader: ash::khr::surface::Instance,
+    /// Whether `surface` is destroyed together with the swapchain.
+    pub owns_surface: bool,
 
     pub available: Vec<Semaphore>,
     pub finished: Vec<Semaphore>,
@@ -54,29 +81,110 @@ pub struct SwapchainImpl {
     pub max_flight: usize,
     pub preferred_extent: vk::Extent2D,
     pub preferred_present_mode: vk::PresentModeKHR,
+    /// Tried in order when `preferred_present_mode` is unavailable; FIFO is always the last resort.
+    pub present_mode_fallbacks: Vec<vk::PresentModeKHR>,
+    pub require_present_mode: bool,
+    pub storage: bool,
     pub formats: Arc<[vk::SurfaceFormatKHR]>,
     pub format: vk::SurfaceFormatKHR,
     pub present_modes: Arc<[vk::PresentModeKHR]>,
 }
 
+/// Vsync preferences fall back to `FIFO_RELAXED`, low-latency ones to `MAILBOX` then `IMMEDIATE`.
+fn default_present_mode_fallbacks(preferred: vk::PresentModeKHR) -> Vec<vk::PresentModeKHR> {
+    match preferred {
+        vk::PresentModeKHR::FIFO | vk::PresentModeKHR::FIFO_RELAXED => {
+            vec![vk::PresentModeKHR::FIFO_RELAXED]
+        }
+        _ => vec![vk::PresentModeKHR::MAILBOX, vk::PresentModeKHR::IMMEDIATE],
+    }
+}
+
+fn present_mode_order(
+    preferred: vk::PresentModeKHR,
+    fallbacks: &[vk::PresentModeKHR],
+) -> Vec<vk::PresentModeKHR> {
+    std::iter::once(preferred)
+        .chain(fallbacks.iter().copied())
+        .collect()
+}
+
+fn select_present_mode(
+    available: &[vk::PresentModeKHR],
+    order: &[vk::PresentModeKHR],
+) -> vk::PresentModeKHR {
+    order
+        .iter()
+        .copied()
+        .find(|mode| available.contains(mode))
+        .unwrap_or(vk::PresentModeKHR::FIFO)
+}
+
 impl SwapchainImpl {
     fn new(device: RawDevice, info: &SwapchainCreateInfo) -> Result<Self, GPUError> {
-        let adapter = device.adapter.clone();
+        if device.compute_only {
+            return Err(GPUError::Validation(
+                "swapchains can't be created on a compute_only device",
+            ));
+        }
         let surface = match Self::create_surface(&device, info.display, info.window) {
             Ok(surface) => surface,
             Err(e) => panic!("{:?}", e),
         };
 
-        let surface_loader =
-            ash::khr::surface::Instance::new(&device.instance.entry, &device.instance.handle);
-        let loader = ash::khr::swapchain::Device::new(&device.instance.handle, &device.handle);
+        Self::from_surface(
+            device,
+            surface,
+            true,
+            info.preferred_extent,
+            info.preferred_image_count,
+            info.preferred_present_mode,
+            info.require_present_mode,
+            info.storage,
+            &info.format_selector,
+        )
+    }
+
+    fn new_for_surface(device: RawDevice, info: &SwapchainSurfaceInfo) -> Result<Self, GPUError> {
+        if device.compute_only {
+            return Err(GPUError::Validation(
+                "swapchains can't be created on a compute_only device",
+            ));
+        }
+        Self::from_surface(
+            device,
+            info.surface,
+            false,
+            info.preferred_extent,
+            info.preferred_image_count,
+            info.preferred_present_mode,
+            info.require_present_mode,
+            info.storage,
+            &info.format_selector,
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn from_surface(
+        device: RawDevice,
+        surface: vk::SurfaceKHR,
+        owns_surface: bool,
+        preferred_extent: vk::Extent2D,
+        preferred_image_count: usize,
+        preferred_present_mode: vk::PresentModeKHR,
+        require_present_mode: bool,
+        storage: bool,
+        format_selector: &dyn Fn(&[vk::SurfaceFormatKHR]) -> vk::SurfaceFormatKHR,
+    ) -> Result<Self, GPUError> {
+        let adapter = device.adapter.clone();
+        let surface_loader = &device.ext.surface;
 
         let formats = unsafe {
             surface_loader
                 .get_physical_device_surface_formats(adapter.handle, surface)
                 .map_err(GPUError::from)?
         };
-        let format = (info.format_selector)(&formats);
+        let format = (format_selector)(&formats);
         log::info!("format: {:?}", format);
 
         let present_modes = unsafe {
@@ -86,17 +194,20 @@ impl SwapchainImpl {
         };
 
         let (available, finished, flight) =
-            Self::create_syncs(device.clone(), info.preferred_image_count)?;
+            Self::create_syncs(device.clone(), preferred_image_count)?;
 
         let resources = Self::create_resources(
             device.clone(),
-            &loader,
             surface,
-            &surface_loader,
             adapter.handle,
-            info.preferred_extent,
-            info.preferred_image_count as u32,
-            info.preferred_present_mode,
+            preferred_extent,
+            preferred_image_count as u32,
+            &present_mode_order(
+                preferred_present_mode,
+                &default_present_mode_fallbacks(preferred_present_mode),
+            ),
+            require_present_mode,
+            storage,
             format,
             None,
         )?;
@@ -104,9 +215,8 @@ impl SwapchainImpl {
         let new = Self {
             device,
             adapter,
-            loader,
             surface,
-            surface_loader,
+            owns_surface,
 
             available,
             finished,
@@ -115,9 +225,12 @@ impl SwapchainImpl {
 
             resources,
 
-            max_flight: info.preferred_image_count,
-            preferred_extent: info.preferred_extent,
-            preferred_present_mode: info.preferred_present_mode,
+            max_flight: preferred_image_count,
+            preferred_extent,
+            preferred_present_mode,
+            present_mode_fallbacks: default_present_mode_fallbacks(preferred_present_mode),
+            require_present_mode,
+            storage,
             formats: Arc::from(formats),
             format,
             present_modes: Arc::from(present_modes),
@@ -162,18 +275,21 @@ impl SwapchainImpl {
         Ok((available, finished, flight))
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn create_resources(
         device: RawDevice,
-        loader: &ash::khr::swapchain::Device,
         surface_handle: vk::SurfaceKHR,
-        surface_loader: &ash::khr::surface::Instance,
         adapter_handle: vk::PhysicalDevice,
         preferred_extent: vk::Extent2D,
         preferred_image_count: u32,
-        preferred_present_mode: vk::PresentModeKHR,
+        present_mode_order: &[vk::PresentModeKHR],
+        require_present_mode: bool,
+        storage: bool,
         format: vk::SurfaceFormatKHR,
         old_swapchain: Option<vk::SwapchainKHR>,
     ) -> Result<SwapchainImplResources, GPUError> {
+        let surface_loader = &device.ext.surface;
+        let loader = &device.ext.swapchain;
         let capabilities = unsafe {
             surface_loader
                 .get_physical_device_surface_capabilities(adapter_handle, surface_handle)
@@ -209,25 +325,43 @@ impl SwapchainImpl {
                 .get_physical_device_surface_present_modes(adapter_handle, surface_handle)?
         };
 
-        let present_mode = if present_modes.contains(&preferred_present_mode) {
-            preferred_present_mode
-        } else {
-            present_modes
-                .iter()
-                .copied()
-                .find(|&mode| mode == vk::PresentModeKHR::MAILBOX)
-                .or_else(|| {
-                    log::warn!("Present mode: MAILBOX not found, falling back to Immediate");
-                    present_modes
-                        .iter()
-                        .copied()
-                        .find(|&mode| mode == vk::PresentModeKHR::IMMEDIATE)
-                })
-                .unwrap_or_else(|| {
-                    log::warn!("Present mode: IMMEDIATE not found, falling back to Fifo");
-                    vk::PresentModeKHR::FIFO
-                })
-        };
+        let present_mode = select_present_mode(&present_modes, present_mode_order);
+        if let Some(&preferred) = present_mode_order.first()
+            && preferred != present_mode
+        {
+            if require_present_mode {
+                return Err(GPUError::Validation(
+                    "the required present mode is not supported by the surface",
+                ));
+            }
+            log::warn!(
+                "Present mode: {:?} not found, falling back to {:?}",
+                preferred,
+                present_mode
+            );
+        }
+
+        let mut usage = ImageUsage::COLOR | ImageUsage::COPY_DST;
+        if storage {
+            if !capabilities
+                .supported_usage_flags
+                .contains(vk::ImageUsageFlags::STORAGE)
+            {
+                return Err(GPUError::Validation(
+                    "the surface doesn't support storage swapchain images",
+                ));
+            }
+            let features = device
+                .adapter
+                .format_properties(format.format)
+                .optimal_tiling_features;
+            if !features.contains(vk::FormatFeatureFlags::STORAGE_IMAGE) {
+                return Err(GPUError::Validation(
+                    "the swapchain format doesn't support storage images",
+                ));
+            }
+            usage |= ImageUsage::STORAGE;
+        }
 
         let info = vk::SwapchainCreateInfoKHR::default()
             .surface(surface_handle)
@@ -236,7 +370,7 @@ impl SwapchainImpl {
             .image_color_space(format.color_space)
             .image_extent(extent)
             .image_array_layers(1)
-            .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_DST)
+            .image_usage(usage.into())
             .image_sharing_mode(vk::SharingMode::EXCLUSIVE)
             .pre_transform(capabilities.current_transform)
             .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
@@ -265,12 +399,27 @@ impl SwapchainImpl {
                     handle,
                     device: device.clone(),
                     allocation: None,
+                    extent: vk::Extent3D {
+                        width: extent.width,
+                        height: extent.height,
+                        depth: 1,
+                    },
+                    mip_levels: 1,
+                    layers: 1,
+                    flags: vk::ImageCreateFlags::empty(),
+                    usage,
+                    aliased: None,
                 }),
             })
             .collect::<Vec<_>>();
 
         let views = Self::create_image_views(device.clone(), &images, format);
 
+        for (index, (image, view)) in images.iter().zip(&views).enumerate() {
+            image.set_name(&format!("swapchain-image-{index}"));
+            view.set_name(&format!("swapchain-view-{index}"));
+        }
+
         let resources = SwapchainImplResources {
             handle,
             images,
@@ -317,11 +466,11 @@ impl SwapchainImpl {
 
                 ImageView {
                     sampler: None,
-                    inner: ImageViewImpl {
+                    inner: Arc::new(ImageViewImpl {
                         handle,
                         device: device.clone(),
                         image: img.inner.clone(),
-                    },
+                    }),
                 }
             })
             .collect()
@@ -345,15 +494,15 @@ impl SwapchainImpl {
     }
 
     pub fn acquire_next(&mut self, timeout: Option<u64>) -> Result<Frame, GPUError> {
+        profile_span!("swapchain_acquire", frame = self.frame);
         let flight_fence = self.flight[self.frame];
         let available_semaphore = &self.available[self.frame];
 
-        unsafe { self.device.wait_fence(flight_fence, timeout) };
-        unsafe { self.device.reset_fence(flight_fence) };
+        unsafe { self.device.wait_fence(flight_fence, timeout) }?;
 
         let timeout_ns = timeout.unwrap_or(u64::MAX);
         let (image_index, suboptimal) = unsafe {
-            match self.loader.acquire_next_image(
+            match self.device.ext.swapchain.acquire_next_image(
                 self.resources.handle,
                 timeout_ns,
                 available_semaphore.inner.handle,
@@ -366,10 +515,16 @@ impl SwapchainImpl {
                         suboptimal: true,
                     });
                 }
-                Err(e) => return Err(GPUError::Vulkan(e)),
+                Err(e) => return Err(self.device.vk_error(e)),
             }
         };
 
+        // Only reset once a submit is expected to signal the fence again; resetting before a
+        // failed or skipped frame would make the next acquire wait forever.
+        if !suboptimal {
+            unsafe { self.device.reset_fence(flight_fence) };
+        }
+
         Ok(Frame {
             index: image_index,
             suboptimal,
@@ -385,20 +540,52 @@ impl SwapchainImpl {
     }
 
     pub fn present(&mut self, queue: &QueueImpl, frame: Frame) -> Result<bool, GPUError> {
-        let finished_semaphore = &self.finished[self.frame];
+        let wait_semaphores = [self.finished[self.frame].inner.handle];
+        self.present_raw(queue, frame, &wait_semaphores)
+    }
+
+    pub fn present_with(
+        &mut self,
+        queue: &QueueImpl,
+        frame: Frame,
+        wait: &[Semaphore],
+    ) -> Result<bool, GPUError> {
+        let wait_semaphores = wait
+            .iter()
+            .map(|semaphore| {
+                debug_assert!(
+                    !semaphore.is_timeline(),
+                    "present can only wait on binary semaphores"
+                );
+                semaphore.inner.handle
+            })
+            .collect::<Vec<_>>();
+        self.present_raw(queue, frame, &wait_semaphores)
+    }
 
+    fn present_raw(
+        &mut self,
+        queue: &QueueImpl,
+        frame: Frame,
+        wait_semaphores: &[vk::Semaphore],
+    ) -> Result<bool, GPUError> {
+        profile_span!("swapchain_present", image = frame.index);
         let swapchains = [self.resources.handle];
         let image_indices = [frame.index];
-        let wait_semaphores = [finished_semaphore.inner.handle];
         let present_info = vk::PresentInfoKHR::default()
-            .wait_semaphores(&wait_semaphores)
+            .wait_semaphores(wait_semaphores)
             .swapchains(&swapchains)
             .image_indices(&image_indices);
-        let result = unsafe { self.loader.queue_present(queue.handle, &present_info) };
+        let result = unsafe {
+            self.device
+                .ext
+                .swapchain
+                .queue_present(queue.handle, &present_info)
+        };
         let needs_recreation = match result {
             Ok(suboptimal) => suboptimal || frame.suboptimal,
             Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => true,
-            Err(e) => return Err(GPUError::Vulkan(e)),
+            Err(e) => return Err(self.device.vk_error(e)),
         };
 
         self.frame = (self.frame + 1) % self.max_flight;
@@ -424,13 +611,13 @@ impl SwapchainImpl {
         unsafe { self.device.wait_idle() };
         let new = Self::create_resources(
             self.device.clone(),
-            &self.loader,
             self.surface,
-            &self.surface_loader,
             self.device.adapter.handle,
             self.preferred_extent,
             self.resources.images.len() as u32,
-            self.preferred_present_mode,
+            &present_mode_order(self.preferred_present_mode, &self.present_mode_fallbacks),
+            self.require_present_mode,
+            self.storage,
             self.format,
             Some(self.resources.handle),
         )?;
@@ -439,7 +626,10 @@ impl SwapchainImpl {
             Self::create_syncs(self.device.clone(), new.images.len())?;
 
         unsafe {
-            self.loader.destroy_swapchain(self.resources.handle, None);
+            self.device
+                .ext
+                .swapchain
+                .destroy_swapchain(self.resources.handle, None);
             for &fence in &self.flight {
                 self.device.handle.destroy_fence(fence, None);
             }
@@ -460,10 +650,28 @@ impl Swapchain {
         self.inner.preferred_extent = extent;
     }
 
+    /// Also resets the fallbacks to the defaults for `present_mode`; applied on `recreate`.
     pub fn set_preferred_present_mode(&mut self, present_mode: vk::PresentModeKHR) {
         self.inner.preferred_present_mode = present_mode;
+        self.inner.present_mode_fallbacks = default_present_mode_fallbacks(present_mode);
+    }
+
+    /// Whether `recreate` fails instead of falling back when the preferred mode is unavailable.
+    pub fn set_require_present_mode(&mut self, require: bool) {
+        self.inner.require_present_mode = require;
+    }
+
+    /// Modes tried in order when the preferred one is unavailable; applied on `recreate`.
+    ///
+    /// FIFO is always used as the last resort since every surface supports it.
+    pub fn set_present_mode_fallbacks(&mut self, fallbacks: &[vk::PresentModeKHR]) {
+        self.inner.present_mode_fallbacks = fallbacks.to_vec();
     }
 
+    /// Waits for the current flight slot and acquires the next image.
+    ///
+    /// The flight fence is only reset for frames that aren't `suboptimal`; those must be submitted
+    /// with that fence, while suboptimal frames should be skipped and the swapchain recreated.
     #[inline]
     pub fn acquire_next(&mut self, timeout: Option<u64>) -> Result<Frame, GPUError> {
         self.inner.acquire_next(timeout)
@@ -474,6 +682,20 @@ impl Swapchain {
         self.inner.present(&queue.inner, frame)
     }
 
+    /// Presents after waiting on `wait` instead of the swapchain's own finished semaphore.
+    ///
+    /// The semaphores must be binary and signaled by a prior submission; an empty slice presents
+    /// without waiting.
+    #[inline]
+    pub fn present_with(
+        &mut self,
+        queue: &Queue,
+        frame: Frame,
+        wait: &[Semaphore],
+    ) -> Result<bool, GPUError> {
+        self.inner.present_with(&queue.inner, frame, wait)
+    }
+
     #[inline]
     pub fn image(&self, frame: Frame) -> &Image {
         self.inner.image(frame)
@@ -484,6 +706,20 @@ impl Swapchain {
         self.inner.view(frame)
     }
 
+    /// One view per swapchain image, indexed by `Frame::index`; replaced on `recreate`.
+    ///
+    /// With `storage` set, these can be bound as storage images in the `GENERAL` layout.
+    #[inline]
+    pub fn views(&self) -> &[ImageView] {
+        &self.inner.resources.views
+    }
+
+    /// Whether the images were created with `STORAGE` usage.
+    #[inline]
+    pub fn is_storage(&self) -> bool {
+        self.inner.storage
+    }
+
     pub fn recreate(&mut self) -> Result<(), GPUError> {
         self.inner.recreate()?;
         Ok(())
@@ -494,11 +730,17 @@ impl Swapchain {
         self.inner.preferred_present_mode
     }
 
+    /// The mode actually selected, which differs from the preferred one after a fallback.
     #[inline]
     pub fn present_mode(&self) -> vk::PresentModeKHR {
         self.inner.resources.present_mode
     }
 
+    #[inline]
+    pub fn present_mode_fallbacks(&self) -> &[vk::PresentModeKHR] {
+        &self.inner.present_mode_fallbacks
+    }
+
     #[inline]
     pub fn supported_present_modes(&self) -> &[vk::PresentModeKHR] {
         self.inner.present_modes.as_ref()
@@ -530,6 +772,41 @@ impl Device {
         let inner = SwapchainImpl::new(self.inner.clone(), info)?;
         Ok(Swapchain { inner })
     }
+
+    /// Creates a swapchain for an existing surface, e.g. one owned by an external windowing layer.
+    ///
+    /// # Safety
+    /// `info.surface` must have been created from this device's instance and must outlive the
+    /// returned swapchain.
+    pub unsafe fn create_swapchain_for_surface(
+        &self,
+        info: &SwapchainSurfaceInfo,
+    ) -> Result<Swapchain, GPUError> {
+        let inner = SwapchainImpl::new_for_surface(self.inner.clone(), info)?;
+        Ok(Swapchain { inner })
+    }
+
+    /// Returns whether `queue` can present to `surface`.
+    ///
+    /// # Safety
+    /// `surface` must be a valid surface created from this device's instance.
+    pub unsafe fn present_support(
+        &self,
+        queue: &Queue,
+        surface: vk::SurfaceKHR,
+    ) -> Result<bool, GPUError> {
+        unsafe {
+            self.inner
+                .ext
+                .surface
+                .get_physical_device_surface_support(
+                    self.inner.adapter.handle,
+                    queue.inner.info.family_index,
+                    surface,
+                )
+                .map_err(GPUError::from)
+        }
+    }
 }
 
 impl Drop for SwapchainImplResources {
@@ -547,8 +824,40 @@ impl Drop for SwapchainImpl {
             for &fence in &self.flight {
                 self.device.handle.destroy_fence(fence, None);
             }
-            self.loader.destroy_swapchain(self.resources.handle, None);
-            self.surface_loader.destroy_surface(self.surface, None);
+            self.device
+                .ext
+                .swapchain
+                .destroy_swapchain(self.resources.handle, None);
+            if self.owns_surface {
+                self.device.ext.surface.destroy_surface(self.surface, None);
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn present_mode_follows_preference_then_fallbacks() {
+        let available = [vk::PresentModeKHR::FIFO, vk::PresentModeKHR::FIFO_RELAXED];
+        let immediate = vk::PresentModeKHR::IMMEDIATE;
+        let select = |preferred, fallbacks: &[vk::PresentModeKHR]| {
+            select_present_mode(&available, &present_mode_order(preferred, fallbacks))
+        };
+
+        assert_eq!(
+            select(vk::PresentModeKHR::FIFO, &[]),
+            vk::PresentModeKHR::FIFO
+        );
+        assert_eq!(
+            select(immediate, &[vk::PresentModeKHR::FIFO_RELAXED]),
+            vk::PresentModeKHR::FIFO_RELAXED
+        );
+        assert_eq!(
+            select(immediate, &default_present_mode_fallbacks(immediate)),
+            vk::PresentModeKHR::FIFO
+        );
+    }
+}
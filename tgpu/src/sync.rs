@@ -1,12 +1,25 @@
-use std::{sync::Arc, time::Duration};
+use std::{ffi::CStr, sync::Arc, time::Duration};
 
 use ash::vk;
 
 use crate::{
-    Device,
+    Device, GPUError, Queue,
     raw::{DeviceImpl, RawDevice},
 };
 
+/// Extension exporting semaphores as OS handles on this platform.
+#[cfg(unix)]
+pub(crate) const EXTERNAL_SEMAPHORE_EXTENSION: &CStr = ash::khr::external_semaphore_fd::NAME;
+#[cfg(windows)]
+pub(crate) const EXTERNAL_SEMAPHORE_EXTENSION: &CStr = ash::khr::external_semaphore_win32::NAME;
+
+#[cfg(unix)]
+const EXTERNAL_SEMAPHORE_HANDLE_TYPE: vk::ExternalSemaphoreHandleTypeFlags =
+    vk::ExternalSemaphoreHandleTypeFlags::OPAQUE_FD;
+#[cfg(windows)]
+const EXTERNAL_SEMAPHORE_HANDLE_TYPE: vk::ExternalSemaphoreHandleTypeFlags =
+    vk::ExternalSemaphoreHandleTypeFlags::OPAQUE_WIN32;
+
 #[derive(Debug, Clone)]
 pub struct Semaphore {
     pub inner: RawSemaphore,
@@ -14,26 +27,217 @@ pub struct Semaphore {
 
 pub type RawSemaphore = Arc<SemaphoreImpl>;
 
-// pub struct Fence {
-// }
+/// A submission index together with the queue timeline it is signaled on.
+#[derive(Debug, Clone)]
+pub struct SubmitToken {
+    pub timeline: Semaphore,
+    pub value: u64,
+}
+
+/// One instance of a resource per frame in flight, e.g. uniform buffers or descriptor sets.
+///
+/// Each slot remembers the queue submission that last used it, so `current` only hands it out
+/// again once the GPU is done with it.
+#[derive(Debug)]
+pub struct PerFrame<T> {
+    slots: Vec<T>,
+    submissions: Vec<u64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Fence {
+    pub inner: Arc<FenceImpl>,
+}
+
+#[derive(Debug)]
+pub struct FenceImpl {
+    pub handle: vk::Fence,
+    pub device: RawDevice,
+}
+
+/// A split barrier: set after the producer, waited on before the consumer, with unrelated
+/// work recorded in between.
+#[derive(Debug, Clone)]
+pub struct Event {
+    pub inner: Arc<EventImpl>,
+}
+
+#[derive(Debug)]
+pub struct EventImpl {
+    pub handle: vk::Event,
+    pub device: RawDevice,
+}
+
+/// An OS handle exported from a semaphore, closed on drop.
+///
+/// Importing it elsewhere (CUDA, OpenGL, another process) takes its own reference to the
+/// semaphore's payload.
+#[derive(Debug)]
+pub enum ExternalHandle {
+    #[cfg(unix)]
+    Fd(std::os::fd::OwnedFd),
+    #[cfg(windows)]
+    Win32(std::os::windows::io::OwnedHandle),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SemaphoreKind {
+    /// Signaled and waited without a value, e.g. around swapchain acquire and present.
+    Binary,
+    /// Carries a monotonically increasing counter.
+    Timeline,
+}
 
 #[derive(Debug)]
 pub struct SemaphoreImpl {
     pub handle: vk::Semaphore,
     pub device: RawDevice,
+    pub kind: SemaphoreKind,
+    /// Handle types the semaphore can be exported as; empty for regular semaphores.
+    pub export: vk::ExternalSemaphoreHandleTypeFlags,
 }
 
 impl Semaphore {
+    pub fn kind(&self) -> SemaphoreKind {
+        self.inner.kind
+    }
+
+    pub fn is_timeline(&self) -> bool {
+        self.inner.kind == SemaphoreKind::Timeline
+    }
+
+    /// Exports an OS handle (an fd on unix, a `HANDLE` on windows) for interop with other APIs.
+    ///
+    /// Only semaphores from `Device::create_exportable_semaphore` can be exported.
+    pub fn export_handle(&self) -> Result<ExternalHandle, GPUError> {
+        self.inner.export_handle()
+    }
+
     pub fn get(&self) -> u64 {
+        debug_assert!(self.is_timeline(), "Semaphore::get on a binary semaphore");
         unsafe { self.inner.get() }
     }
 
+    /// Sets the counter from the host, releasing GPU and host waits on values up to `value`.
     pub fn signal(&self, value: u64) {
-        unsafe { self.inner.signal(value) };
+        debug_assert!(
+            self.is_timeline(),
+            "Semaphore::signal on a binary semaphore"
+        );
+        unsafe { self.inner.signal(value) }.expect("Signal Semaphore");
     }
 
-    pub fn wait(&self, value: u64, timeout: Option<Duration>) {
-        unsafe { self.inner.wait(value, timeout) };
+    /// Waits for the counter to reach `value`, returning `Ok(false)` if `timeout` elapsed first.
+    pub fn wait(&self, value: u64, timeout: Option<Duration>) -> Result<bool, GPUError> {
+        debug_assert!(self.is_timeline(), "Semaphore::wait on a binary semaphore");
+        unsafe { self.inner.wait(value, timeout) }
+    }
+
+    #[deprecated(note = "`Semaphore::wait` now returns the same result")]
+    pub fn wait_for_value(&self, value: u64, timeout: Option<Duration>) -> Result<bool, GPUError> {
+        self.wait(value, timeout)
+    }
+}
+
+impl Fence {
+    /// Raw handle, e.g. for `SubmitInfo::fence`.
+    pub fn handle(&self) -> vk::Fence {
+        self.inner.handle
+    }
+
+    pub fn is_signaled(&self) -> Result<bool, GPUError> {
+        unsafe {
+            self.inner
+                .device
+                .handle
+                .get_fence_status(self.inner.handle)
+                .map_err(|e| self.inner.device.vk_error(e))
+        }
+    }
+
+    /// Waits for the fence, returning `Ok(false)` if `timeout` elapsed first.
+    pub fn wait(&self, timeout: Option<Duration>) -> Result<bool, GPUError> {
+        unsafe {
+            self.inner
+                .device
+                .wait_fences(&[self.inner.handle], true, timeout)
+        }
+    }
+
+    pub fn reset(&self) {
+        unsafe { self.inner.device.reset_fence(self.inner.handle) };
+    }
+}
+
+impl<T> PerFrame<T> {
+    pub fn new(count: usize, create: impl FnMut(usize) -> T) -> Self {
+        assert!(count > 0, "PerFrame needs at least one slot");
+        Self {
+            slots: (0..count).map(create).collect(),
+            submissions: vec![0; count],
+        }
+    }
+
+    pub fn try_new<E>(count: usize, create: impl FnMut(usize) -> Result<T, E>) -> Result<Self, E> {
+        assert!(count > 0, "PerFrame needs at least one slot");
+        Ok(Self {
+            slots: (0..count).map(create).collect::<Result<_, _>>()?,
+            submissions: vec![0; count],
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// The slot for `frame_index`, wrapping around; does not wait for the GPU.
+    pub fn get(&self, frame_index: usize) -> &T {
+        &self.slots[frame_index % self.slots.len()]
+    }
+
+    /// Waits until the last submission using this slot has finished, then returns it for writing.
+    pub fn current(&mut self, queue: &Queue, frame_index: usize) -> Result<&mut T, GPUError> {
+        let slot = frame_index % self.slots.len();
+        let submission = self.submissions[slot];
+        if submission != 0 && !queue.is_complete(submission) {
+            queue.wait_for(submission)?;
+        }
+        Ok(&mut self.slots[slot])
+    }
+
+    /// Records that `submission`, as returned by `Queue::submit`, uses the slot for `frame_index`.
+    pub fn submitted(&mut self, frame_index: usize, submission: u64) {
+        let slot = frame_index % self.slots.len();
+        self.submissions[slot] = submission;
+    }
+
+    /// Submission index that last used the slot for `frame_index`, or 0 if it was never used.
+    pub fn last_submission(&self, frame_index: usize) -> u64 {
+        self.submissions[frame_index % self.slots.len()]
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.slots.iter()
+    }
+}
+
+impl SubmitToken {
+    /// Blocks until the submission has finished on the GPU.
+    pub fn wait(&self) -> Result<(), GPUError> {
+        self.timeline.wait(self.value, None).map(|_| ())
+    }
+
+    /// Returns `Ok(false)` if `timeout` elapsed before the submission finished.
+    pub fn wait_timeout(&self, timeout: Duration) -> Result<bool, GPUError> {
+        self.timeline.wait(self.value, Some(timeout))
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.timeline.get() >= self.value
     }
 }
 
@@ -42,12 +246,12 @@ impl SemaphoreImpl {
         unsafe { self.device.get_semaphore_value(self.handle) }
     }
 
-    pub unsafe fn signal(&self, value: u64) {
-        unsafe { self.device.signal_semaphore(self.handle, value) };
+    pub unsafe fn signal(&self, value: u64) -> Result<(), GPUError> {
+        unsafe { self.device.signal_semaphore(self.handle, value) }
     }
 
-    pub unsafe fn wait(&self, value: u64, timeout: Option<Duration>) {
-        unsafe { self.device.wait_semaphore(self.handle, value, timeout) };
+    pub unsafe fn wait(&self, value: u64, timeout: Option<Duration>) -> Result<bool, GPUError> {
+        unsafe { self.device.wait_semaphore(self.handle, value, timeout) }
     }
 
     pub unsafe fn new_signal(device: RawDevice) -> Self {
@@ -58,7 +262,12 @@ impl SemaphoreImpl {
                 .create_semaphore(&semaphore_info, None)
                 .expect("Create Signal Semaphore")
         };
-        Self { handle, device }
+        Self {
+            handle,
+            device,
+            kind: SemaphoreKind::Binary,
+            export: vk::ExternalSemaphoreHandleTypeFlags::empty(),
+        }
     }
     pub unsafe fn new_timeline(device: Arc<DeviceImpl>, value: u64) -> Self {
         let mut semaphore_type_info = vk::SemaphoreTypeCreateInfo::default()
@@ -77,11 +286,200 @@ impl SemaphoreImpl {
         SemaphoreImpl {
             handle,
             device: device.clone(),
+            kind: SemaphoreKind::Timeline,
+            export: vk::ExternalSemaphoreHandleTypeFlags::empty(),
+        }
+    }
+
+    /// Creates a semaphore exportable as this platform's opaque handle type.
+    ///
+    /// `value` is the initial counter of a timeline semaphore and ignored for binary ones.
+    pub fn new_exportable(
+        device: RawDevice,
+        kind: SemaphoreKind,
+        value: u64,
+    ) -> Result<Self, GPUError> {
+        if !device.features.external_semaphore {
+            return Err(GPUError::Validation(
+                "exportable semaphores require the external_semaphore device feature",
+            ));
+        }
+
+        let (semaphore_type, value) = match kind {
+            SemaphoreKind::Binary => (vk::SemaphoreType::BINARY, 0),
+            SemaphoreKind::Timeline => (vk::SemaphoreType::TIMELINE, value),
+        };
+        let mut semaphore_type_info = vk::SemaphoreTypeCreateInfo::default()
+            .semaphore_type(semaphore_type)
+            .initial_value(value);
+
+        let exportable = {
+            let mut properties = vk::ExternalSemaphoreProperties::default();
+            let external_info = vk::PhysicalDeviceExternalSemaphoreInfo::default()
+                .handle_type(EXTERNAL_SEMAPHORE_HANDLE_TYPE)
+                .push_next(&mut semaphore_type_info);
+            unsafe {
+                device
+                    .instance
+                    .handle
+                    .get_physical_device_external_semaphore_properties(
+                        device.adapter.handle,
+                        &external_info,
+                        &mut properties,
+                    )
+            };
+            properties
+                .external_semaphore_features
+                .contains(vk::ExternalSemaphoreFeatureFlags::EXPORTABLE)
+        };
+        if !exportable {
+            return Err(GPUError::Validation(
+                "the adapter can't export semaphores of this kind",
+            ));
+        }
+
+        let mut export_info =
+            vk::ExportSemaphoreCreateInfo::default().handle_types(EXTERNAL_SEMAPHORE_HANDLE_TYPE);
+        let info = vk::SemaphoreCreateInfo::default()
+            .push_next(&mut semaphore_type_info)
+            .push_next(&mut export_info);
+
+        let handle = unsafe {
+            device
+                .handle
+                .create_semaphore(&info, None)
+                .map_err(GPUError::from)?
+        };
+        Ok(Self {
+            handle,
+            device,
+            kind,
+            export: EXTERNAL_SEMAPHORE_HANDLE_TYPE,
+        })
+    }
+
+    /// Exports a new OS handle referencing the semaphore's payload.
+    pub fn export_handle(&self) -> Result<ExternalHandle, GPUError> {
+        let ext = self
+            .device
+            .ext
+            .external_semaphore
+            .as_ref()
+            .ok_or(GPUError::Validation(
+                "exporting semaphores requires the external_semaphore device feature",
+            ))?;
+        if !self.export.contains(EXTERNAL_SEMAPHORE_HANDLE_TYPE) {
+            return Err(GPUError::Validation(
+                "semaphore was not created with Device::create_exportable_semaphore",
+            ));
+        }
+
+        #[cfg(unix)]
+        {
+            use std::os::fd::FromRawFd;
+
+            let info = vk::SemaphoreGetFdInfoKHR::default()
+                .semaphore(self.handle)
+                .handle_type(EXTERNAL_SEMAPHORE_HANDLE_TYPE);
+            let fd = unsafe { ext.get_semaphore_fd(&info) }?;
+            Ok(ExternalHandle::Fd(unsafe {
+                std::os::fd::OwnedFd::from_raw_fd(fd)
+            }))
+        }
+        #[cfg(windows)]
+        {
+            use std::os::windows::io::FromRawHandle;
+
+            let info = vk::SemaphoreGetWin32HandleInfoKHR::default()
+                .semaphore(self.handle)
+                .handle_type(EXTERNAL_SEMAPHORE_HANDLE_TYPE);
+            let handle = unsafe { ext.get_semaphore_win32_handle(&info) }?;
+            Ok(ExternalHandle::Win32(unsafe {
+                std::os::windows::io::OwnedHandle::from_raw_handle(handle as _)
+            }))
         }
     }
 }
 
+impl Event {
+    /// Signals the event from the host; GPU waits then order against host writes.
+    pub fn set(&self) -> Result<(), GPUError> {
+        unsafe { self.inner.device.handle.set_event(self.inner.handle) }?;
+        Ok(())
+    }
+
+    pub fn reset(&self) -> Result<(), GPUError> {
+        unsafe { self.inner.device.handle.reset_event(self.inner.handle) }?;
+        Ok(())
+    }
+
+    pub fn is_set(&self) -> Result<bool, GPUError> {
+        let set = unsafe { self.inner.device.handle.get_event_status(self.inner.handle) }?;
+        Ok(set)
+    }
+}
+
+impl EventImpl {
+    pub fn new(device: RawDevice) -> Result<Self, GPUError> {
+        let info = vk::EventCreateInfo::default();
+        let handle = unsafe { device.handle.create_event(&info, None) }?;
+        Ok(Self { handle, device })
+    }
+}
+
+impl FenceImpl {
+    pub fn new(device: RawDevice, signaled: bool) -> Result<Self, GPUError> {
+        let flags = if signaled {
+            vk::FenceCreateFlags::SIGNALED
+        } else {
+            vk::FenceCreateFlags::empty()
+        };
+        let info = vk::FenceCreateInfo::default().flags(flags);
+        let handle = unsafe {
+            device
+                .handle
+                .create_fence(&info, None)
+                .map_err(GPUError::from)?
+        };
+        Ok(Self { handle, device })
+    }
+}
+
 impl Device {
+    pub fn create_fence(&self, signaled: bool) -> Result<Fence, GPUError> {
+        let inner = FenceImpl::new(self.inner.clone(), signaled)?;
+        Ok(Fence {
+            inner: Arc::new(inner),
+        })
+    }
+
+    /// Waits until all (or, without `wait_all`, any) of `fences` are signaled.
+    ///
+    /// Returns `Ok(false)` if `timeout` elapsed first. An empty slice returns `Ok(true)`.
+    pub fn wait_fences(
+        &self,
+        fences: &[&Fence],
+        wait_all: bool,
+        timeout: Option<Duration>,
+    ) -> Result<bool, GPUError> {
+        if fences.is_empty() {
+            return Ok(true);
+        }
+        let handles = fences
+            .iter()
+            .map(|fence| fence.inner.handle)
+            .collect::<Vec<_>>();
+        unsafe { self.inner.wait_fences(&handles, wait_all, timeout) }
+    }
+
+    /// Creates an unsignaled event for `CommandRecorder::set_event` and `wait_event`.
+    pub fn create_event(&self) -> Result<Event, GPUError> {
+        let inner = EventImpl::new(self.inner.clone())?;
+        Ok(Event {
+            inner: Arc::new(inner),
+        })
+    }
+
     pub fn create_semaphore(&self, value: u64) -> Semaphore {
         let inner = unsafe { SemaphoreImpl::new_timeline(self.inner.clone(), value) };
         Semaphore {
@@ -94,6 +492,36 @@ impl Device {
             inner: Arc::new(inner),
         }
     }
+
+    /// Creates a semaphore that `Semaphore::export_handle` can share with other APIs.
+    ///
+    /// Requires `DeviceFeatures::external_semaphore`; `value` only applies to timelines.
+    pub fn create_exportable_semaphore(
+        &self,
+        kind: SemaphoreKind,
+        value: u64,
+    ) -> Result<Semaphore, GPUError> {
+        let inner = SemaphoreImpl::new_exportable(self.inner.clone(), kind, value)?;
+        Ok(Semaphore {
+            inner: Arc::new(inner),
+        })
+    }
+}
+
+impl Drop for FenceImpl {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.handle.destroy_fence(self.handle, None);
+        }
+    }
+}
+
+impl Drop for EventImpl {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.handle.destroy_event(self.handle, None);
+        }
+    }
 }
 
 impl Drop for SemaphoreImpl {
@@ -103,3 +531,107 @@ impl Drop for SemaphoreImpl {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use ash::vk;
+
+    use crate::{
+        PerFrame, QueueFlags, Semaphore, SemaphoreKind, SubmitInfo,
+        device::{test_device, test_device_with},
+    };
+
+    #[test]
+    fn per_frame_slots_wrap_and_track_submissions() {
+        let mut frames = PerFrame::new(3, |i| i * 10);
+        assert_eq!(*frames.get(1), 10);
+        assert_eq!(*frames.get(5), 20);
+
+        frames.submitted(4, 42);
+        assert_eq!(frames.last_submission(1), 42);
+        assert_eq!(frames.last_submission(0), 0);
+    }
+
+    #[test]
+    #[ignore = "requires a Vulkan device"]
+    fn host_signal_releases_queue_wait() {
+        let (device, queue) = test_device(QueueFlags::GRAPHICS);
+
+        let ready: Semaphore = device.create_semaphore(0);
+        let token = queue
+            .submit_token(SubmitInfo {
+                wait_timeline: &[(&ready, 1, vk::PipelineStageFlags::ALL_COMMANDS)],
+                ..Default::default()
+            })
+            .unwrap();
+
+        assert!(!token.wait_timeout(Duration::from_millis(50)).unwrap());
+
+        let producer = {
+            let ready = ready.clone();
+            thread::spawn(move || ready.signal(1))
+        };
+        producer.join().unwrap();
+
+        assert!(token.wait_timeout(Duration::from_secs(5)).unwrap());
+        assert_eq!(ready.get(), 1);
+    }
+
+    #[test]
+    #[ignore = "requires a Vulkan device"]
+    fn empty_submit_chains_timeline_signals() {
+        let (device, queue) = test_device(QueueFlags::GRAPHICS);
+
+        let first: Semaphore = device.create_semaphore(0);
+        let second: Semaphore = device.create_semaphore(0);
+        let before = queue.timeline().get();
+        let submission = queue
+            .submit(SubmitInfo {
+                wait_timeline: &[(&first, 1, vk::PipelineStageFlags::ALL_COMMANDS)],
+                signal_timeline: &[(&second, 7)],
+                ..Default::default()
+            })
+            .unwrap();
+
+        first.signal(1);
+        queue.wait_for(submission).unwrap();
+
+        assert_eq!(second.get(), 7);
+        assert!(queue.timeline().get() >= submission);
+        assert!(queue.timeline().get() > before);
+    }
+
+    #[test]
+    #[ignore = "requires a Vulkan device"]
+    fn exportable_semaphores_require_the_feature() {
+        let (device, _queue) = test_device_with(QueueFlags::GRAPHICS, |adapter| {
+            let mut info = crate::DeviceCreateInfo::default();
+            info.features = info
+                .features
+                .external_semaphore(adapter.features().external_semaphore);
+            info
+        });
+        let external = device.inner.features.external_semaphore;
+
+        assert!(device.create_semaphore(0).export_handle().is_err());
+        let exported = device
+            .create_exportable_semaphore(SemaphoreKind::Binary, 0)
+            .and_then(|semaphore| semaphore.export_handle());
+        assert_eq!(exported.is_ok(), external);
+    }
+
+    #[test]
+    #[ignore = "requires a Vulkan device"]
+    fn events_toggle_from_the_host() {
+        let (device, _queue) = test_device(QueueFlags::GRAPHICS);
+
+        let event = device.create_event().unwrap();
+        assert!(!event.is_set().unwrap());
+        event.set().unwrap();
+        assert!(event.is_set().unwrap());
+        event.reset().unwrap();
+        assert!(!event.is_set().unwrap());
+    }
+}
@@ -0,0 +1,130 @@
+//! Pieces shared by the egui and imgui renderers.
+
+use ash::vk;
+
+/// Draws `[f32; 2]` position, `[f32; 2]` uv, `[u8; 4]` gamma color vertices given in UI units.
+pub(crate) const SHADER_WGSL: &str = r#"
+struct PushConstants {
+    scale: vec2<f32>,
+    translate: vec2<f32>,
+};
+
+var<push_constant> pc: PushConstants;
+
+struct VertexOutput {
+    @location(0) tex_coord: vec2<f32>,
+    @location(1) color_gamma: vec4<f32>,
+    @builtin(position) position: vec4<f32>,
+};
+
+fn linear_from_gamma_rgb(srgb: vec3<f32>) -> vec3<f32> {
+    let cutoff = srgb < vec3<f32>(0.04045);
+    let lower = srgb / vec3<f32>(12.92);
+    let higher = pow((srgb + vec3<f32>(0.055)) / vec3<f32>(1.055), vec3<f32>(2.4));
+    return select(higher, lower, cutoff);
+}
+
+@vertex
+fn vs_main(
+    @location(0) pos: vec2<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) color: vec4<f32>,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.tex_coord = uv;
+    out.color_gamma = color;
+    out.position = vec4<f32>(pos * pc.scale + pc.translate, 0.0, 1.0);
+    return out;
+}
+
+@group(0) @binding(0) var ui_texture: texture_2d<f32>;
+@group(0) @binding(1) var ui_sampler: sampler;
+
+@fragment
+fn fs_main_gamma(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color_gamma * textureSample(ui_texture, ui_sampler, in.tex_coord);
+}
+
+@fragment
+fn fs_main_linear(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color_gamma = in.color_gamma * textureSample(ui_texture, ui_sampler, in.tex_coord);
+    return vec4<f32>(linear_from_gamma_rgb(color_gamma.rgb), color_gamma.a);
+}
+"#;
+
+/// Maps UI units to clip space as `pos * scale + translate`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct PushConstants {
+    pub scale: [f32; 2],
+    pub translate: [f32; 2],
+}
+
+pub(crate) const VERTEX_STRIDE: u32 = 20;
+
+pub(crate) const VERTEX_ATTRIBUTES: [vk::VertexInputAttributeDescription; 3] = [
+    vk::VertexInputAttributeDescription {
+        location: 0,
+        binding: 0,
+        format: vk::Format::R32G32_SFLOAT,
+        offset: 0,
+    },
+    vk::VertexInputAttributeDescription {
+        location: 1,
+        binding: 0,
+        format: vk::Format::R32G32_SFLOAT,
+        offset: 8,
+    },
+    vk::VertexInputAttributeDescription {
+        location: 2,
+        binding: 0,
+        format: vk::Format::R8G8B8A8_UNORM,
+        offset: 16,
+    },
+];
+
+/// `SHADER_WGSL` fragment entry point that writes colors encoded for `format`.
+pub(crate) fn fragment_entry(format: vk::Format) -> &'static str {
+    if is_srgb_format(format) {
+        "fs_main_linear"
+    } else {
+        "fs_main_gamma"
+    }
+}
+
+/// Rounds `[min_x, min_y, max_x, max_y]` in framebuffer pixels outwards and clamps it to
+/// `extent`, returning `None` if nothing is left.
+pub(crate) fn clip_rect_to_scissor(rect: [f32; 4], extent: vk::Extent2D) -> Option<vk::Rect2D> {
+    let min_x = rect[0].floor().clamp(0.0, extent.width as f32);
+    let min_y = rect[1].floor().clamp(0.0, extent.height as f32);
+    let max_x = rect[2].ceil().clamp(min_x, extent.width as f32);
+    let max_y = rect[3].ceil().clamp(min_y, extent.height as f32);
+
+    if max_x <= min_x || max_y <= min_y {
+        return None;
+    }
+
+    Some(vk::Rect2D {
+        offset: vk::Offset2D {
+            x: min_x as i32,
+            y: min_y as i32,
+        },
+        extent: vk::Extent2D {
+            width: (max_x - min_x) as u32,
+            height: (max_y - min_y) as u32,
+        },
+    })
+}
+
+fn is_srgb_format(format: vk::Format) -> bool {
+    matches!(
+        format,
+        vk::Format::R8_SRGB
+            | vk::Format::R8G8_SRGB
+            | vk::Format::R8G8B8_SRGB
+            | vk::Format::B8G8R8_SRGB
+            | vk::Format::R8G8B8A8_SRGB
+            | vk::Format::B8G8R8A8_SRGB
+            | vk::Format::A8B8G8R8_SRGB_PACK32
+    )
+}
@@ -0,0 +1,102 @@
+use ash::vk;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VertexAttribute {
+    pub location: u32,
+    pub format: vk::Format,
+    pub offset: u32,
+}
+
+/// Implemented by `#[repr(C)]` vertex structs so they can describe their own layout.
+///
+/// ```
+/// #[repr(C)]
+/// #[derive(Clone, Copy)]
+/// struct PosColor {
+///     pos: [f32; 3],
+///     color: [f32; 4],
+/// }
+///
+/// impl tgpu::Vertex for PosColor {
+///     const ATTRIBUTES: &'static [tgpu::VertexAttribute] = &[
+///         tgpu::VertexAttribute {
+///             location: 0,
+///             format: tgpu::Format::R32G32B32_SFLOAT,
+///             offset: std::mem::offset_of!(PosColor, pos) as u32,
+///         },
+///         tgpu::VertexAttribute {
+///             location: 1,
+///             format: tgpu::Format::R32G32B32A32_SFLOAT,
+///             offset: std::mem::offset_of!(PosColor, color) as u32,
+///         },
+///     ];
+/// }
+/// ```
+pub trait Vertex: Copy {
+    const ATTRIBUTES: &'static [VertexAttribute];
+}
+
+/// Owns binding and attribute descriptions for `RenderPipelineInfo::vertex_input_state`.
+#[derive(Debug, Clone, Default)]
+pub struct VertexLayout {
+    bindings: Vec<vk::VertexInputBindingDescription>,
+    attributes: Vec<vk::VertexInputAttributeDescription>,
+}
+
+impl VertexLayout {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts a new binding; following `attribute` calls read from it.
+    pub fn binding(mut self, binding: u32, stride: u32, rate: vk::VertexInputRate) -> Self {
+        self.bindings.push(
+            vk::VertexInputBindingDescription::default()
+                .binding(binding)
+                .stride(stride)
+                .input_rate(rate),
+        );
+        self
+    }
+
+    pub fn attribute(mut self, location: u32, format: vk::Format, offset: u32) -> Self {
+        let binding = self
+            .bindings
+            .last()
+            .expect("VertexLayout::attribute requires a binding")
+            .binding;
+        self.attributes.push(
+            vk::VertexInputAttributeDescription::default()
+                .location(location)
+                .binding(binding)
+                .format(format)
+                .offset(offset),
+        );
+        self
+    }
+
+    /// Adds a per-vertex binding with the stride and attributes of `V`.
+    pub fn vertex<V: Vertex>(self, binding: u32) -> Self {
+        self.with::<V>(binding, vk::VertexInputRate::VERTEX)
+    }
+
+    /// Adds a per-instance binding with the stride and attributes of `V`.
+    pub fn instance<V: Vertex>(self, binding: u32) -> Self {
+        self.with::<V>(binding, vk::VertexInputRate::INSTANCE)
+    }
+
+    fn with<V: Vertex>(self, binding: u32, rate: vk::VertexInputRate) -> Self {
+        let stride = std::mem::size_of::<V>() as u32;
+        let mut layout = self.binding(binding, stride, rate);
+        for attribute in V::ATTRIBUTES {
+            layout = layout.attribute(attribute.location, attribute.format, attribute.offset);
+        }
+        layout
+    }
+
+    pub fn state(&self) -> vk::PipelineVertexInputStateCreateInfo<'_> {
+        vk::PipelineVertexInputStateCreateInfo::default()
+            .vertex_binding_descriptions(&self.bindings)
+            .vertex_attribute_descriptions(&self.attributes)
+    }
+}